@@ -0,0 +1,110 @@
+// vim: cc=81
+use crate::term::ansi::*;
+use serde_json::{json, Value};
+
+/// A local bluetooth controller devices are reachable through
+#[derive(Clone)]
+pub struct Adapter {
+    /// Short name of the adapter like "hci0"
+    pub name: String,
+    pub address: String,
+    /// Name the adapter is visible as to remote devices
+    pub alias: String,
+
+    pub powered: bool,
+    pub discoverable: bool,
+    pub pairable: bool,
+    pub discovering: bool,
+}
+
+impl Adapter {
+    /// Returns whether filter is the name or address of the adapter.
+    pub fn matches(&self, filter: &str) -> bool {
+        self.name == filter || self.address.eq_ignore_ascii_case(filter)
+    }
+
+    /// State flags similar to Device::flags, one character per state:
+    /// P(owered), d(iscoverable), p(airable), s(canning) or "-" if the
+    /// adapter is not in that state.
+    pub fn flags(&self) -> String {
+        [
+            (self.powered, 'P'),
+            (self.discoverable, 'd'),
+            (self.pairable, 'p'),
+            (self.discovering, 's'),
+        ]
+        .iter()
+        .map(|(state, flag)| if *state { *flag } else { '-' })
+        .collect()
+    }
+
+    /// Prints the adapter on one line with its flags, name, address and
+    /// alias.
+    pub fn print_line(&self) {
+        println!(
+            "{} {} {} {}",
+            self.flags(),
+            self.name,
+            self.address,
+            self.alias
+        );
+    }
+
+    /// Will print detailed information about the adapter. States are colored
+    /// if in_color is true.
+    pub fn print_info(&self, in_color: bool) {
+        let (ansi_red, ansi_green, ansi_reset) = if in_color {
+            (ANSI_RED, ANSI_GREEN, ANSI_RESET)
+        } else {
+            ("", "", "")
+        };
+        let mut print_str = format!("{} {}\n\tAlias: {}", self.address, self.name, self.alias);
+        for (prefix, state) in [
+            ("\n\tPowered: ", self.powered),
+            ("\n\tDiscoverable: ", self.discoverable),
+            ("\n\tPairable: ", self.pairable),
+            ("\n\tDiscovering: ", self.discovering),
+        ] {
+            print_str += &format!(
+                "{prefix}{}{}{ansi_reset}",
+                if state { ansi_green } else { ansi_red },
+                if state { "yes" } else { "no" }
+            );
+        }
+        println!("{print_str}");
+    }
+
+    /// Prints a single line with the alias and name of the adapter, whether
+    /// it is powered and whether it is discoverable. States are colored if
+    /// in_color is true.
+    pub fn print_summary(&self, in_color: bool) {
+        let (ansi_red, ansi_green, ansi_reset) = if in_color {
+            (ANSI_RED, ANSI_GREEN, ANSI_RESET)
+        } else {
+            ("", "", "")
+        };
+        let mut print_str = format!("{} ({}): ", self.alias, self.name);
+        if self.powered {
+            print_str += &format!("{ansi_green}powered on{ansi_reset}");
+        } else {
+            print_str += &format!("{ansi_red}powered off{ansi_reset}");
+        }
+        if self.discoverable {
+            print_str += ", discoverable";
+        }
+        println!("{print_str}");
+    }
+
+    /// Returns the adapter as a JSON object
+    pub fn to_json(&self) -> Value {
+        json!({
+            "name": self.name,
+            "address": self.address,
+            "alias": self.alias,
+            "powered": self.powered,
+            "discoverable": self.discoverable,
+            "pairable": self.pairable,
+            "discovering": self.discovering,
+        })
+    }
+}
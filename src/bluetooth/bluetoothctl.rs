@@ -0,0 +1,566 @@
+// vim: cc=81
+use super::{
+    Adapter, BluetoothManager, BtError, Device, DeviceEvent, DeviceEventKind, Devices,
+    DiscoveryFilter, MediaCommand, MediaStatus, MediaTransport, Transport,
+};
+use crate::interrupt;
+use regex::Regex;
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader},
+    process::{Child, Command, Stdio},
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError},
+        Arc, Mutex, OnceLock,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+const BLUETOOTHCTL: &str = "bluetoothctl";
+/// Longest time to wait for output before checking for Ctrl+C
+const INTERRUPT_POLL: Duration = Duration::from_millis(100);
+/// Time between checks of device properties while waiting for a state
+const WAIT_POLL: Duration = Duration::from_millis(500);
+const BLUEZ_NOT_SUPPORTED_ERROR: &str = "org.bluez.Error.NotSupported";
+
+/// Matches color codes and the prompt bluetoothctl prints before lines
+static NOISE: OnceLock<Regex> = OnceLock::new();
+
+/// Returns whether bluetoothctl can be run.
+pub fn is_available() -> bool {
+    Command::new(BLUETOOTHCTL)
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// Removes color codes, carriage returns and prompts from a line printed by
+/// bluetoothctl.
+fn clean_line(line: &str) -> String {
+    let noise = NOISE.get_or_init(|| {
+        Regex::new(r"\x1b\[[0-9;]*[A-Za-z]|\r|\x01|\x02|^\[[^\]]*\]# ").expect("Regex is valid")
+    });
+    // Removing the color codes first makes the prompt visible to the regex
+    let line = noise.replace_all(line, "");
+    noise.replace_all(&line, "").trim().to_string()
+}
+
+/// Runs bluetoothctl with args until it exits. Returns its cleaned output
+/// lines, or None if it could not be run.
+fn run(args: &[&str]) -> Option<Vec<String>> {
+    let output = Command::new(BLUETOOTHCTL)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    Some(
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(clean_line)
+            .filter(|line| !line.is_empty())
+            .collect(),
+    )
+}
+
+/// Runs bluetoothctl with args, converting failures it reports like "Failed
+/// to connect: org.bluez.Error.Failed br-connection-page-timeout" to errors.
+fn run_checked(args: &[&str]) -> Result<(), BtError> {
+    let lines = run(args).ok_or(BtError::ManagerUnavailable)?;
+    for line in &lines {
+        if let Some(start) = line.find("org.bluez.Error.") {
+            let (name, message) = line[start..]
+                .split_once(' ')
+                .unwrap_or((&line[start..], ""));
+            return Err(dbus::Error::new_custom(name, message).into());
+        }
+        if line.contains("not available") {
+            return Err(BtError::NotFound);
+        }
+        if line.starts_with("Failed") {
+            return Err(dbus::Error::new_failed(line).into());
+        }
+    }
+    Ok(())
+}
+
+/// Returns the properties printed by bluetoothctl info or show, e.g. Paired
+/// with the value "yes". Properties given several times (like UUID) keep
+/// their first value.
+fn properties(lines: &[String]) -> HashMap<String, String> {
+    let mut properties = HashMap::new();
+    for line in lines {
+        if let Some((key, value)) = line.split_once(": ") {
+            properties
+                .entry(key.to_string())
+                .or_insert_with(|| value.to_string());
+        }
+    }
+    properties
+}
+
+/// Parses numbers printed either plainly or like "0x64 (100)".
+fn number<T: std::str::FromStr>(value: &str) -> Option<T> {
+    let decimal = match value.split_once('(') {
+        Some((_, rest)) => rest.trim_end_matches(')'),
+        None => value,
+    };
+    decimal.trim().parse().ok()
+}
+
+/// Spawns bluetoothctl with args and returns it with a channel receiving its
+/// cleaned output lines as they are printed.
+fn spawn(args: &[&str]) -> Option<(Child, Receiver<String>)> {
+    let mut child = Command::new(BLUETOOTHCTL)
+        .args(args)
+        // An open stdin keeps bluetoothctl running without arguments
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    let stdout = child.stdout.take()?;
+    let (sender, lines) = mpsc::channel();
+    thread::spawn(move || {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            if sender.send(clean_line(&line)).is_err() {
+                break;
+            }
+        }
+    });
+    Some((child, lines))
+}
+
+/// Splits lines like "[CHG] Device 00:11:22:33:44:55 Connected: yes" into
+/// the tag (CHG), address and the rest.
+fn device_line(line: &str) -> Option<(&str, &str, &str)> {
+    let (tag, rest) = line.strip_prefix('[')?.split_once("] Device ")?;
+    let (address, rest) = rest.split_once(' ').unwrap_or((rest, ""));
+    Some((tag, address, rest))
+}
+
+/// BluetoothManager running bluetoothctl for every operation, as a fallback
+/// where bluetoothd cannot be used over D-Bus directly. Only devices of the
+/// default adapter are available, and pairing only works with devices that
+/// do not require entering or confirming a passkey.
+#[derive(Default)]
+pub struct BluetoothctlManager {
+    devices: Devices<Self>,
+    adapters: Vec<Adapter>,
+    /// Address of the adapter bluetoothctl uses by default
+    default_adapter: Option<String>,
+    /// Name or address of the only adapter to use, all are used if None
+    selected_adapter: Option<String>,
+    discovery_filter: DiscoveryFilter,
+}
+
+impl BluetoothctlManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the device with address as printed by bluetoothctl info, or
+    /// None if it is unknown.
+    fn device_info(&self, address: &str) -> Option<Device<Self>> {
+        let properties = properties(&run(&["info", address])?);
+        let state = |property| properties.get(property).is_some_and(|value| value == "yes");
+        let name = properties
+            .get("Alias")
+            .or(properties.get("Name"))
+            .cloned()
+            .unwrap_or_else(|| address.to_string());
+        let mut device = Device::new(
+            address.to_string(),
+            name,
+            state("Paired"),
+            state("Bonded"),
+            state("Trusted"),
+            state("Blocked"),
+            state("Connected"),
+        );
+        device.remote_name = properties.get("Name").cloned();
+        device.icon = properties.get("Icon").cloned();
+        device.rssi = properties.get("RSSI").and_then(|rssi| number(rssi));
+        device.battery = properties
+            .get("Battery Percentage")
+            .and_then(|battery| number(battery));
+        device.adapters = Vec::from_iter(self.default_adapter.clone());
+        Some(device)
+    }
+
+    /// Returns the name of the device with address and its icon, as known
+    /// from the last update.
+    fn known_name(&self, address: &str) -> (String, Option<String>) {
+        self.devices
+            .iter()
+            .map(|device| device.lock().expect("Mutex should not be poisoned."))
+            .find(|device| device.address == address)
+            .map_or((address.to_string(), None), |device| {
+                (device.name.clone(), device.icon.clone())
+            })
+    }
+
+    /// Runs an adapter command of bluetoothctl like "power on". Only the
+    /// default adapter can be changed. Returns whether it succeeded.
+    fn adapter_command(&self, adapter: &Adapter, args: &[&str]) -> bool {
+        self.default_adapter.as_ref() == Some(&adapter.address)
+            && run(args).is_some_and(|lines| lines.iter().any(|line| line.contains("succeeded")))
+    }
+
+    /// Polls the yes or no property of the device with address until it has
+    /// value, failing with BtError::Timeout once timeout elapsed.
+    fn wait_for_property(
+        &self,
+        address: &str,
+        property: &str,
+        value: bool,
+        timeout: &Duration,
+    ) -> Result<(), BtError> {
+        let _guard = interrupt::guard();
+        let start = Instant::now();
+        let expected = if value { "yes" } else { "no" };
+        loop {
+            let lines = run(&["info", address]).ok_or(BtError::ManagerUnavailable)?;
+            let properties = properties(&lines);
+            // Older versions of bluetoothctl do not print ServicesResolved
+            let state = properties
+                .get(property)
+                .or_else(|| properties.get("Connected"));
+            if state.is_some_and(|state| state == expected) {
+                return Ok(());
+            }
+            if start.elapsed() >= *timeout {
+                return Err(BtError::Timeout);
+            }
+            if interrupt::interrupted() {
+                return Err(BtError::Canceled);
+            }
+            thread::sleep(WAIT_POLL.min(timeout.saturating_sub(start.elapsed())));
+        }
+    }
+}
+
+impl BluetoothManager for BluetoothctlManager {
+    fn update(&mut self) -> &mut Self {
+        self.devices = Vec::new();
+        self.adapters = Vec::new();
+        self.default_adapter = None;
+        // Lines look like "Controller 00:11:22:33:44:55 alias [default]"
+        for line in run(&["list"]).unwrap_or_default() {
+            let Some(rest) = line.strip_prefix("Controller ") else {
+                continue;
+            };
+            let address = rest.split(' ').next().unwrap_or_default().to_string();
+            if rest.ends_with("[default]") {
+                self.default_adapter = Some(address.clone());
+            }
+            let properties = properties(&run(&["show", &address]).unwrap_or_default());
+            let state = |property| properties.get(property).is_some_and(|value| value == "yes");
+            // bluetoothctl does not show names like hci0
+            let adapter = Adapter {
+                name: address.clone(),
+                alias: properties.get("Alias").cloned().unwrap_or_default(),
+                address,
+                powered: state("Powered"),
+                discoverable: state("Discoverable"),
+                pairable: state("Pairable"),
+                discovering: state("Discovering"),
+            };
+            if self
+                .selected_adapter
+                .as_ref()
+                .is_none_or(|filter| adapter.matches(filter))
+            {
+                self.adapters.push(adapter);
+            }
+        }
+        // Devices are only listed for the default adapter
+        let default_in_use = self
+            .adapters
+            .iter()
+            .any(|adapter| Some(&adapter.address) == self.default_adapter.as_ref());
+        if !default_in_use {
+            return self;
+        }
+        for line in run(&["devices"]).unwrap_or_default() {
+            let Some(rest) = line.strip_prefix("Device ") else {
+                continue;
+            };
+            let address = rest.split(' ').next().unwrap_or_default();
+            if let Some(device) = self.device_info(address) {
+                self.devices.push(Arc::new(Mutex::new(device)));
+            }
+        }
+        self
+    }
+
+    fn fork(&self) -> Option<Self> {
+        // Every operation runs its own bluetoothctl anyway
+        Some(Self {
+            devices: Vec::new(),
+            adapters: self.adapters.clone(),
+            default_adapter: self.default_adapter.clone(),
+            selected_adapter: self.selected_adapter.clone(),
+            discovery_filter: self.discovery_filter.clone(),
+        })
+    }
+
+    fn get_all_devices(&self) -> Devices<Self> {
+        self.devices.iter().map(Arc::clone).collect()
+    }
+
+    fn get_all_adapters(&self) -> Vec<Adapter> {
+        self.adapters.clone()
+    }
+
+    fn set_selected_adapter(&mut self, selected_adapter: Option<String>) {
+        self.selected_adapter = selected_adapter;
+    }
+
+    fn set_adapter_alias(&self, adapter: &Adapter, alias: &str) -> bool {
+        if alias.is_empty() {
+            self.adapter_command(adapter, &["reset-alias"])
+        } else {
+            self.adapter_command(adapter, &["system-alias", alias])
+        }
+    }
+
+    fn set_adapter_powered(&self, adapter: &Adapter, powered: bool) -> bool {
+        self.adapter_command(adapter, &["power", if powered { "on" } else { "off" }])
+    }
+
+    fn set_adapter_discoverable(
+        &self,
+        adapter: &Adapter,
+        discoverable: bool,
+        timeout: Option<u32>,
+    ) -> bool {
+        if let Some(timeout) = timeout.filter(|_| discoverable) {
+            if !self.adapter_command(adapter, &["discoverable-timeout", &timeout.to_string()]) {
+                return false;
+            }
+        }
+        self.adapter_command(
+            adapter,
+            &["discoverable", if discoverable { "on" } else { "off" }],
+        )
+    }
+
+    fn set_adapter_pairable(&self, adapter: &Adapter, pairable: bool) -> bool {
+        self.adapter_command(adapter, &["pairable", if pairable { "on" } else { "off" }])
+    }
+
+    fn set_discovery_filter(&mut self, filter: DiscoveryFilter) {
+        self.discovery_filter = filter;
+    }
+
+    fn scan(&self, duration: &Duration) -> &Self {
+        self.scan_live(duration, &mut |_| true)
+    }
+
+    fn scan_live(
+        &self,
+        duration: &Duration,
+        found: &mut dyn FnMut(&Device<Self>) -> bool,
+    ) -> &Self {
+        // Only the transport of the discovery filter can be passed on
+        let mode = match self.discovery_filter.transport {
+            Some(Transport::Le) => "le",
+            Some(Transport::BrEdr) => "bredr",
+            _ => "on",
+        };
+        let Some((mut child, lines)) = spawn(&["scan", mode]) else {
+            return self;
+        };
+        let _guard = interrupt::guard();
+        let start = Instant::now();
+        while !interrupt::interrupted() {
+            let remaining = duration.saturating_sub(start.elapsed());
+            if remaining.is_zero() {
+                break;
+            }
+            let line = match lines.recv_timeout(remaining.min(INTERRUPT_POLL)) {
+                Ok(line) => line,
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
+            let Some((tag, address, change)) = device_line(&line) else {
+                continue;
+            };
+            let relevant = ["RSSI", "Name", "Alias"]
+                .iter()
+                .any(|property| change.starts_with(&format!("{property}: ")));
+            if tag != "NEW" && !(tag == "CHG" && relevant) {
+                continue;
+            }
+            if let Some(device) = self.device_info(address) {
+                if !found(&device) {
+                    break;
+                }
+            }
+        }
+        // bluez stops discovery once bluetoothctl is gone
+        let _ = child.kill();
+        let _ = child.wait();
+        self
+    }
+
+    fn monitor(&self, handle: &mut dyn FnMut(Option<&DeviceEvent>) -> bool) {
+        let Some((mut child, lines)) = spawn(&[]) else {
+            return;
+        };
+        let _guard = interrupt::guard();
+        while !interrupt::interrupted() {
+            let line = match lines.recv_timeout(INTERRUPT_POLL) {
+                Ok(line) => line,
+                Err(RecvTimeoutError::Timeout) => {
+                    if !handle(None) {
+                        break;
+                    }
+                    continue;
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
+            let Some((tag, address, change)) = device_line(&line) else {
+                continue;
+            };
+            let kind = match (tag, change) {
+                ("NEW", _) => DeviceEventKind::Discovered,
+                ("DEL", _) => DeviceEventKind::Unpaired,
+                (_, "Connected: yes") => DeviceEventKind::Connected,
+                (_, "Connected: no") => DeviceEventKind::Disconnected,
+                (_, "Paired: yes") => DeviceEventKind::Paired,
+                (_, change) => match change.strip_prefix("Battery Percentage: ").and_then(number) {
+                    Some(percentage) => DeviceEventKind::Battery(percentage),
+                    None => continue,
+                },
+            };
+            let (name, icon) = self.known_name(address);
+            let event = DeviceEvent {
+                address: address.to_string(),
+                name,
+                icon,
+                kind,
+            };
+            if !handle(Some(&event)) {
+                break;
+            }
+        }
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+
+    fn pair_device(&self, device: &Device<Self>) -> Result<(), BtError> {
+        if device.paired {
+            return Err(BtError::AlreadyPaired);
+        }
+        // Without a way to ask the user, only pairing without passkeys works
+        run_checked(&["--agent", "NoInputNoOutput", "pair", &device.address])
+    }
+
+    fn unpair_device(&self, device: &Device<Self>) -> Result<(), BtError> {
+        run_checked(&["remove", &device.address])
+    }
+
+    fn connect_device(&self, device: &Device<Self>) -> Result<(), BtError> {
+        if device.connected {
+            return Err(BtError::AlreadyConnected);
+        }
+        run_checked(&["connect", &device.address])
+    }
+
+    fn connect_device_profile(&self, device: &Device<Self>, uuid: &str) -> Result<(), BtError> {
+        run_checked(&["connect", &device.address, uuid])
+    }
+
+    fn disconnect_device(&self, device: &Device<Self>) -> Result<(), BtError> {
+        run_checked(&["disconnect", &device.address])
+    }
+
+    fn disconnect_device_profile(&self, device: &Device<Self>, uuid: &str) -> Result<(), BtError> {
+        run_checked(&["disconnect", &device.address, uuid])
+    }
+
+    fn set_device_blocked(&self, device: &Device<Self>, blocked: bool) -> Result<(), BtError> {
+        if device.blocked == blocked {
+            return Ok(());
+        }
+        let command = if blocked { "block" } else { "unblock" };
+        run_checked(&[command, &device.address])
+    }
+
+    fn set_device_trusted(&self, device: &Device<Self>, trusted: bool) -> Result<(), BtError> {
+        if device.trusted == trusted {
+            return Ok(());
+        }
+        let command = if trusted { "trust" } else { "untrust" };
+        run_checked(&[command, &device.address])
+    }
+
+    fn set_device_alias(&self, _device: &Device<Self>, _alias: &str) -> Result<(), BtError> {
+        // set-alias only applies to a device selected in an interactive
+        // session
+        Err(dbus::Error::new_custom(
+            BLUEZ_NOT_SUPPORTED_ERROR,
+            "renaming devices is not supported with bluetoothctl",
+        )
+        .into())
+    }
+
+    fn control_media(&self, _device: &Device<Self>, _command: MediaCommand) -> Result<(), BtError> {
+        // The player menu only applies to an interactive session
+        Err(dbus::Error::new_custom(
+            BLUEZ_NOT_SUPPORTED_ERROR,
+            "media control is not supported with bluetoothctl",
+        )
+        .into())
+    }
+
+    fn media_status(&self, _device: &Device<Self>) -> Result<MediaStatus, BtError> {
+        Err(dbus::Error::new_custom(
+            BLUEZ_NOT_SUPPORTED_ERROR,
+            "media status is not supported with bluetoothctl",
+        )
+        .into())
+    }
+
+    fn media_transports(&self, _device: &Device<Self>) -> Result<Vec<MediaTransport>, BtError> {
+        Err(dbus::Error::new_custom(
+            BLUEZ_NOT_SUPPORTED_ERROR,
+            "audio transports are not supported with bluetoothctl",
+        )
+        .into())
+    }
+
+    fn media_volume(&self, _device: &Device<Self>) -> Result<u16, BtError> {
+        Err(dbus::Error::new_custom(
+            BLUEZ_NOT_SUPPORTED_ERROR,
+            "volume control is not supported with bluetoothctl",
+        )
+        .into())
+    }
+
+    fn set_media_volume(&self, device: &Device<Self>, _volume: u16) -> Result<(), BtError> {
+        self.media_volume(device).map(|_| ())
+    }
+
+    fn wait_for_connected(
+        &self,
+        device: &Device<Self>,
+        connected: bool,
+        timeout: &Duration,
+    ) -> Result<(), BtError> {
+        self.wait_for_property(&device.address, "Connected", connected, timeout)
+    }
+
+    fn wait_for_services_resolved(
+        &self,
+        device: &Device<Self>,
+        timeout: &Duration,
+    ) -> Result<(), BtError> {
+        self.wait_for_property(&device.address, "ServicesResolved", true, timeout)
+    }
+}
@@ -6,7 +6,7 @@ use dbus::blocking;
 
 pub trait OrgBluezAdapter1 {
     fn start_discovery(&self) -> Result<(), dbus::Error>;
-    // fn set_discovery_filter(&self, properties: arg::PropMap) -> Result<(), dbus::Error>;
+    fn set_discovery_filter(&self, properties: arg::PropMap) -> Result<(), dbus::Error>;
     fn stop_discovery(&self) -> Result<(), dbus::Error>;
     fn remove_device(&self, device: dbus::Path) -> Result<(), dbus::Error>;
     // fn get_discovery_filters(&self) -> Result<Vec<String>, dbus::Error>;
@@ -14,17 +14,17 @@ pub trait OrgBluezAdapter1 {
     // fn address_type(&self) -> Result<String, dbus::Error>;
     // fn name(&self) -> Result<String, dbus::Error>;
     // fn alias(&self) -> Result<String, dbus::Error>;
-    // fn set_alias(&self, value: String) -> Result<(), dbus::Error>;
+    fn set_alias(&self, value: String) -> Result<(), dbus::Error>;
     // fn class(&self) -> Result<u32, dbus::Error>;
     // fn powered(&self) -> Result<bool, dbus::Error>;
-    // fn set_powered(&self, value: bool) -> Result<(), dbus::Error>;
+    fn set_powered(&self, value: bool) -> Result<(), dbus::Error>;
     // fn power_state(&self) -> Result<String, dbus::Error>;
     // fn discoverable(&self) -> Result<bool, dbus::Error>;
-    // fn set_discoverable(&self, value: bool) -> Result<(), dbus::Error>;
+    fn set_discoverable(&self, value: bool) -> Result<(), dbus::Error>;
     // fn discoverable_timeout(&self) -> Result<u32, dbus::Error>;
-    // fn set_discoverable_timeout(&self, value: u32) -> Result<(), dbus::Error>;
+    fn set_discoverable_timeout(&self, value: u32) -> Result<(), dbus::Error>;
     // fn pairable(&self) -> Result<bool, dbus::Error>;
-    // fn set_pairable(&self, value: bool) -> Result<(), dbus::Error>;
+    fn set_pairable(&self, value: bool) -> Result<(), dbus::Error>;
     // fn pairable_timeout(&self) -> Result<u32, dbus::Error>;
     // fn set_pairable_timeout(&self, value: u32) -> Result<(), dbus::Error>;
     // fn discovering(&self) -> Result<bool, dbus::Error>;
@@ -42,11 +42,11 @@ impl<'a, T: blocking::BlockingSender, C: ::std::ops::Deref<Target = T>> OrgBluez
     fn start_discovery(&self) -> Result<(), dbus::Error> {
         self.method_call("org.bluez.Adapter1", "StartDiscovery", ())
     }
-    /*
-        fn set_discovery_filter(&self, properties: arg::PropMap) -> Result<(), dbus::Error> {
-            self.method_call("org.bluez.Adapter1", "SetDiscoveryFilter", (properties,))
-        }
-    */
+
+    fn set_discovery_filter(&self, properties: arg::PropMap) -> Result<(), dbus::Error> {
+        self.method_call("org.bluez.Adapter1", "SetDiscoveryFilter", (properties,))
+    }
+
     fn stop_discovery(&self) -> Result<(), dbus::Error> {
         self.method_call("org.bluez.Adapter1", "StopDiscovery", ())
     }
@@ -54,6 +54,51 @@ impl<'a, T: blocking::BlockingSender, C: ::std::ops::Deref<Target = T>> OrgBluez
     fn remove_device(&self, device: dbus::Path) -> Result<(), dbus::Error> {
         self.method_call("org.bluez.Adapter1", "RemoveDevice", (device,))
     }
+
+    fn set_alias(&self, value: String) -> Result<(), dbus::Error> {
+        <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::set(
+            self,
+            "org.bluez.Adapter1",
+            "Alias",
+            value,
+        )
+    }
+
+    fn set_powered(&self, value: bool) -> Result<(), dbus::Error> {
+        <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::set(
+            self,
+            "org.bluez.Adapter1",
+            "Powered",
+            value,
+        )
+    }
+
+    fn set_discoverable(&self, value: bool) -> Result<(), dbus::Error> {
+        <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::set(
+            self,
+            "org.bluez.Adapter1",
+            "Discoverable",
+            value,
+        )
+    }
+
+    fn set_discoverable_timeout(&self, value: u32) -> Result<(), dbus::Error> {
+        <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::set(
+            self,
+            "org.bluez.Adapter1",
+            "DiscoverableTimeout",
+            value,
+        )
+    }
+
+    fn set_pairable(&self, value: bool) -> Result<(), dbus::Error> {
+        <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::set(
+            self,
+            "org.bluez.Adapter1",
+            "Pairable",
+            value,
+        )
+    }
     /*
     fn get_discovery_filters(&self) -> Result<Vec<String>, dbus::Error> {
         self.method_call("org.bluez.Adapter1", "GetDiscoveryFilters", ())
@@ -204,51 +249,6 @@ impl<'a, T: blocking::BlockingSender, C: ::std::ops::Deref<Target = T>> OrgBluez
         )
     }
 
-    fn set_alias(&self, value: String) -> Result<(), dbus::Error> {
-        <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::set(
-            self,
-            "org.bluez.Adapter1",
-            "Alias",
-            value,
-        )
-    }
-
-    fn set_powered(&self, value: bool) -> Result<(), dbus::Error> {
-        <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::set(
-            self,
-            "org.bluez.Adapter1",
-            "Powered",
-            value,
-        )
-    }
-
-    fn set_discoverable(&self, value: bool) -> Result<(), dbus::Error> {
-        <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::set(
-            self,
-            "org.bluez.Adapter1",
-            "Discoverable",
-            value,
-        )
-    }
-
-    fn set_discoverable_timeout(&self, value: u32) -> Result<(), dbus::Error> {
-        <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::set(
-            self,
-            "org.bluez.Adapter1",
-            "DiscoverableTimeout",
-            value,
-        )
-    }
-
-    fn set_pairable(&self, value: bool) -> Result<(), dbus::Error> {
-        <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::set(
-            self,
-            "org.bluez.Adapter1",
-            "Pairable",
-            value,
-        )
-    }
-
     fn set_pairable_timeout(&self, value: u32) -> Result<(), dbus::Error> {
         <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::set(
             self,
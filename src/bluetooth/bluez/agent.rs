@@ -24,7 +24,7 @@ pub trait OrgBluezAgent1 {
         device: dbus::Path<'static>,
         passkey: u32,
     ) -> Result<(), dbus::MethodErr>;
-    fn request_authoritation(&mut self, device: dbus::Path<'static>)
+    fn request_authorization(&mut self, device: dbus::Path<'static>)
         -> Result<(), dbus::MethodErr>;
     fn authorize_service(
         &mut self,
@@ -71,10 +71,10 @@ where
             |_, t: &mut T, (device, passkey)| t.request_confirmation(device, passkey),
         );
         b.method(
-            "RequestAuthoritation",
+            "RequestAuthorization",
             ("device",),
             (),
-            |_, t: &mut T, (device,)| t.request_authoritation(device),
+            |_, t: &mut T, (device,)| t.request_authorization(device),
         );
         b.method(
             "AuthorizeService",
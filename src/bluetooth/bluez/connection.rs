@@ -0,0 +1,155 @@
+// vim: cc=81
+use dbus::{
+    arg::ReadAll,
+    blocking::{BlockingSender, Connection, Proxy},
+    channel::{Sender, Token},
+    message::MatchRule,
+    strings::BusName,
+    Message, Path,
+};
+use std::{
+    fmt,
+    ops::Deref,
+    time::{Duration, Instant},
+};
+
+/// Writes a line of the D-Bus debug log, e.g. to stderr
+pub type LogWriter = fn(&str);
+
+/// Passes D-Bus traffic to a LogWriter, prefixed with the seconds since
+/// debugging was enabled. Does nothing if debugging is disabled.
+#[derive(Clone, Copy)]
+pub struct DebugLog(Option<(Instant, LogWriter)>);
+
+impl DebugLog {
+    fn print(&self, line: fmt::Arguments) {
+        if let Some((start, write)) = self.0 {
+            write(&format!("[{:>10.3}] {line}", start.elapsed().as_secs_f64()));
+        }
+    }
+
+    /// Logs a method call or signal, kind describing which it is.
+    pub fn message(&self, kind: &str, msg: &Message) {
+        self.print(format_args!(
+            "{kind} {} on {}",
+            member_name(msg),
+            msg.path().as_deref().unwrap_or("/")
+        ));
+    }
+
+    /// Logs the reply to a call of the method member.
+    pub fn reply<T>(&self, member: &str, result: &Result<T, dbus::Error>) {
+        match result {
+            Ok(_) => self.print(format_args!("return {member}")),
+            Err(error) => self.print(format_args!(
+                "error {member}: {} {}",
+                error.name().unwrap_or_default(),
+                error.message().unwrap_or_default()
+            )),
+        }
+    }
+}
+
+/// Returns interface and member of msg, e.g. org.bluez.Device1.Connect
+fn member_name(msg: &Message) -> String {
+    format!(
+        "{}.{}",
+        msg.interface().as_deref().unwrap_or_default(),
+        msg.member().as_deref().unwrap_or_default()
+    )
+}
+
+/// Connection to the system bus that logs method calls, their replies and
+/// received signals if debugging is enabled
+pub struct BusConnection {
+    connection: Connection,
+    log: DebugLog,
+}
+
+impl BusConnection {
+    pub fn new_system() -> Result<Self, dbus::Error> {
+        Ok(Self {
+            connection: Connection::new_system()?,
+            log: DebugLog(None),
+        })
+    }
+
+    /// Opens another connection to the system bus, logging like this one.
+    pub fn fork(&self) -> Result<Self, dbus::Error> {
+        let mut connection = Self::new_system()?;
+        connection.log = self.log;
+        Ok(connection)
+    }
+
+    /// Enables logging of D-Bus traffic with write, or disables it if write
+    /// is None.
+    pub fn set_debug(&mut self, write: Option<LogWriter>) {
+        self.log = DebugLog(write.map(|write| (Instant::now(), write)));
+    }
+
+    /// Adds a match for signals like Connection::add_match, logging the
+    /// signals passed to f. Signals are logged by the handlers they are
+    /// dispatched to, so debugging does not change which handler gets them.
+    pub fn add_match<S: ReadAll, F>(
+        &self,
+        match_rule: MatchRule<'static>,
+        mut f: F,
+    ) -> Result<Token, dbus::Error>
+    where
+        F: FnMut(S, &Connection, &Message) -> bool + Send + 'static,
+    {
+        let log = self.log;
+        self.connection.add_match(
+            match_rule,
+            move |signal: S, conn: &Connection, msg: &Message| {
+                log.message("signal", msg);
+                f(signal, conn, msg)
+            },
+        )
+    }
+
+    /// Returns the log used for D-Bus traffic, e.g. for handlers of replies
+    /// or incoming method calls.
+    pub fn debug_log(&self) -> DebugLog {
+        self.log
+    }
+
+    /// Creates a proxy whose method calls are logged.
+    pub fn with_proxy<'a, 'b, D: Into<BusName<'a>>, P: Into<Path<'a>>>(
+        &'b self,
+        dest: D,
+        path: P,
+        timeout: Duration,
+    ) -> Proxy<'a, &'b Self> {
+        Proxy::new(dest, path, timeout, self)
+    }
+
+    /// Sends msg without waiting for a reply. Returns the serial of the
+    /// message to recognize the reply by.
+    pub fn send(&self, msg: Message) -> Result<u32, ()> {
+        self.log.message("call", &msg);
+        self.connection.send(msg)
+    }
+}
+
+impl Deref for BusConnection {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        &self.connection
+    }
+}
+
+impl BlockingSender for BusConnection {
+    fn send_with_reply_and_block(
+        &self,
+        msg: Message,
+        timeout: Duration,
+    ) -> Result<Message, dbus::Error> {
+        let member = member_name(&msg);
+        self.log.message("call", &msg);
+        let result = self.connection.send_with_reply_and_block(msg, timeout);
+        self.log.reply(&member, &result);
+        result
+    }
+}
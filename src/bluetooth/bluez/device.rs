@@ -7,24 +7,24 @@ use dbus::blocking;
 pub trait OrgBluezDevice1 {
     fn disconnect(&self) -> Result<(), dbus::Error>;
     fn connect(&self) -> Result<(), dbus::Error>;
-    // fn connect_profile(&self, uuid: &str) -> Result<(), dbus::Error>;
-    // fn disconnect_profile(&self, uuid: &str) -> Result<(), dbus::Error>;
+    fn connect_profile(&self, uuid: &str) -> Result<(), dbus::Error>;
+    fn disconnect_profile(&self, uuid: &str) -> Result<(), dbus::Error>;
     fn pair(&self) -> Result<(), dbus::Error>;
-    // fn cancel_pairing(&self) -> Result<(), dbus::Error>;
+    fn cancel_pairing(&self) -> Result<(), dbus::Error>;
     // fn address(&self) -> Result<String, dbus::Error>;
     // fn address_type(&self) -> Result<String, dbus::Error>;
     // fn name(&self) -> Result<String, dbus::Error>;
     // fn alias(&self) -> Result<String, dbus::Error>;
-    // fn set_alias(&self, value: String) -> Result<(), dbus::Error>;
+    fn set_alias(&self, value: String) -> Result<(), dbus::Error>;
     // fn class(&self) -> Result<u32, dbus::Error>;
     // fn appearance(&self) -> Result<u16, dbus::Error>;
     // fn icon(&self) -> Result<String, dbus::Error>;
     // fn paired(&self) -> Result<bool, dbus::Error>;
     // fn bonded(&self) -> Result<bool, dbus::Error>;
     // fn trusted(&self) -> Result<bool, dbus::Error>;
-    // fn set_trusted(&self, value: bool) -> Result<(), dbus::Error>;
+    fn set_trusted(&self, value: bool) -> Result<(), dbus::Error>;
     // fn blocked(&self) -> Result<bool, dbus::Error>;
-    // fn set_blocked(&self, value: bool) -> Result<(), dbus::Error>;
+    fn set_blocked(&self, value: bool) -> Result<(), dbus::Error>;
     // fn legacy_pairing(&self) -> Result<bool, dbus::Error>;
     // fn rssi(&self) -> Result<i16, dbus::Error>;
     // fn connected(&self) -> Result<bool, dbus::Error>;
@@ -57,23 +57,22 @@ impl<'a, T: blocking::BlockingSender, C: ::std::ops::Deref<Target = T>> OrgBluez
     fn connect(&self) -> Result<(), dbus::Error> {
         self.method_call("org.bluez.Device1", "Connect", ())
     }
-    /*
-        fn connect_profile(&self, uuid: &str) -> Result<(), dbus::Error> {
-            self.method_call("org.bluez.Device1", "ConnectProfile", (uuid,))
-        }
 
-        fn disconnect_profile(&self, uuid: &str) -> Result<(), dbus::Error> {
-            self.method_call("org.bluez.Device1", "DisconnectProfile", (uuid,))
-        }
-    */
+    fn connect_profile(&self, uuid: &str) -> Result<(), dbus::Error> {
+        self.method_call("org.bluez.Device1", "ConnectProfile", (uuid,))
+    }
+
+    fn disconnect_profile(&self, uuid: &str) -> Result<(), dbus::Error> {
+        self.method_call("org.bluez.Device1", "DisconnectProfile", (uuid,))
+    }
+
     fn pair(&self) -> Result<(), dbus::Error> {
         self.method_call("org.bluez.Device1", "Pair", ())
     }
+    fn cancel_pairing(&self) -> Result<(), dbus::Error> {
+        self.method_call("org.bluez.Device1", "CancelPairing", ())
+    }
     /*
-        fn cancel_pairing(&self) -> Result<(), dbus::Error> {
-            self.method_call("org.bluez.Device1", "CancelPairing", ())
-        }
-
         fn address(&self) -> Result<String, dbus::Error> {
             <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::get(
                 self,
@@ -209,6 +208,33 @@ impl<'a, T: blocking::BlockingSender, C: ::std::ops::Deref<Target = T>> OrgBluez
             "Adapter",
         )
     }
+
+    fn set_alias(&self, value: String) -> Result<(), dbus::Error> {
+        <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::set(
+            self,
+            "org.bluez.Device1",
+            "Alias",
+            value,
+        )
+    }
+
+    fn set_blocked(&self, value: bool) -> Result<(), dbus::Error> {
+        <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::set(
+            self,
+            "org.bluez.Device1",
+            "Blocked",
+            value,
+        )
+    }
+
+    fn set_trusted(&self, value: bool) -> Result<(), dbus::Error> {
+        <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::set(
+            self,
+            "org.bluez.Device1",
+            "Trusted",
+            value,
+        )
+    }
     /*
     fn manufacturer_data(
         &self,
@@ -265,33 +291,6 @@ impl<'a, T: blocking::BlockingSender, C: ::std::ops::Deref<Target = T>> OrgBluez
         )
     }
 
-    fn set_alias(&self, value: String) -> Result<(), dbus::Error> {
-        <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::set(
-            self,
-            "org.bluez.Device1",
-            "Alias",
-            value,
-        )
-    }
-
-    fn set_trusted(&self, value: bool) -> Result<(), dbus::Error> {
-        <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::set(
-            self,
-            "org.bluez.Device1",
-            "Trusted",
-            value,
-        )
-    }
-
-    fn set_blocked(&self, value: bool) -> Result<(), dbus::Error> {
-        <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::set(
-            self,
-            "org.bluez.Device1",
-            "Blocked",
-            value,
-        )
-    }
-
     fn set_wake_allowed(&self, value: bool) -> Result<(), dbus::Error> {
         <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::set(
             self,
@@ -0,0 +1,149 @@
+// vim: cc=81
+use super::{DBusBluetoothManager, BLUEZ_DBUS, DBUS_TIMEOUT};
+use crate::bluetooth::BluetoothManager;
+use dbus::blocking::{stdintf::org_freedesktop_dbus::ObjectManager, Connection};
+use std::{fs, path::Path};
+
+const RFKILL_DIR: &str = "/sys/class/rfkill";
+
+/// Result of an environment check
+pub struct Check {
+    pub message: String,
+    /// How to fix the problem if the check failed, None if it passed
+    pub fix: Option<String>,
+}
+
+/// Collects the results of environment checks
+#[derive(Default)]
+struct Report {
+    checks: Vec<Check>,
+}
+
+impl Report {
+    fn ok(&mut self, message: &str) {
+        self.checks.push(Check {
+            message: message.to_string(),
+            fix: None,
+        });
+    }
+
+    fn fail(&mut self, message: &str, fix: &str) {
+        self.checks.push(Check {
+            message: message.to_string(),
+            fix: Some(fix.to_string()),
+        });
+    }
+}
+
+/// Checks whether the system is set up for bt to work: the system bus is
+/// reachable, bluetoothd is running and accessible, and an unblocked, powered
+/// adapter exists. Returns the result of each check in order. Checks
+/// depending on a failed one are skipped.
+pub fn diagnose() -> Vec<Check> {
+    let mut report = Report::default();
+    let connection = match Connection::new_system() {
+        Ok(connection) => {
+            report.ok("Connected to the system D-Bus");
+            connection
+        }
+        Err(error) => {
+            report.fail(
+                &format!("Cannot connect to the system D-Bus: {error}"),
+                "start the D-Bus system daemon, e.g. sudo systemctl start dbus",
+            );
+            return report.checks;
+        }
+    };
+
+    let bluez_running = connection
+        .with_proxy(
+            "org.freedesktop.DBus",
+            "/org/freedesktop/DBus",
+            DBUS_TIMEOUT,
+        )
+        .method_call("org.freedesktop.DBus", "NameHasOwner", (BLUEZ_DBUS,))
+        .is_ok_and(|(has_owner,): (bool,)| has_owner);
+    if bluez_running {
+        report.ok("bluetoothd is running");
+    } else {
+        report.fail(
+            "bluetoothd is not running",
+            "sudo systemctl enable --now bluetooth",
+        );
+        return report.checks;
+    }
+
+    match connection
+        .with_proxy(BLUEZ_DBUS, "/", DBUS_TIMEOUT)
+        .get_managed_objects()
+    {
+        Ok(_) => report.ok("D-Bus policy allows access to bluetoothd"),
+        Err(error) => {
+            report.fail(
+                &format!("Cannot access bluetoothd: {error}"),
+                "check the D-Bus policy in /etc/dbus-1/system.d/bluetooth.conf \
+                or add your user to the group it allows (usually bluetooth)",
+            );
+            return report.checks;
+        }
+    }
+
+    check_rfkill(&mut report);
+
+    let adapters = match DBusBluetoothManager::new() {
+        Ok(mut manager) => manager.update().get_all_adapters(),
+        Err(_) => Vec::new(),
+    };
+    if adapters.is_empty() {
+        report.fail(
+            "No bluetooth adapter found",
+            "plug in an adapter or check that its kernel driver is loaded \
+            (e.g. lsmod | grep btusb)",
+        );
+    }
+    for adapter in adapters {
+        if adapter.powered {
+            report.ok(&format!("Adapter {} is powered", adapter.name));
+        } else {
+            report.fail(
+                &format!("Adapter {} is not powered", adapter.name),
+                &format!("bt adapter power on {}", adapter.name),
+            );
+        }
+    }
+    report.checks
+}
+
+/// Checks the soft and hard blocks of all bluetooth rfkill switches. Systems
+/// without rfkill support are skipped.
+fn check_rfkill(report: &mut Report) {
+    let Ok(entries) = fs::read_dir(RFKILL_DIR) else {
+        return;
+    };
+    let read = |dir: &Path, file| {
+        fs::read_to_string(dir.join(file))
+            .map(|content| content.trim().to_string())
+            .unwrap_or_default()
+    };
+    for entry in entries.flatten() {
+        let dir = entry.path();
+        if read(&dir, "type") != "bluetooth" {
+            continue;
+        }
+        let name = read(&dir, "name");
+        if read(&dir, "hard") == "1" {
+            report.fail(
+                &format!("{name} is hard blocked by rfkill"),
+                "enable bluetooth with the hardware switch or in the firmware \
+                settings",
+            );
+        } else if read(&dir, "soft") == "1" {
+            report.fail(
+                &format!("{name} is soft blocked by rfkill"),
+                "rfkill unblock bluetooth",
+            );
+        } else {
+            report.ok(&format!("{name} is not blocked by rfkill"));
+        }
+    }
+}
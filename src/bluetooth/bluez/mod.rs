@@ -2,29 +2,41 @@
 pub mod adapter;
 pub mod agent;
 pub mod agent_manager;
+mod connection;
 pub mod device;
+pub mod doctor;
 
 use agent_manager::OrgBluezAgentManager1;
+use connection::BusConnection;
 
-use super::{BluetoothManager, Device, Devices};
-use crate::utils::ansi::ANSI_RESET;
+use super::{
+    media, uuids, Adapter, BluetoothManager, BtError, Device, DeviceEvent, DeviceEventKind,
+    Devices, DiscoveryFilter, MediaCommand, MediaStatus, MediaTransport,
+};
+use crate::interrupt;
 use adapter::OrgBluezAdapter1;
 use agent::OrgBluezAgent1;
 use dbus::{
-    arg::prop_cast,
-    blocking::{stdintf::org_freedesktop_dbus::ObjectManager, Connection, Proxy},
-    channel::{MatchingReceiver, Sender, Token},
-    message::MatchRule,
+    arg::{prop_cast, PropMap, RefArg, Variant},
+    blocking::{
+        stdintf::org_freedesktop_dbus::{
+            ObjectManager, ObjectManagerInterfacesAdded, Properties, PropertiesPropertiesChanged,
+        },
+        Connection, Proxy,
+    },
+    channel::{MatchingReceiver, Token},
+    message::{MatchRule, SignalArgs},
     Message, Path,
 };
 use dbus_crossroads::Crossroads;
 use device::OrgBluezDevice1;
 use std::{
-    collections::HashMap,
-    io::{self, Read, Write},
+    collections::{HashMap, HashSet},
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, IsTerminal, Read, Write},
     sync::{Arc, Mutex},
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 pub const BLUEZ_DBUS: &str = "org.bluez";
@@ -32,76 +44,630 @@ pub const BLUEZ_DBUS: &str = "org.bluez";
 pub const ADAPTER_INTERFACE: &str = "org.bluez.Adapter1";
 pub const DEVICE_INTERFACE: &str = "org.bluez.Device1";
 pub const BATTERY_INTERFACE: &str = "org.bluez.Battery1";
+pub const MEDIA_PLAYER_INTERFACE: &str = "org.bluez.MediaPlayer1";
+pub const MEDIA_TRANSPORT_INTERFACE: &str = "org.bluez.MediaTransport1";
 
 const DBUS_TIMEOUT: Duration = Duration::new(60, 0);
+/// IO capabilities the pairing agent can register with, deciding which
+/// pairing method bluez negotiates
+pub const AGENT_CAPABILITIES: [&str; 5] = [
+    "DisplayOnly",
+    "DisplayYesNo",
+    "KeyboardOnly",
+    "NoInputNoOutput",
+    "KeyboardDisplay",
+];
+/// Longest time to block on messages before checking for Ctrl+C
+const INTERRUPT_POLL: Duration = Duration::from_millis(100);
 
 const BLUEZ_REJECTED_ERROR: &str = "org.bluez.Error.Rejected";
 const BLUEZ_CANCELED_ERROR: &str = "org.bluez.Error.Canceled";
+const BLUEZ_IN_PROGRESS_ERROR: &str = "org.bluez.Error.InProgress";
+const BLUEZ_NOT_AVAILABLE_ERROR: &str = "org.bluez.Error.NotAvailable";
+
+/// Messages of bluez errors caused by the radio link rather than the request,
+/// which often go away when trying again
+const TRANSIENT_ERROR_MESSAGES: [&str; 6] = [
+    "le-connection-abort-by-local",
+    "br-connection-page-timeout",
+    "br-connection-busy",
+    "Page Timeout",
+    "Software caused connection abort",
+    "Host is down",
+];
+
+/// Returns whether error is worth retrying the operation for.
+fn is_transient_error(error: &dbus::Error) -> bool {
+    error.name() == Some(BLUEZ_IN_PROGRESS_ERROR)
+        || error.message().is_some_and(|message| {
+            TRANSIENT_ERROR_MESSAGES
+                .iter()
+                .any(|transient| message.contains(transient))
+        })
+}
+
+/// Where the pairing agent gets pin codes and passkeys from
+#[derive(Clone)]
+pub enum SecretSource {
+    /// Interactively prompt the user on stdin
+    Prompt,
+    /// Read the first line of the file descriptor, which is done once when
+    /// the source is set
+    Fd(u32),
+    /// Answer with a fixed value, e.g. given on the command line
+    Value(String),
+}
+
+impl SecretSource {
+    /// Reads the first line of the file descriptor of SecretSource::Fd into
+    /// a SecretSource::Value, as the line is consumed from pipes and could
+    /// not be read again for another request or retry. Other sources are
+    /// returned unchanged. If reading fails, requests are rejected.
+    fn read_fd(self) -> SecretSource {
+        let SecretSource::Fd(fd) = self else {
+            return self;
+        };
+        let mut secret = Vec::new();
+        // Opening /dev/fd/N reads from the same pipe or file as the
+        // descriptor. Reading single bytes leaves anything after the first
+        // line in it.
+        if let Ok(mut file) = File::open(format!("/dev/fd/{fd}")) {
+            let mut byte = [0u8];
+            while let Ok(1) = file.read(&mut byte) {
+                if byte[0] == b'\n' {
+                    break;
+                }
+                secret.push(byte[0]);
+            }
+        }
+        let secret = String::from_utf8_lossy(&secret);
+        SecretSource::Value(secret.trim_end_matches('\r').to_string())
+    }
+
+    /// Returns the secret of a non-interactive source, or None for
+    /// SecretSource::Prompt.
+    fn read(&self) -> Option<String> {
+        match self {
+            SecretSource::Prompt | SecretSource::Fd(_) => None,
+            SecretSource::Value(secret) => Some(secret.clone()),
+        }
+    }
+}
 
 pub struct DBusBluetoothManager {
-    connection: Connection,
+    connection: BusConnection,
     address_dbus_paths: HashMap<String, Path<'static>>,
     devices: Devices<Self>,
     adapter_paths: Vec<Path<'static>>,
-    scan_display_hint: bool,
+    adapters: Vec<Adapter>,
+    /// Name or address of the only adapter to use, all are used if None
+    selected_adapter: Option<String>,
+    discovery_filter: DiscoveryFilter,
+    pin_source: SecretSource,
+    passkey_source: SecretSource,
+    /// IO capability of the pairing agent, chosen by agent_capability if None
+    agent_capability: Option<String>,
+    /// Whether the pairing agent accepts confirmations without asking and
+    /// rejects requests it would have to prompt for
+    no_input: bool,
+    /// Whether the agent of run_agent accepts all requests without asking,
+    /// including pairings and services of devices that are not paired
+    accept_all: bool,
+    /// Set by the pairing agent to the error to report if no_input kept it
+    /// from answering a request
+    agent_error: Arc<Mutex<Option<BtError>>>,
+    /// Time after which a pairing that has not completed is canceled
+    pair_timeout: Duration,
+    /// How often connecting and pairing are retried on transient errors
+    retries: u32,
+    /// Delay before the first retry, doubled for each following one
+    retry_delay: Duration,
 }
 
 impl DBusBluetoothManager {
     pub fn new() -> Result<Self, dbus::Error> {
-        let connection = Connection::new_system()?;
+        let connection = BusConnection::new_system()?;
         Ok(Self {
             connection,
             address_dbus_paths: HashMap::new(),
             devices: Vec::new(),
             adapter_paths: Vec::new(),
-            scan_display_hint: true,
+            adapters: Vec::new(),
+            selected_adapter: None,
+            discovery_filter: DiscoveryFilter::default(),
+            pin_source: SecretSource::Prompt,
+            passkey_source: SecretSource::Prompt,
+            agent_capability: None,
+            no_input: false,
+            accept_all: false,
+            agent_error: Arc::default(),
+            pair_timeout: DBUS_TIMEOUT,
+            retries: 0,
+            retry_delay: Duration::from_secs(1),
         })
     }
 
     fn _create_device_proxy<'a: 'b, 'b>(
         &'a self,
         address: &'b str,
-    ) -> Option<Proxy<'b, &'a Connection>> {
+    ) -> Option<Proxy<'b, &'a BusConnection>> {
         self.address_dbus_paths
             .get(address)
             .and_then(|path| Some(self.connection.with_proxy(BLUEZ_DBUS, path, DBUS_TIMEOUT)))
     }
 
-    /// Creates a DBusBluetoothAgent for the device with address.
-    fn _create_agent(&self, device: &Device<Self>) -> Option<DBusBluetoothAgent> {
-        let device_path = self.address_dbus_paths.get(&device.address).cloned()?;
+    /// Returns the path of the media player of the device with address,
+    /// which bluez creates while the device offers playback control. The
+    /// first one is used if there are several.
+    fn _media_player_path(&self, address: &str) -> Option<Path<'static>> {
+        let prefix = format!("{}/", self.address_dbus_paths.get(address)?);
+        let objects = self
+            .connection
+            .with_proxy(BLUEZ_DBUS, "/", DBUS_TIMEOUT)
+            .get_managed_objects()
+            .ok()?;
+        objects
+            .into_iter()
+            .filter(|(path, interfaces)| {
+                path.starts_with(&prefix) && interfaces.contains_key(MEDIA_PLAYER_INTERFACE)
+            })
+            .map(|(path, _)| path)
+            .min_by_key(|path| path.to_string())
+    }
+
+    /// Returns the path of the media player of device, failing if it is not
+    /// connected or has no media player.
+    fn _media_player(&self, device: &Device<Self>) -> Result<Path<'static>, BtError> {
+        if !device.connected {
+            return Err(BtError::NotConnected);
+        }
+        self._media_player_path(&device.address).ok_or_else(|| {
+            dbus::Error::new_custom(
+                BLUEZ_NOT_AVAILABLE_ERROR,
+                "no media player, does the device support playback control?",
+            )
+            .into()
+        })
+    }
+
+    /// Returns the paths and properties of the media transports (audio
+    /// streams) of the device with address, which exist while audio profiles
+    /// are connected.
+    fn _media_transports(&self, address: &str) -> Vec<(Path<'static>, PropMap)> {
+        let Some(device_path) = self.address_dbus_paths.get(address) else {
+            return Vec::new();
+        };
+        let Ok(objects) = self
+            .connection
+            .with_proxy(BLUEZ_DBUS, "/", DBUS_TIMEOUT)
+            .get_managed_objects()
+        else {
+            return Vec::new();
+        };
+        let mut transports: Vec<_> = objects
+            .into_iter()
+            .filter_map(|(path, mut interfaces)| {
+                let props = interfaces.remove(MEDIA_TRANSPORT_INTERFACE)?;
+                let device = prop_cast::<Path>(&props, "Device")?;
+                (device == device_path).then_some((path, props))
+            })
+            .collect();
+        transports.sort_by_key(|(path, _)| path.to_string());
+        transports
+    }
+
+    /// Returns the path and volume of the first media transport of device
+    /// supporting absolute volume.
+    fn _volume_transport(&self, device: &Device<Self>) -> Result<(Path<'static>, u16), BtError> {
+        if !device.connected {
+            return Err(BtError::NotConnected);
+        }
+        self._media_transports(&device.address)
+            .into_iter()
+            .find_map(|(path, props)| Some((path, *prop_cast::<u16>(&props, "Volume")?)))
+            .ok_or_else(|| {
+                dbus::Error::new_custom(
+                    BLUEZ_NOT_AVAILABLE_ERROR,
+                    "no audio stream with volume control, is audio playing?",
+                )
+                .into()
+            })
+    }
+
+    fn _create_adapter_proxy<'a: 'b, 'b>(
+        &'a self,
+        adapter: &Adapter,
+    ) -> Option<Proxy<'b, &'a BusConnection>> {
+        self.adapter_paths
+            .iter()
+            .find(|path| path.rsplit('/').next() == Some(adapter.name.as_str()))
+            .map(|path| self.connection.with_proxy(BLUEZ_DBUS, path, DBUS_TIMEOUT))
+    }
+
+    /// Applies the discovery filter, if any, to the adapter behind proxy and
+    /// starts discovery. Returns whether the adapter is discovering.
+    fn _start_discovery(&self, proxy: &Proxy<&BusConnection>) -> bool {
+        let mut properties = PropMap::new();
+        if let Some(transport) = self.discovery_filter.transport {
+            properties.insert(
+                "Transport".to_string(),
+                Variant(Box::new(transport.name().to_string()) as Box<dyn RefArg>),
+            );
+        }
+        if let Some(rssi) = self.discovery_filter.rssi {
+            properties.insert(
+                "RSSI".to_string(),
+                Variant(Box::new(rssi) as Box<dyn RefArg>),
+            );
+        }
+        if let Some(pathloss) = self.discovery_filter.pathloss {
+            properties.insert(
+                "Pathloss".to_string(),
+                Variant(Box::new(pathloss) as Box<dyn RefArg>),
+            );
+        }
+        if !self.discovery_filter.uuids.is_empty() {
+            properties.insert(
+                "UUIDs".to_string(),
+                Variant(Box::new(self.discovery_filter.uuids.clone()) as Box<dyn RefArg>),
+            );
+        }
+        // Bluez keeps a filter per client, so there is nothing to reset
+        // without one. A rejected filter (e.g. by old bluez versions) still
+        // leaves an unfiltered scan.
+        if !properties.is_empty() {
+            let _ = proxy.set_discovery_filter(properties);
+        }
+        proxy.start_discovery().is_ok()
+    }
+
+    /// Creates a DBusBluetoothAgent handling requests of device, or of all
+    /// devices if None.
+    fn _create_agent(&self, device: Option<&Device<Self>>) -> Option<DBusBluetoothAgent> {
+        let mut device_names = HashMap::new();
+        let mut paired_paths = HashSet::new();
+        let device_path = match device {
+            Some(device) => {
+                let path = self.address_dbus_paths.get(&device.address).cloned()?;
+                device_names.insert(path.clone(), device.get_name_colored());
+                Some(path)
+            }
+            // Only locked here, as a single device is locked while pairing
+            None => {
+                for device in &self.devices {
+                    let device = device.lock().expect("Mutex should not be poisoned.");
+                    if let Some(path) = self.address_dbus_paths.get(&device.address) {
+                        device_names.insert(path.clone(), device.get_name_colored());
+                        if device.paired {
+                            paired_paths.insert(path.clone());
+                        }
+                    }
+                }
+                None
+            }
+        };
         Some(DBusBluetoothAgent {
-            device_name: device.get_name_colored(),
             device_path,
+            device_names,
+            paired_paths,
+            pin_source: self.pin_source.clone(),
+            passkey_source: self.passkey_source.clone(),
+            no_input: self.no_input,
+            // Only the agent for requests of all devices accepts everything
+            accept_all: self.accept_all && device.is_none(),
+            error: Arc::clone(&self.agent_error),
         })
     }
 
-    /// Creates a DBusBluetoothAgent and registers it with self.connection
-    fn _register_agent(&self, device: &Device<Self>) -> Option<Token> {
+    /// Creates a DBusBluetoothAgent for device like _create_agent and
+    /// registers it with self.connection
+    fn _register_agent(&self, device: Option<&Device<Self>>) -> Result<Token, dbus::Error> {
         let mut cr = Crossroads::new();
         let iface_token = agent::register_org_bluez_agent1(&mut cr);
-        let agent = self._create_agent(device)?;
+        let agent = self
+            ._create_agent(device)
+            .ok_or_else(|| dbus::Error::new_failed("Device not found"))?;
 
         cr.insert("/agent", &[iface_token], agent);
-        let token = Some(self.connection.start_receive(
+        let log = self.connection.debug_log();
+        let token = self.connection.start_receive(
             MatchRule::new_method_call().with_path("/agent\0"),
-            Box::new(move |msg, conn| cr.handle_message(msg, conn).is_ok()),
-        ));
+            Box::new(move |msg, conn| {
+                log.message("agent call", &msg);
+                cr.handle_message(msg, conn).is_ok()
+            }),
+        );
         match self
             .connection
             .with_proxy(BLUEZ_DBUS, "/org/bluez", DBUS_TIMEOUT)
-            .register_agent("/agent\0".into(), "KeyboardDisplay")
+            .register_agent("/agent\0".into(), &self.agent_capability())
         {
-            Ok(_) => token,
-            Err(_) => {
-                self.connection.stop_receive(token?);
-                None
+            Ok(_) => Ok(token),
+            Err(error) => {
+                self.connection.stop_receive(token);
+                Err(error)
             }
         }
     }
 
-    pub fn set_scan_display_hint(&mut self, scan_display_hint: bool) {
-        self.scan_display_hint = scan_display_hint;
+    /// Returns the IO capability to register the agent with. Unless set, it
+    /// can only enter and display codes if there is a terminal to prompt on
+    /// and prompting is allowed, or if a pin code or passkey was given.
+    fn agent_capability(&self) -> String {
+        if let Some(capability) = &self.agent_capability {
+            capability.clone()
+        } else if (!self.no_input && can_prompt())
+            || !matches!(self.pin_source, SecretSource::Prompt)
+            || !matches!(self.passkey_source, SecretSource::Prompt)
+        {
+            "KeyboardDisplay".to_string()
+        } else {
+            "NoInputNoOutput".to_string()
+        }
+    }
+
+    /// Unregisters the agent registered by _register_agent
+    fn _unregister_agent(&self, token: Token) {
+        let _ = self
+            .connection
+            .with_proxy(BLUEZ_DBUS, "/org/bluez", DBUS_TIMEOUT)
+            .unregister_agent("/agent\0".into());
+        self.connection.stop_receive(token);
+    }
+
+    /// Creates a device from the properties of its Device1 interface.
+    fn device_from_props(d_props: &PropMap) -> Device<Self> {
+        let address = prop_cast::<String>(d_props, "Address")
+            .cloned()
+            .expect("Address is required");
+        // alias is used for device.name, not device.name
+        let alias = prop_cast::<String>(d_props, "Alias")
+            .cloned()
+            .expect("Alias is required");
+        let paired = prop_cast::<bool>(d_props, "Paired")
+            .cloned()
+            .expect("Paired is required");
+        let bonded = prop_cast::<bool>(d_props, "Bonded")
+            .cloned()
+            .expect("Bonded is required");
+        let trusted = prop_cast::<bool>(d_props, "Trusted")
+            .cloned()
+            .expect("Trusted is required");
+        let blocked = prop_cast::<bool>(d_props, "Blocked")
+            .cloned()
+            .expect("Blocked is required");
+        let connected = prop_cast::<bool>(d_props, "Connected")
+            .cloned()
+            .expect("Connected is required");
+        let mut device = Device::new(address, alias, paired, bonded, trusted, blocked, connected);
+        device.remote_name = prop_cast::<String>(d_props, "Name").cloned();
+        device.icon = prop_cast::<String>(d_props, "Icon").cloned();
+        device.rssi = prop_cast::<i16>(d_props, "RSSI").cloned();
+        // Adapter name is the last element of the adapter path
+        device.adapters = Vec::from_iter(
+            prop_cast::<Path>(d_props, "Adapter")
+                .and_then(|a_path| a_path.rsplit('/').next())
+                .map(String::from),
+        );
+        device
+    }
+
+    /// Blocks until the boolean property of the device with address has value
+    /// or the timeout elapsed. Returns whether the value was reached.
+    fn wait_for_property(
+        &self,
+        address: &str,
+        property: &'static str,
+        value: bool,
+        timeout: &Duration,
+    ) -> bool {
+        let path = match self.address_dbus_paths.get(address) {
+            Some(path) => path.clone(),
+            None => return false,
+        };
+        let reached = Arc::new(Mutex::new(false));
+        let reached_closure = Arc::clone(&reached);
+        let rule = PropertiesPropertiesChanged::match_rule(Some(&BLUEZ_DBUS.into()), Some(&path))
+            .static_clone();
+        let match_token = self.connection.add_match(
+            rule,
+            move |changed: PropertiesPropertiesChanged, _: &Connection, _: &Message| {
+                if changed.interface_name == DEVICE_INTERFACE
+                    && prop_cast::<bool>(&changed.changed_properties, property) == Some(&value)
+                {
+                    *reached_closure
+                        .lock()
+                        .expect("Mutex should not be poisoned.") = true;
+                }
+                true
+            },
+        );
+        // Check the current value after adding the match to not miss changes
+        if self
+            .connection
+            .with_proxy(BLUEZ_DBUS, &path, DBUS_TIMEOUT)
+            .get::<bool>(DEVICE_INTERFACE, property)
+            .is_ok_and(|current| current == value)
+        {
+            *reached.lock().expect("Mutex should not be poisoned.") = true;
+        }
+        let start = Instant::now();
+        while !*reached.lock().expect("Mutex should not be poisoned.") {
+            let remaining = timeout.saturating_sub(start.elapsed());
+            if remaining.is_zero() {
+                break;
+            }
+            let _ = self.connection.process(remaining);
+        }
+        if let Ok(match_token) = match_token {
+            let _ = self.connection.remove_match(match_token);
+        }
+        let reached = *reached.lock().expect("Mutex should not be poisoned.");
+        reached
+    }
+
+    /// Registers a pairing agent handling requests of all devices, e.g.
+    /// pairings started from a phone, and handles them until Ctrl+C is
+    /// pressed. If default is set, the agent is made the default agent of
+    /// bluez, which is asked for pairings not started by another agent.
+    pub fn run_agent(&mut self, default: bool) -> Result<(), dbus::Error> {
+        self.update();
+        let _guard = interrupt::guard();
+        let token = self._register_agent(None)?;
+        if default {
+            let result = self
+                .connection
+                .with_proxy(BLUEZ_DBUS, "/org/bluez", DBUS_TIMEOUT)
+                .request_default_agent("/agent\0".into());
+            if let Err(error) = result {
+                self._unregister_agent(token);
+                return Err(error);
+            }
+        }
+        while !interrupt::interrupted() {
+            let _ = self.connection.process(INTERRUPT_POLL);
+        }
+        self._unregister_agent(token);
+        Ok(())
+    }
+
+    /// Sets where the pairing agent gets requested pin codes from
+    pub fn set_pin_source(&mut self, pin_source: SecretSource) {
+        self.pin_source = pin_source.read_fd();
+    }
+
+    /// Sets where the pairing agent gets requested passkeys from
+    pub fn set_passkey_source(&mut self, passkey_source: SecretSource) {
+        self.passkey_source = passkey_source.read_fd();
+    }
+
+    /// Sets the IO capability the pairing agent registers with, one of
+    /// AGENT_CAPABILITIES
+    pub fn set_agent_capability(&mut self, capability: String) {
+        self.agent_capability = Some(capability);
+    }
+
+    /// Sets whether the pairing agent accepts passkey confirmations without
+    /// asking and rejects pin code and passkey requests it would have to
+    /// prompt for
+    pub fn set_no_input(&mut self, no_input: bool) {
+        self.no_input = no_input;
+    }
+
+    /// Sets whether the agent of run_agent accepts all pairing and service
+    /// requests without asking, even from devices that are not paired
+    pub fn set_accept_all(&mut self, accept_all: bool) {
+        self.accept_all = accept_all;
+    }
+
+    /// Sets the time after which pairing with a device is canceled
+    pub fn set_pair_timeout(&mut self, pair_timeout: Duration) {
+        self.pair_timeout = pair_timeout;
+    }
+
+    /// Sets how often connecting and pairing are retried on transient errors
+    /// and the delay before the first retry
+    pub fn set_retries(&mut self, retries: u32, retry_delay: Duration) {
+        self.retries = retries;
+        self.retry_delay = retry_delay;
+    }
+
+    /// Sets where D-Bus method calls, replies and signals are logged to,
+    /// nowhere if write is None
+    pub fn set_debug_dbus(&mut self, write: Option<fn(&str)>) {
+        self.connection.set_debug(write);
+    }
+
+    /// Calls attempt until it succeeds, retrying on transient errors with
+    /// exponential backoff. Returns the result of the last attempt.
+    fn _retry(
+        &self,
+        mut attempt: impl FnMut() -> Result<(), dbus::Error>,
+    ) -> Result<(), dbus::Error> {
+        let mut delay = self.retry_delay;
+        let mut retry = 0;
+        loop {
+            match attempt() {
+                Err(error)
+                    if retry < self.retries
+                        && is_transient_error(&error)
+                        && !interrupt::interrupted() =>
+                {
+                    thread::sleep(delay);
+                    delay = delay.saturating_mul(2);
+                    retry += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Calls Pair on the device behind proxy with a registered pairing agent
+    /// and waits for the reply. Cancels the pairing on timeout or Ctrl+C.
+    fn _pair_once(
+        &self,
+        device: &Device<Self>,
+        proxy: &Proxy<&BusConnection>,
+    ) -> Result<(), dbus::Error> {
+        // Cannot call proxy method directly because that would block
+        // the pairing agent, so matches are used instead.
+        let msg = Message::new_method_call(
+            proxy.destination.clone(),
+            proxy.path.clone(),
+            "org.bluez.Device1",
+            "Pair",
+        )
+        .map_err(|error| dbus::Error::new_failed(&error))?;
+        let _guard = interrupt::guard();
+        let agent_token = self._register_agent(Some(device)).ok();
+
+        // Variables for communication between closure and this scope, the
+        // reply is None while the call is pending
+        let reply = Arc::new(Mutex::new(None));
+        let reply_closure = Arc::clone(&reply);
+        let pair_reply_serial = Arc::new(Mutex::new(None));
+        let pair_reply_serial_closure = Arc::clone(&pair_reply_serial);
+        let log = self.connection.debug_log();
+        let pair_token = self.connection.start_receive(
+            MatchRule::new().with_sender(BLUEZ_DBUS),
+            Box::new(move |mut answer, _conn| {
+                let answer_serial = pair_reply_serial_closure
+                    .lock()
+                    .expect("Mutex should not be poisoned.");
+                if *answer_serial != answer.get_reply_serial() || answer_serial.is_none() {
+                    // Not the reply, continue receiving
+                    return true;
+                }
+                let result = answer.as_result().map(|_| ());
+                log.reply("org.bluez.Device1.Pair", &result);
+                *reply_closure.lock().expect("Mutex should not be poisoned.") = Some(result);
+                false
+            }),
+        );
+        *pair_reply_serial
+            .lock()
+            .expect("Mutex should not be poisoned.") = self.connection.send(msg).ok();
+        let start = Instant::now();
+        let result = loop {
+            if let Some(result) = reply.lock().expect("Mutex should not be poisoned.").take() {
+                break result;
+            }
+            let remaining = self.pair_timeout.saturating_sub(start.elapsed());
+            if interrupt::interrupted() || remaining.is_zero() {
+                // Pending agent requests are canceled by bluez
+                let _ = proxy.cancel_pairing();
+                break Err(dbus::Error::new_custom(
+                    BLUEZ_CANCELED_ERROR,
+                    "Pairing was canceled",
+                ));
+            }
+            let _ = self.connection.process(remaining.min(INTERRUPT_POLL));
+        };
+        self.connection.stop_receive(pair_token);
+        if let Some(agent_token) = agent_token {
+            self._unregister_agent(agent_token);
+        }
+        result
     }
 }
 
@@ -109,49 +675,64 @@ impl BluetoothManager for DBusBluetoothManager {
     fn update(&mut self) -> &mut Self {
         self.devices = Vec::new();
         self.adapter_paths = Vec::new();
+        self.adapters = Vec::new();
         if let Ok(objects) = self
             .connection
             .with_proxy(BLUEZ_DBUS, "/", DBUS_TIMEOUT)
             .get_managed_objects()
         {
-            for (path, interfaces) in objects {
-                if let Some(_) = interfaces.get(ADAPTER_INTERFACE) {
-                    self.adapter_paths.push(path);
-                } else if let Some(d_props) = interfaces.get(DEVICE_INTERFACE) {
-                    let address = prop_cast::<String>(d_props, "Address")
+            // Adapters are collected first, so devices of adapters that are
+            // not selected can be skipped
+            for (path, interfaces) in &objects {
+                if let Some(a_props) = interfaces.get(ADAPTER_INTERFACE) {
+                    // Same naming as for device.adapters
+                    let name = path.rsplit('/').next().unwrap_or_default().to_string();
+                    let address = prop_cast::<String>(a_props, "Address")
                         .cloned()
                         .expect("Address is required");
-                    // alias is used for device.name, not device.name
-                    let alias = prop_cast::<String>(d_props, "Alias")
+                    let alias = prop_cast::<String>(a_props, "Alias")
                         .cloned()
                         .expect("Alias is required");
-                    let paired = prop_cast::<bool>(d_props, "Paired")
-                        .cloned()
-                        .expect("Paired is required");
-                    let bonded = prop_cast::<bool>(d_props, "Bonded")
-                        .cloned()
-                        .expect("Bonded is required");
-                    let trusted = prop_cast::<bool>(d_props, "Trusted")
-                        .cloned()
-                        .expect("Trusted is required");
-                    let blocked = prop_cast::<bool>(d_props, "Blocked")
-                        .cloned()
-                        .expect("Blocked is required");
-                    let connected = prop_cast::<bool>(d_props, "Connected")
-                        .cloned()
-                        .expect("Connected is required");
-                    let name = prop_cast::<String>(d_props, "Name").cloned();
-                    let icon = prop_cast::<String>(d_props, "Icon").cloned();
-
+                    let state = |property| {
+                        prop_cast::<bool>(a_props, property)
+                            .cloned()
+                            .unwrap_or_default()
+                    };
+                    let adapter = Adapter {
+                        name,
+                        address,
+                        alias,
+                        powered: state("Powered"),
+                        discoverable: state("Discoverable"),
+                        pairable: state("Pairable"),
+                        discovering: state("Discovering"),
+                    };
+                    if self
+                        .selected_adapter
+                        .as_ref()
+                        .is_none_or(|filter| adapter.matches(filter))
+                    {
+                        self.adapters.push(adapter);
+                        self.adapter_paths.push(path.clone());
+                    }
+                }
+            }
+            for (path, interfaces) in objects {
+                if let Some(d_props) = interfaces.get(DEVICE_INTERFACE) {
                     let battery = interfaces.get(BATTERY_INTERFACE).and_then(|battery_props| {
                         prop_cast::<u8>(battery_props, "Percentage").cloned()
                     });
-                    self.address_dbus_paths.insert(address.clone(), path);
-                    let mut device =
-                        Device::new(address, alias, paired, bonded, trusted, blocked, connected);
-                    device.remote_name = name;
-                    device.icon = icon;
+                    let mut device = Self::device_from_props(d_props);
+                    if self.selected_adapter.is_some()
+                        && !self
+                            .adapters
+                            .iter()
+                            .any(|a| device.adapters.contains(&a.name))
+                    {
+                        continue;
+                    }
                     device.battery = battery;
+                    self.address_dbus_paths.insert(device.address.clone(), path);
                     let wrapped_device = Arc::new(Mutex::new(device));
                     self.devices.push(Arc::clone(&wrapped_device));
                 };
@@ -160,6 +741,28 @@ impl BluetoothManager for DBusBluetoothManager {
         self
     }
 
+    fn fork(&self) -> Option<Self> {
+        // Devices are only needed for updates, which a fork is not used for
+        Some(Self {
+            connection: self.connection.fork().ok()?,
+            address_dbus_paths: self.address_dbus_paths.clone(),
+            devices: Vec::new(),
+            adapter_paths: self.adapter_paths.clone(),
+            adapters: self.adapters.clone(),
+            selected_adapter: self.selected_adapter.clone(),
+            discovery_filter: self.discovery_filter.clone(),
+            pin_source: self.pin_source.clone(),
+            passkey_source: self.passkey_source.clone(),
+            agent_capability: self.agent_capability.clone(),
+            no_input: self.no_input,
+            accept_all: self.accept_all,
+            agent_error: Arc::default(),
+            pair_timeout: self.pair_timeout,
+            retries: self.retries,
+            retry_delay: self.retry_delay,
+        })
+    }
+
     fn get_all_devices(&self) -> Devices<Self> {
         Vec::from_iter(
             self.devices
@@ -168,139 +771,549 @@ impl BluetoothManager for DBusBluetoothManager {
         )
     }
 
-    fn set_pairable(&self, _pairable: bool) {
-        todo!()
+    fn get_all_adapters(&self) -> Vec<Adapter> {
+        self.adapters.clone()
+    }
+
+    fn set_selected_adapter(&mut self, selected_adapter: Option<String>) {
+        self.selected_adapter = selected_adapter;
+    }
+
+    fn set_adapter_alias(&self, adapter: &Adapter, alias: &str) -> bool {
+        // Device proxies have a set_alias method as well
+        self._create_adapter_proxy(adapter)
+            .is_some_and(|proxy| OrgBluezAdapter1::set_alias(&proxy, alias.to_string()).is_ok())
+    }
+
+    fn set_adapter_powered(&self, adapter: &Adapter, powered: bool) -> bool {
+        self._create_adapter_proxy(adapter)
+            .is_some_and(|proxy| proxy.set_powered(powered).is_ok())
+    }
+
+    fn set_adapter_discoverable(
+        &self,
+        adapter: &Adapter,
+        discoverable: bool,
+        timeout: Option<u32>,
+    ) -> bool {
+        self._create_adapter_proxy(adapter).is_some_and(|proxy| {
+            // Timeout has to be set first, as it starts counting down once the
+            // adapter is discoverable
+            timeout.is_none_or(|timeout| proxy.set_discoverable_timeout(timeout).is_ok())
+                && proxy.set_discoverable(discoverable).is_ok()
+        })
+    }
+
+    fn set_adapter_pairable(&self, adapter: &Adapter, pairable: bool) -> bool {
+        self._create_adapter_proxy(adapter)
+            .is_some_and(|proxy| proxy.set_pairable(pairable).is_ok())
+    }
+
+    fn set_discovery_filter(&mut self, filter: DiscoveryFilter) {
+        self.discovery_filter = filter;
     }
 
     fn scan(&self, duration: &Duration) -> &Self {
-        for a_path in &self.adapter_paths {
-            let proxy = self.connection.with_proxy(BLUEZ_DBUS, a_path, DBUS_TIMEOUT);
-            let discovering = proxy.start_discovery().is_ok();
-            if discovering {
-                if self.scan_display_hint {
-                    print!("\x1b[2;37mScanning for devices...{ANSI_RESET}");
-                    let _ = io::stdout().flush();
+        // All adapters discover at the same time while signals are processed,
+        // devices are picked up by the next update
+        self.scan_live(duration, &mut |_| true)
+    }
+
+    fn scan_live(
+        &self,
+        duration: &Duration,
+        found: &mut dyn FnMut(&Device<Self>) -> bool,
+    ) -> &Self {
+        let _guard = interrupt::guard();
+        // Paths of devices that appeared or changed, collected by the signal
+        // handlers and processed after each batch of messages
+        let changed_paths: Arc<Mutex<Vec<Path<'static>>>> = Arc::new(Mutex::new(Vec::new()));
+        let added_paths = Arc::clone(&changed_paths);
+        let added_token = self.connection.add_match(
+            ObjectManagerInterfacesAdded::match_rule(Some(&BLUEZ_DBUS.into()), None).static_clone(),
+            move |added: ObjectManagerInterfacesAdded, _: &Connection, _: &Message| {
+                if added.interfaces.contains_key(DEVICE_INTERFACE) {
+                    added_paths
+                        .lock()
+                        .expect("Mutex should not be poisoned.")
+                        .push(added.object);
                 }
-                thread::sleep(*duration);
-                let _ = proxy.stop_discovery();
-                if self.scan_display_hint {
-                    print!("\x1b[1K\r");
+                true
+            },
+        );
+        let properties_paths = Arc::clone(&changed_paths);
+        let properties_token = self.connection.add_match(
+            PropertiesPropertiesChanged::match_rule(Some(&BLUEZ_DBUS.into()), None).static_clone(),
+            move |changed: PropertiesPropertiesChanged, _: &Connection, message: &Message| {
+                let relevant = ["RSSI", "Name", "Alias"];
+                if changed.interface_name == DEVICE_INTERFACE
+                    && relevant
+                        .iter()
+                        .any(|property| changed.changed_properties.contains_key(*property))
+                {
+                    if let Some(path) = message.path() {
+                        properties_paths
+                            .lock()
+                            .expect("Mutex should not be poisoned.")
+                            .push(path.into_static());
+                    }
                 }
+                true
+            },
+        );
+
+        let discovering_paths: Vec<_> = self
+            .adapter_paths
+            .iter()
+            .filter(|a_path| {
+                self._start_discovery(&self.connection.with_proxy(
+                    BLUEZ_DBUS,
+                    *a_path,
+                    DBUS_TIMEOUT,
+                ))
+            })
+            .collect();
+        let start = Instant::now();
+        'scan: while !discovering_paths.is_empty() && !interrupt::interrupted() {
+            let remaining = duration.saturating_sub(start.elapsed());
+            if remaining.is_zero() {
+                break;
             }
+            let _ = self.connection.process(remaining.min(INTERRUPT_POLL));
+            let paths: Vec<_> = changed_paths
+                .lock()
+                .expect("Mutex should not be poisoned.")
+                .drain(..)
+                .collect();
+            for path in paths {
+                // Only devices of adapters in use are of interest
+                if !discovering_paths
+                    .iter()
+                    .any(|a_path| path.starts_with(&format!("{a_path}/")))
+                {
+                    continue;
+                }
+                if let Ok(d_props) = self
+                    .connection
+                    .with_proxy(BLUEZ_DBUS, &path, DBUS_TIMEOUT)
+                    .get_all(DEVICE_INTERFACE)
+                {
+                    if !found(&Self::device_from_props(&d_props)) {
+                        break 'scan;
+                    }
+                }
+            }
+        }
+        for a_path in &discovering_paths {
+            let _ = self
+                .connection
+                .with_proxy(BLUEZ_DBUS, *a_path, DBUS_TIMEOUT)
+                .stop_discovery();
         }
-        &self
+        for token in [added_token, properties_token].into_iter().flatten() {
+            let _ = self.connection.remove_match(token);
+        }
+        self
     }
 
-    fn pair_device(&self, device: &Device<Self>) -> bool {
-        if device.paired {
-            return true;
-        }
-        self._create_device_proxy(&device.address)
-            .is_some_and(|proxy| {
-                // Cannot call proxy method directly because that would block
-                // the pairing agent, so matches are used instead.
-
-                // Variables for communication between closure and this scope
-                let return_value = Arc::new(Mutex::new(false));
-                let return_value_closure = Arc::clone(&return_value);
-                let agent_token = self._register_agent(device);
-
-                if let Ok(msg) = Message::new_method_call(
-                    proxy.destination,
-                    proxy.path,
-                    "org.bluez.Device1",
-                    "Pair",
-                ) {
-                    let answer_pending = Arc::new(Mutex::new(true));
-                    let answer_pending_closure = Arc::clone(&answer_pending);
-                    let pair_reply_serial = Arc::new(Mutex::new(None));
-                    let pair_reply_serial_closure = Arc::clone(&pair_reply_serial);
-
-                    let pair_token = self.connection.start_receive(
-                        MatchRule::new().with_sender(BLUEZ_DBUS),
-                        Box::new(move |mut answer, _conn| {
-                            let answer_serial = pair_reply_serial_closure
-                                .lock()
-                                .expect("Mutex should not be poisoned.");
-                            if *answer_serial != answer.get_reply_serial()
-                                || answer_serial.is_none()
-                            {
-                                // Not the reply, continue receiving
-                                return true;
-                            }
-                            // Is answer
-                            let is_paired = match answer.as_result() {
-                                Ok(_) => true,
-                                // Also return true if the device is already paired
-                                Err(error) => error.name() == Some("org.bluez.Error.AlreadyExists"),
-                            };
-                            *return_value_closure
-                                .lock()
-                                .expect("Mutex should not be poisoned.") = is_paired;
-                            *answer_pending_closure
-                                .lock()
-                                .expect("Mutex should not be poisoned.") = false;
-                            return false;
-                        }),
-                    );
-                    *pair_reply_serial
-                        .lock()
-                        .expect("Mutex should not be poisoned.") = self.connection.send(msg).ok();
-                    while answer_pending.lock().is_ok_and(|pending| *pending) {
-                        let _ = self.connection.process(DBUS_TIMEOUT);
+    fn monitor(&self, handle: &mut dyn FnMut(Option<&DeviceEvent>) -> bool) {
+        let _guard = interrupt::guard();
+        // Changes are collected by the signal handlers with the path of the
+        // device and resolved to events after each batch of messages
+        let changes: Arc<Mutex<Vec<(Path<'static>, DeviceEventKind)>>> =
+            Arc::new(Mutex::new(Vec::new()));
+        let added_changes = Arc::clone(&changes);
+        let added_token = self.connection.add_match(
+            ObjectManagerInterfacesAdded::match_rule(Some(&BLUEZ_DBUS.into()), None).static_clone(),
+            move |added: ObjectManagerInterfacesAdded, _: &Connection, _: &Message| {
+                let mut changes = added_changes.lock().expect("Mutex should not be poisoned.");
+                if added.interfaces.contains_key(DEVICE_INTERFACE) {
+                    changes.push((added.object.clone(), DeviceEventKind::Discovered));
+                }
+                // Battery interface appears once a device is connected
+                if let Some(percentage) = added
+                    .interfaces
+                    .get(BATTERY_INTERFACE)
+                    .and_then(|b_props| prop_cast::<u8>(b_props, "Percentage"))
+                {
+                    changes.push((added.object, DeviceEventKind::Battery(*percentage)));
+                }
+                true
+            },
+        );
+        let properties_changes = Arc::clone(&changes);
+        let properties_token = self.connection.add_match(
+            PropertiesPropertiesChanged::match_rule(Some(&BLUEZ_DBUS.into()), None).static_clone(),
+            move |changed: PropertiesPropertiesChanged, _: &Connection, message: &Message| {
+                let Some(path) = message.path() else {
+                    return true;
+                };
+                let props = &changed.changed_properties;
+                let mut kinds = Vec::new();
+                if changed.interface_name == DEVICE_INTERFACE {
+                    match prop_cast::<bool>(props, "Connected") {
+                        Some(true) => kinds.push(DeviceEventKind::Connected),
+                        Some(false) => kinds.push(DeviceEventKind::Disconnected),
+                        None => (),
+                    }
+                    match prop_cast::<bool>(props, "Paired") {
+                        Some(true) => kinds.push(DeviceEventKind::Paired),
+                        Some(false) => kinds.push(DeviceEventKind::Unpaired),
+                        None => (),
+                    }
+                } else if changed.interface_name == BATTERY_INTERFACE {
+                    if let Some(percentage) = prop_cast::<u8>(props, "Percentage") {
+                        kinds.push(DeviceEventKind::Battery(*percentage));
                     }
-                    self.connection.stop_receive(pair_token);
                 }
-                if let Some(agent_token) = agent_token {
-                    self.connection.stop_receive(agent_token);
+                let mut changes = properties_changes
+                    .lock()
+                    .expect("Mutex should not be poisoned.");
+                for kind in kinds {
+                    changes.push((path.clone().into_static(), kind));
                 }
-                return_value.lock().is_ok_and(|val| *val)
-            })
+                true
+            },
+        );
+
+        'monitor: while !interrupt::interrupted() {
+            let _ = self.connection.process(INTERRUPT_POLL);
+            let batch: Vec<_> = changes
+                .lock()
+                .expect("Mutex should not be poisoned.")
+                .drain(..)
+                .collect();
+            for (path, kind) in batch {
+                // Only devices of adapters in use are of interest
+                if !self
+                    .adapter_paths
+                    .iter()
+                    .any(|a_path| path.starts_with(&format!("{a_path}/")))
+                {
+                    continue;
+                }
+                let proxy = self.connection.with_proxy(BLUEZ_DBUS, &path, DBUS_TIMEOUT);
+                let (Ok(address), Ok(name)) = (
+                    proxy.get::<String>(DEVICE_INTERFACE, "Address"),
+                    proxy.get::<String>(DEVICE_INTERFACE, "Alias"),
+                ) else {
+                    continue;
+                };
+                if !handle(Some(&DeviceEvent {
+                    address,
+                    name,
+                    icon: proxy.get::<String>(DEVICE_INTERFACE, "Icon").ok(),
+                    kind,
+                })) {
+                    break 'monitor;
+                }
+            }
+            if !handle(None) {
+                break;
+            }
+        }
+        for token in [added_token, properties_token].into_iter().flatten() {
+            let _ = self.connection.remove_match(token);
+        }
     }
 
-    fn unpair_device(&self, device: &Device<Self>) {
+    fn pair_device(&self, device: &Device<Self>) -> Result<(), BtError> {
+        if device.paired {
+            return Err(BtError::AlreadyPaired);
+        }
+        let proxy = self
+            ._create_device_proxy(&device.address)
+            .ok_or(BtError::NotFound)?;
+        let result = self._retry(|| self._pair_once(device, &proxy));
+        let agent_error = self
+            .agent_error
+            .lock()
+            .expect("Mutex should not be poisoned.")
+            .take();
+        match (result, agent_error) {
+            (Err(_), Some(agent_error)) => Err(agent_error),
+            (result, _) => Ok(result?),
+        }
+    }
+
+    fn unpair_device(&self, device: &Device<Self>) -> Result<(), BtError> {
         // Get DBus Path to device
-        if let Some(d_path) = self
+        let d_path = self
             .address_dbus_paths
             .get(&device.address)
             .and_then(|path| Path::new(path.to_string()).ok())
-        {
-            // Get adapter that manages device via proxy
-            let d_proxy = self
-                .connection
-                .with_proxy(BLUEZ_DBUS, &d_path, DBUS_TIMEOUT);
-            if let Ok(path) = d_proxy.adapter() {
-                // Disconnect device from its adapter
-                let _ = self
-                    .connection
-                    .with_proxy(BLUEZ_DBUS, path, DBUS_TIMEOUT)
-                    .remove_device(d_path);
-            };
-        };
+            .ok_or(BtError::NotFound)?;
+        // Get adapter that manages device via proxy
+        let d_proxy = self
+            .connection
+            .with_proxy(BLUEZ_DBUS, &d_path, DBUS_TIMEOUT);
+        let path = d_proxy.adapter()?;
+        // Disconnect device from its adapter
+        Ok(self
+            .connection
+            .with_proxy(BLUEZ_DBUS, path, DBUS_TIMEOUT)
+            .remove_device(d_path)?)
     }
 
-    fn connect_device(&self, device: &Device<Self>) -> bool {
+    fn connect_device(&self, device: &Device<Self>) -> Result<(), BtError> {
         if device.connected {
-            return true;
+            return Err(BtError::AlreadyConnected);
         }
-        self._create_device_proxy(&device.address)
-            .is_some_and(|proxy| match proxy.connect() {
-                Ok(_) => true,
-                // Also return true if the device is already connected
-                Err(error) => error.name() == Some("org.bluez.Error.AlreadyConnected"),
-            })
+        let proxy = self
+            ._create_device_proxy(&device.address)
+            .ok_or(BtError::NotFound)?;
+        Ok(self._retry(|| proxy.connect())?)
+    }
+
+    fn connect_device_profile(&self, device: &Device<Self>, uuid: &str) -> Result<(), BtError> {
+        let proxy = self
+            ._create_device_proxy(&device.address)
+            .ok_or(BtError::NotFound)?;
+        Ok(self._retry(|| proxy.connect_profile(uuid))?)
+    }
+
+    fn disconnect_device(&self, device: &Device<Self>) -> Result<(), BtError> {
+        let proxy = self
+            ._create_device_proxy(&device.address)
+            .ok_or(BtError::NotFound)?;
+        Ok(proxy.disconnect()?)
+    }
+
+    fn disconnect_device_profile(&self, device: &Device<Self>, uuid: &str) -> Result<(), BtError> {
+        let proxy = self
+            ._create_device_proxy(&device.address)
+            .ok_or(BtError::NotFound)?;
+        Ok(proxy.disconnect_profile(uuid)?)
+    }
+
+    fn set_device_blocked(&self, device: &Device<Self>, blocked: bool) -> Result<(), BtError> {
+        if device.blocked == blocked {
+            return Ok(());
+        }
+        let proxy = self
+            ._create_device_proxy(&device.address)
+            .ok_or(BtError::NotFound)?;
+        Ok(proxy.set_blocked(blocked)?)
+    }
+
+    fn set_device_trusted(&self, device: &Device<Self>, trusted: bool) -> Result<(), BtError> {
+        if device.trusted == trusted {
+            return Ok(());
+        }
+        let proxy = self
+            ._create_device_proxy(&device.address)
+            .ok_or(BtError::NotFound)?;
+        Ok(proxy.set_trusted(trusted)?)
     }
 
-    fn disconnect_device(&self, device: &Device<Self>) {
-        if let Some(proxy) = self._create_device_proxy(&device.address) {
-            let _ = proxy.disconnect();
+    fn set_device_alias(&self, device: &Device<Self>, alias: &str) -> Result<(), BtError> {
+        let proxy = self
+            ._create_device_proxy(&device.address)
+            .ok_or(BtError::NotFound)?;
+        Ok(OrgBluezDevice1::set_alias(&proxy, alias.to_string())?)
+    }
+
+    fn control_media(&self, device: &Device<Self>, command: MediaCommand) -> Result<(), BtError> {
+        let path = self._media_player(device)?;
+        let method = match command {
+            MediaCommand::Play => "Play",
+            MediaCommand::Pause => "Pause",
+            MediaCommand::Next => "Next",
+            MediaCommand::Previous => "Previous",
+            MediaCommand::Stop => "Stop",
         };
+        Ok(self
+            .connection
+            .with_proxy(BLUEZ_DBUS, path, DBUS_TIMEOUT)
+            .method_call(MEDIA_PLAYER_INTERFACE, method, ())?)
+    }
+
+    fn media_status(&self, device: &Device<Self>) -> Result<MediaStatus, BtError> {
+        let path = self._media_player(device)?;
+        let proxy = self.connection.with_proxy(BLUEZ_DBUS, path, DBUS_TIMEOUT);
+        // Track is read with its type, as untyped dictionaries cannot be
+        // cast to a PropMap
+        let track: PropMap = proxy
+            .get(MEDIA_PLAYER_INTERFACE, "Track")
+            .unwrap_or_default();
+        let text = |key: &str| {
+            prop_cast::<String>(&track, key)
+                .filter(|text| !text.is_empty())
+                .cloned()
+        };
+        Ok(MediaStatus {
+            status: proxy.get(MEDIA_PLAYER_INTERFACE, "Status")?,
+            title: text("Title"),
+            artist: text("Artist"),
+            album: text("Album"),
+            position: proxy.get(MEDIA_PLAYER_INTERFACE, "Position").unwrap_or(0),
+            duration: prop_cast::<u32>(&track, "Duration")
+                .copied()
+                .filter(|duration| *duration > 0),
+        })
+    }
+
+    fn media_transports(&self, device: &Device<Self>) -> Result<Vec<MediaTransport>, BtError> {
+        Ok(self
+            ._media_transports(&device.address)
+            .into_iter()
+            .map(|(_, props)| {
+                let configuration = prop_cast::<Vec<u8>>(&props, "Configuration");
+                MediaTransport {
+                    codec: media::codec_name(
+                        prop_cast::<u8>(&props, "Codec")
+                            .copied()
+                            .unwrap_or_default(),
+                        configuration.map_or(&[], Vec::as_slice),
+                    ),
+                    state: prop_cast::<String>(&props, "State")
+                        .cloned()
+                        .unwrap_or_default(),
+                    volume: prop_cast::<u16>(&props, "Volume")
+                        .map(|volume| media::volume_to_percent(*volume)),
+                }
+            })
+            .collect())
+    }
+
+    fn media_volume(&self, device: &Device<Self>) -> Result<u16, BtError> {
+        Ok(self._volume_transport(device)?.1)
+    }
+
+    fn set_media_volume(&self, device: &Device<Self>, volume: u16) -> Result<(), BtError> {
+        let (path, _) = self._volume_transport(device)?;
+        Ok(self
+            .connection
+            .with_proxy(BLUEZ_DBUS, path, DBUS_TIMEOUT)
+            .set(MEDIA_TRANSPORT_INTERFACE, "Volume", volume)?)
+    }
+
+    fn wait_for_connected(
+        &self,
+        device: &Device<Self>,
+        connected: bool,
+        timeout: &Duration,
+    ) -> Result<(), BtError> {
+        if self.wait_for_property(&device.address, "Connected", connected, timeout) {
+            Ok(())
+        } else {
+            Err(BtError::Timeout)
+        }
+    }
+
+    fn wait_for_services_resolved(
+        &self,
+        device: &Device<Self>,
+        timeout: &Duration,
+    ) -> Result<(), BtError> {
+        if self.wait_for_property(&device.address, "ServicesResolved", true, timeout) {
+            Ok(())
+        } else {
+            Err(BtError::Timeout)
+        }
     }
 }
 
+/// Writes line for the user to the terminal, or to stderr if there is none,
+/// so prompts work and do not mix with the output if stdout is redirected.
+fn tell(line: &str) {
+    match OpenOptions::new().write(true).open("/dev/tty") {
+        Ok(mut tty) => {
+            let _ = writeln!(tty, "{line}");
+        }
+        Err(_) => eprintln!("{line}"),
+    }
+}
+
+/// Reads a line the user answered on the terminal, or on stdin if there is
+/// none. Returns the line without the line break, or None if reading failed.
+fn read_answer() -> Option<String> {
+    let mut answer = String::new();
+    let read = match File::open("/dev/tty") {
+        Ok(tty) => BufReader::new(tty).read_line(&mut answer),
+        Err(_) => io::stdin().read_line(&mut answer),
+    };
+    match read {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(answer.trim_end_matches(['\r', '\n']).to_string()),
+    }
+}
+
+/// Returns whether the user can be prompted on a terminal.
+fn can_prompt() -> bool {
+    File::open("/dev/tty").is_ok() || io::stdin().is_terminal()
+}
+
 struct DBusBluetoothAgent {
-    device_name: String,
-    device_path: dbus::Path<'static>,
+    /// Path of the only device to handle requests of, or None to handle all
+    /// devices
+    device_path: Option<dbus::Path<'static>>,
+    /// Names of the devices known when the agent was created by path
+    device_names: HashMap<dbus::Path<'static>, String>,
+    /// Paths of the devices paired when the agent was created, which may use
+    /// services without asking with no_input
+    paired_paths: HashSet<dbus::Path<'static>>,
+    pin_source: SecretSource,
+    passkey_source: SecretSource,
+    no_input: bool,
+    /// Whether all requests are accepted without asking
+    accept_all: bool,
+    /// Error explaining why a request was rejected, reported by pair_device
+    error: Arc<Mutex<Option<BtError>>>,
+}
+
+impl DBusBluetoothAgent {
+    /// Returns the name of device for prompts. Requests of other devices than
+    /// the one the agent was created for are rejected.
+    fn device_name(&self, device: &dbus::Path<'static>) -> Result<String, dbus::MethodErr> {
+        if self.device_path.as_ref().is_some_and(|path| path != device) {
+            return Err(dbus::Error::new_custom(BLUEZ_REJECTED_ERROR, "").into());
+        }
+        // Devices discovered later are named by the address in their path,
+        // e.g. /org/bluez/hci0/dev_00_11_22_33_44_55
+        Ok(self.device_names.get(device).cloned().unwrap_or_else(|| {
+            let node = device.rsplit('/').next().unwrap_or_default();
+            node.trim_start_matches("dev_").replace('_', ":")
+        }))
+    }
+
+    /// Asks question until it is answered with yes or no. Returns an error
+    /// rejecting the request unless the answer is yes.
+    fn ask(&self, question: &str) -> Result<(), dbus::MethodErr> {
+        loop {
+            tell(&format!("{question} [y/n]"));
+            match read_answer().as_deref().map(str::to_lowercase).as_deref() {
+                Some("y" | "yes") => return Ok(()),
+                Some("n" | "no") | None => {
+                    return Err(dbus::Error::new_custom(BLUEZ_REJECTED_ERROR, "").into())
+                }
+                Some(_) => (),
+            }
+        }
+    }
+
+    /// Asks question and returns the answer. Returns an error canceling the
+    /// request if the answer is empty or there is no terminal to ask on.
+    fn prompt(&self, question: &str) -> Result<String, dbus::MethodErr> {
+        tell(question);
+        match read_answer() {
+            Some(answer) if !answer.is_empty() => Ok(answer),
+            answer => {
+                if answer.is_some() {
+                    tell("Empty input, canceling.");
+                }
+                Err(dbus::Error::new_custom(BLUEZ_CANCELED_ERROR, "").into())
+            }
+        }
+    }
+
+    /// Rejects a request for secret that cannot be answered without
+    /// prompting, keeping the argument giving it instead for the error.
+    fn reject_without_input(&self, secret: &'static str, arg: &'static str) -> dbus::MethodErr {
+        *self.error.lock().expect("Mutex should not be poisoned.") =
+            Some(BtError::NoInput { secret, arg });
+        dbus::Error::new_custom(BLUEZ_REJECTED_ERROR, "").into()
+    }
 }
 
 impl OrgBluezAgent1 for DBusBluetoothAgent {
@@ -309,27 +1322,25 @@ impl OrgBluezAgent1 for DBusBluetoothAgent {
     }
 
     fn request_pin_code(&mut self, device: dbus::Path<'static>) -> Result<String, dbus::MethodErr> {
-        if device != self.device_path {
-            return Err(dbus::Error::new_custom(BLUEZ_REJECTED_ERROR, "").into());
+        let device_name = self.device_name(&device)?;
+        if !matches!(self.pin_source, SecretSource::Prompt) {
+            return match self.pin_source.read() {
+                Some(pin_code) if (1..=16).contains(&pin_code.len()) => Ok(pin_code),
+                _ => Err(dbus::Error::new_custom(BLUEZ_REJECTED_ERROR, "").into()),
+            };
         }
-        let device_name = &self.device_name;
-        println!(
-            "Please enter the pin code displayed on {device_name}. \
-            (1-16 symbols, empty input to cancel)"
-        );
-        let mut pin_code = String::new();
-        while io::stdin().read_line(&mut pin_code).is_err() || pin_code.len() > 16 {
-            println!(
+        if self.no_input {
+            return Err(self.reject_without_input("pin code", "--pin"));
+        }
+        loop {
+            let pin_code = self.prompt(&format!(
                 "Please enter the pin code displayed on {device_name}. \
                 (1-16 symbols, empty input to cancel)"
-            );
-            pin_code = String::new();
-        }
-        if pin_code.is_empty() {
-            println!("Empty input, canceling.");
-            return Err(dbus::Error::new_custom(BLUEZ_CANCELED_ERROR, "").into());
+            ))?;
+            if pin_code.len() <= 16 {
+                return Ok(pin_code);
+            }
         }
-        Ok(pin_code)
     }
 
     fn display_pin_code(
@@ -337,43 +1348,32 @@ impl OrgBluezAgent1 for DBusBluetoothAgent {
         device: dbus::Path<'static>,
         pincode: String,
     ) -> Result<(), dbus::MethodErr> {
-        if device != self.device_path {
-            return Err(dbus::Error::new_custom(BLUEZ_REJECTED_ERROR, "").into());
-        }
-        let device_name = &self.device_name;
-        println!("The pincode for {device_name} is {pincode}.");
+        let device_name = self.device_name(&device)?;
+        tell(&format!("The pincode for {device_name} is {pincode}."));
         Ok(())
     }
 
     fn request_passkey(&mut self, device: dbus::Path<'static>) -> Result<u32, dbus::MethodErr> {
-        if device != self.device_path {
-            return Err(dbus::Error::new_custom(BLUEZ_REJECTED_ERROR, "").into());
+        let device_name = self.device_name(&device)?;
+        if !matches!(self.passkey_source, SecretSource::Prompt) {
+            return match self.passkey_source.read().and_then(|p| p.parse().ok()) {
+                Some(passkey) if passkey < 1_000_000 => Ok(passkey),
+                _ => Err(dbus::Error::new_custom(BLUEZ_REJECTED_ERROR, "").into()),
+            };
+        }
+        if self.no_input {
+            return Err(self.reject_without_input("passkey", "--passkey"));
         }
-        let device_name = &self.device_name;
-        println!(
-            "Please enter the passkey displayed on {device_name}. \
-            (6 digits, empty input to cancel)"
-        );
-        let mut passkey = String::new();
-        let mut read_result = io::stdin().read_line(&mut passkey);
         loop {
-            if read_result.is_ok() {
-                let trimmed = passkey.trim();
-                if trimmed.is_empty() {
-                    println!("Empty input, canceling.");
-                    return Err(dbus::Error::new_custom(BLUEZ_CANCELED_ERROR, "").into());
-                } else if let Ok(parsed) = trimmed.parse() {
-                    if parsed < 1_000_000 {
-                        return Ok(parsed);
-                    }
-                }
-            }
-            println!(
+            let passkey = self.prompt(&format!(
                 "Please enter the passkey displayed on {device_name}. \
                 (6 digits, empty input to cancel)"
-            );
-            passkey = String::new();
-            read_result = io::stdin().read_line(&mut passkey);
+            ))?;
+            if let Ok(passkey) = passkey.trim().parse() {
+                if passkey < 1_000_000 {
+                    return Ok(passkey);
+                }
+            }
         }
     }
 
@@ -383,11 +1383,8 @@ impl OrgBluezAgent1 for DBusBluetoothAgent {
         passkey: u32,
         _entered: u16,
     ) -> Result<(), dbus::MethodErr> {
-        if device != self.device_path {
-            return Err(dbus::Error::new_custom(BLUEZ_REJECTED_ERROR, "").into());
-        }
-        let device_name = &self.device_name;
-        println!("The pincode for {device_name} is {passkey:06}.");
+        let device_name = self.device_name(&device)?;
+        tell(&format!("The pincode for {device_name} is {passkey:06}."));
         Ok(())
     }
 
@@ -396,45 +1393,56 @@ impl OrgBluezAgent1 for DBusBluetoothAgent {
         device: dbus::Path<'static>,
         passkey: u32,
     ) -> Result<(), dbus::MethodErr> {
-        if device != self.device_path {
-            return Err(dbus::Error::new_custom(BLUEZ_REJECTED_ERROR, "").into());
+        let device_name = self.device_name(&device)?;
+        // Only pairings bt started itself are confirmed without asking,
+        // unless all requests are accepted
+        if self.accept_all || (self.no_input && self.device_path.is_some()) {
+            tell(&format!(
+                "Confirming pincode {passkey:06} of {device_name}."
+            ));
+            return Ok(());
         }
-        let device_name = &self.device_name;
-        println!("Does {passkey:06} match the pincode on {device_name}? [y/n]");
-        let mut answer = [0u8];
-        let mut read_result = io::stdin().read(&mut answer);
-        loop {
-            if let Ok(1) = read_result {
-                if answer[0] == b'y' {
-                    return Ok(());
-                } else if answer[0] == b'n' {
-                    return Err(dbus::Error::new_custom(BLUEZ_REJECTED_ERROR, "").into());
-                }
-            }
-            println!("Does {passkey:06} match the pincode on {device_name}? [y/n]");
-            read_result = io::stdin().read(&mut answer);
+        if self.no_input {
+            return Err(dbus::Error::new_custom(BLUEZ_REJECTED_ERROR, "").into());
         }
+        self.ask(&format!(
+            "Does {passkey:06} match the pincode on {device_name}?"
+        ))
     }
 
-    fn request_authoritation(
+    fn request_authorization(
         &mut self,
         device: dbus::Path<'static>,
     ) -> Result<(), dbus::MethodErr> {
-        if device != self.device_path {
+        let device_name = self.device_name(&device)?;
+        // Pairing with the device was asked for if there is only one
+        if self.device_path.is_some() || self.accept_all {
+            return Ok(());
+        }
+        if self.no_input {
             return Err(dbus::Error::new_custom(BLUEZ_REJECTED_ERROR, "").into());
         }
-        Ok(())
+        self.ask(&format!("Accept pairing with {device_name}?"))
     }
 
     fn authorize_service(
         &mut self,
         device: dbus::Path<'static>,
-        _uuid: String,
+        uuid: String,
     ) -> Result<(), dbus::MethodErr> {
-        if device != self.device_path {
+        let device_name = self.device_name(&device)?;
+        if self.device_path.is_some() || self.accept_all {
+            return Ok(());
+        }
+        if self.no_input {
+            // Devices that were not paired before are not allowed unasked
+            if self.paired_paths.contains(&device) {
+                return Ok(());
+            }
             return Err(dbus::Error::new_custom(BLUEZ_REJECTED_ERROR, "").into());
         }
-        Ok(())
+        let service = uuids::service_name(&uuid).unwrap_or(&uuid);
+        self.ask(&format!("Allow {device_name} to use {service}?"))
     }
 
     fn cancel(&mut self) -> Result<(), dbus::MethodErr> {
@@ -1,17 +1,146 @@
 // vim: cc=81
-use crate::utils::{self, ansi::*};
+use crate::{
+    term::{self, ansi::*},
+    text,
+};
 use regex::Regex;
+use serde::{ser::SerializeSeq, Deserialize, Serialize, Serializer};
+use serde_json::{json, Value};
 use std::{
+    cmp::Ordering,
+    collections::HashMap,
     io::{stdout, Write},
+    panic,
     sync::Weak,
     sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use super::{
+    media, BluetoothManager, BtError, FieldQuery, MediaCommand, MediaStatus, MediaTransport,
 };
 
-use super::BluetoothManager;
+/// Names of the device fields usable as output columns
+pub const DEVICE_FIELDS: [&str; 13] = [
+    "address",
+    "name",
+    "paired",
+    "bonded",
+    "trusted",
+    "blocked",
+    "connected",
+    "remote_name",
+    "battery",
+    "icon",
+    "index",
+    "adapters",
+    "rssi",
+];
+
+/// Device types usable as filters, with the icons bluez reports for devices
+/// of the type
+pub const DEVICE_TYPES: [(&str, &[&str]); 10] = [
+    (
+        "audio",
+        &["audio-headset", "audio-headphones", "audio-card"],
+    ),
+    (
+        "input",
+        &[
+            "input-keyboard",
+            "input-mouse",
+            "input-gaming",
+            "input-tablet",
+        ],
+    ),
+    ("keyboard", &["input-keyboard"]),
+    ("mouse", &["input-mouse"]),
+    ("gamepad", &["input-gaming"]),
+    ("phone", &["phone"]),
+    ("computer", &["computer"]),
+    ("display", &["video-display"]),
+    ("camera", &["camera-photo", "camera-video"]),
+    ("printer", &["printer"]),
+];
+
+/// Glyph sets to show the type of devices with, based on their icon
+#[derive(Clone, Copy)]
+pub enum IconStyle {
+    /// Glyphs of Nerd Fonts, one column wide
+    Nerd,
+    /// Emoji, two columns wide
+    Emoji,
+}
+
+impl IconStyle {
+    /// Returns the style with the given name, "nerd" or "emoji".
+    pub fn from_name(name: &str) -> Option<IconStyle> {
+        match name {
+            "nerd" => Some(IconStyle::Nerd),
+            "emoji" => Some(IconStyle::Emoji),
+            _ => None,
+        }
+    }
 
+    /// Terminal columns taken by every glyph of the style
+    fn width(self) -> u8 {
+        match self {
+            IconStyle::Nerd => 1,
+            IconStyle::Emoji => 2,
+        }
+    }
+
+    /// Returns the glyph for the freedesktop icon name bluez reports for a
+    /// device, e.g. "audio-headset", or a generic glyph if it is unknown.
+    pub fn glyph(self, icon: Option<&str>) -> &'static str {
+        // Only emoji with emoji presentation by default, so every glyph is
+        // two columns wide
+        let (nerd, emoji) = match icon.unwrap_or_default() {
+            "audio-headset" => ("\u{f02ce}", "🎧"),
+            "audio-headphones" => ("\u{f02cb}", "🎧"),
+            "audio-card" => ("\u{f04c3}", "🔊"),
+            "phone" => ("\u{f011c}", "📱"),
+            "computer" => ("\u{f0322}", "💻"),
+            "input-keyboard" => ("\u{f030c}", "🔤"),
+            "input-mouse" => ("\u{f037d}", "🐭"),
+            "input-gaming" => ("\u{f0297}", "🎮"),
+            "input-tablet" => ("\u{f04f6}", "📱"),
+            "video-display" => ("\u{f0379}", "📺"),
+            "camera-photo" | "camera-video" => ("\u{f0100}", "📷"),
+            "printer" => ("\u{f042a}", "📠"),
+            _ => ("\u{f00af}", "🔵"),
+        };
+        match self {
+            IconStyle::Nerd => nerd,
+            IconStyle::Emoji => emoji,
+        }
+    }
+}
+
+/// Battery percentages from which batteries are shown in green (high) and
+/// yellow (low), batteries below low are shown in red
+#[derive(Clone, Copy)]
+pub struct BatteryThresholds {
+    pub high: u8,
+    pub low: u8,
+}
+
+impl Default for BatteryThresholds {
+    fn default() -> Self {
+        BatteryThresholds { high: 70, low: 30 }
+    }
+}
+
+/// A remote bluetooth device. Serializes to the properties printed by --json,
+/// leaving out the bluetooth manager and output settings. A deserialized device
+/// has no bluetooth manager, so there is no Deserialize for DeviceList.
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
 pub struct Device<M: BluetoothManager> {
     pub address: String,
     pub name: String,
+    #[serde(skip, default = "Weak::new")]
     pub bluetooth_manager: Weak<Mutex<M>>,
 
     pub paired: bool,
@@ -24,9 +153,25 @@ pub struct Device<M: BluetoothManager> {
     pub remote_name: Option<String>,
     pub battery: Option<u8>,
     pub icon: Option<String>,
+    /// Persistent index used as a short handle for the device
+    pub index: Option<u32>,
+    /// Names of the adapters the device is reachable through
+    pub adapters: Vec<String>,
+    /// Signal strength in dBm, only known for recently discovered devices
+    pub rssi: Option<i16>,
+    /// Audio streams, only read for info (see update_transports)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub transports: Vec<MediaTransport>,
+    /// Local nicknames given to the device in the config file
+    #[serde(skip)]
+    pub nicknames: Vec<String>,
 
     // Allow ANSI code color in output from this struct
+    #[serde(skip)]
     pub name_in_color: bool,
+    // Percentages at which the battery color changes
+    #[serde(skip)]
+    pub battery_thresholds: BatteryThresholds,
 }
 
 enum InfoType<'a> {
@@ -59,83 +204,243 @@ impl<M: BluetoothManager> Device<M> {
             remote_name: None,
             battery: None,
             icon: None,
+            index: None,
+            adapters: Vec::new(),
+            rssi: None,
+            transports: Vec::new(),
+            nicknames: Vec::new(),
 
             name_in_color: true,
+            battery_thresholds: BatteryThresholds::default(),
         }
     }
 
-    /// Attempts to pair with device
-    pub fn pair(&mut self) -> bool {
-        println!("Attempting to pair with {}...", self.get_name_colored());
-        let success = self.bluetooth_manager.upgrade().is_some_and(|bt_man| {
-            bt_man
-                .lock()
-                .expect("Mutex should not be poisoned.")
-                .pair_device(&self)
-        });
-        if success {
+    /// Calls call with the bluetooth_manager of the device. Fails with
+    /// BtError::ManagerUnavailable if bluetooth_manager is invalid.
+    fn with_manager<T>(&self, call: impl FnOnce(&M) -> Result<T, BtError>) -> Result<T, BtError> {
+        let bt_man = self
+            .bluetooth_manager
+            .upgrade()
+            .ok_or(BtError::ManagerUnavailable)?;
+        let bt_man = bt_man.lock().expect("Mutex should not be poisoned.");
+        call(&bt_man)
+    }
+
+    /// Attempts to pair with device.
+    pub fn pair(&mut self) -> Result<(), BtError> {
+        self.pair_with(false, false)
+    }
+
+    /// Attempts to pair with device. If trust is true, the device is trusted
+    /// once paired, so it can reconnect on its own later. If connect is true,
+    /// it is connected afterwards. Failing to trust or connect it fails the
+    /// pairing.
+    pub fn pair_with(&mut self, trust: bool, connect: bool) -> Result<(), BtError> {
+        let mut result = self.with_manager(|bt_man| bt_man.pair_device(self));
+        if let Ok(()) | Err(BtError::AlreadyPaired) = &result {
             self.paired = true;
-            println!("{} paired.", self.get_name_colored());
-            self.connect();
+            if trust {
+                if let Err(error) = self.trust() {
+                    result = Err(error);
+                }
+            }
+            if connect && result.is_ok() {
+                result = self.connect();
+            }
+        }
+        result
+    }
+
+    /// Connects the device, pairing with it first if it is not paired yet.
+    pub fn connect_or_pair(&mut self) -> Result<(), BtError> {
+        if self.paired {
+            self.connect()
         } else {
-            println!("Could not pair {}.", self.get_name_colored());
+            self.pair_with(false, true)
         }
-        success
     }
 
-    /// Unpairs the device. Only fails if bluetooth_manager is invalid.
-    pub fn unpair(&mut self) -> bool {
-        let success = self.bluetooth_manager.upgrade().is_some_and(|bt_man| {
-            bt_man
-                .lock()
-                .expect("Mutex should not be poisoned.")
-                .unpair_device(&self);
-            true
-        });
-        if success {
+    /// Unpairs the device.
+    pub fn unpair(&mut self) -> Result<(), BtError> {
+        let result = self.with_manager(|bt_man| bt_man.unpair_device(self));
+        if result.is_ok() {
             self.paired = false;
             self.connected = false;
-            println!("{} unpaired.", self.get_name_colored());
-        } else {
-            println!("Could not unpair {}.", self.get_name_colored());
         }
-        success
+        result
     }
 
     /// Attempts to connect to device
-    pub fn connect(&mut self) -> bool {
-        println!("Attempting to connect with {}...", self.get_name_colored());
-        let success = self.bluetooth_manager.upgrade().is_some_and(|bt_man| {
-            bt_man
-                .lock()
-                .expect("Mutex should not be poisoned.")
-                .connect_device(&self)
-        });
-        if success {
+    pub fn connect(&mut self) -> Result<(), BtError> {
+        let result = self.with_manager(|bt_man| bt_man.connect_device(self));
+        if let Ok(()) | Err(BtError::AlreadyConnected) = &result {
             self.connected = true;
-            println!("{} connected.", self.get_name_colored());
-        } else {
-            println!("Could not connect {}.", self.get_name_colored());
         }
-        success
+        result
     }
 
-    /// Disconnects the device. Only fails if bluetooth_manager is invalid.
-    pub fn disconnect(&mut self) -> bool {
-        let success = self.bluetooth_manager.upgrade().is_some_and(|bt_man| {
-            bt_man
-                .lock()
-                .expect("Mutex should not be poisoned.")
-                .disconnect_device(&self);
-            true
-        });
-        if success {
+    /// Connects only the profile with the service UUID of the device.
+    pub fn connect_profile(&mut self, uuid: &str) -> Result<(), BtError> {
+        let result = self.with_manager(|bt_man| bt_man.connect_device_profile(self, uuid));
+        if let Ok(()) | Err(BtError::AlreadyConnected) = &result {
+            self.connected = true;
+        }
+        result
+    }
+
+    /// Disconnects the device.
+    pub fn disconnect(&mut self) -> Result<(), BtError> {
+        let result = self.with_manager(|bt_man| bt_man.disconnect_device(self));
+        if result.is_ok() {
             self.connected = false;
-            println!("{} disconnected.", self.get_name_colored());
+        }
+        result
+    }
+
+    /// Disconnects only the profile with the service UUID of the device. The
+    /// device stays connected unless bluez disconnects it after its last
+    /// profile, which the next update shows.
+    pub fn disconnect_profile(&mut self, uuid: &str) -> Result<(), BtError> {
+        self.with_manager(|bt_man| bt_man.disconnect_device_profile(self, uuid))
+    }
+
+    /// Switches the device to the profile with the service UUID connect by
+    /// connecting it before disconnecting the profile with the UUID
+    /// disconnect, so the device does not disconnect in between. Succeeds if
+    /// the disconnected profile was not connected.
+    pub fn switch_profile(&mut self, connect: &str, disconnect: &str) -> Result<(), BtError> {
+        match self.connect_profile(connect) {
+            Ok(()) | Err(BtError::AlreadyConnected) => (),
+            Err(error) => return Err(error),
+        }
+        match self.disconnect_profile(disconnect) {
+            Err(BtError::NotConnected) => Ok(()),
+            result => result,
+        }
+    }
+
+    /// Sends a playback command to the media player of the device.
+    pub fn control_media(&mut self, command: MediaCommand) -> Result<(), BtError> {
+        self.with_manager(|bt_man| bt_man.control_media(self, command))
+    }
+
+    /// Returns what the media player of the device is playing.
+    pub fn media_status(&self) -> Result<MediaStatus, BtError> {
+        self.with_manager(|bt_man| bt_man.media_status(self))
+    }
+
+    /// Reads the audio streams of the device into transports. They are left
+    /// empty if the device is not connected or they cannot be read.
+    pub fn update_transports(&mut self) {
+        self.transports = if self.connected {
+            self.with_manager(|bt_man| bt_man.media_transports(self))
+                .unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+    }
+
+    /// Returns the volume of the audio stream of the device in percent.
+    pub fn volume(&self) -> Result<u8, BtError> {
+        let volume = self.with_manager(|bt_man| bt_man.media_volume(self))?;
+        Ok(media::volume_to_percent(volume))
+    }
+
+    /// Sets the volume of the audio stream of the device in percent.
+    pub fn set_volume(&mut self, percent: u8) -> Result<(), BtError> {
+        let volume = media::percent_to_volume(percent);
+        self.with_manager(|bt_man| bt_man.set_media_volume(self, volume))
+    }
+
+    /// Changes the volume of the audio stream of the device by change
+    /// percentage points, staying within 0 and 100%.
+    pub fn change_volume(&mut self, change: i8) -> Result<(), BtError> {
+        let percent = self.volume()?.saturating_add_signed(change).min(100);
+        self.set_volume(percent)
+    }
+
+    /// Waits until the device is connected (or disconnected if connected is
+    /// false). Fails if the timeout elapses or bluetooth_manager is invalid.
+    pub fn wait_for_connected(
+        &mut self,
+        connected: bool,
+        timeout: &Duration,
+    ) -> Result<(), BtError> {
+        let result =
+            self.with_manager(|bt_man| bt_man.wait_for_connected(self, connected, timeout));
+        if result.is_ok() {
+            self.connected = connected;
+        }
+        result
+    }
+
+    /// Waits until the services of the connected device are resolved. Fails
+    /// if the device is not connected, the timeout elapses or
+    /// bluetooth_manager is invalid.
+    pub fn wait_for_services(&mut self, timeout: &Duration) -> Result<(), BtError> {
+        if !self.connected {
+            return Err(BtError::NotConnected);
+        }
+        self.with_manager(|bt_man| bt_man.wait_for_services_resolved(self, timeout))
+    }
+
+    /// Renames the device locally. An empty alias restores the remote name.
+    pub fn rename(&mut self, alias: &str) -> Result<(), BtError> {
+        let result = self.with_manager(|bt_man| bt_man.set_device_alias(self, alias));
+        if result.is_ok() {
+            self.name = match (alias, &self.remote_name) {
+                ("", Some(remote_name)) => remote_name.clone(),
+                _ => alias.to_string(),
+            };
+        }
+        result
+    }
+
+    /// Blocks the device, refusing any connections from it until unblocked.
+    pub fn block(&mut self) -> Result<(), BtError> {
+        self.set_blocked(true)
+    }
+
+    /// Unblocks the device.
+    pub fn unblock(&mut self) -> Result<(), BtError> {
+        self.set_blocked(false)
+    }
+
+    fn set_blocked(&mut self, blocked: bool) -> Result<(), BtError> {
+        let result = self.with_manager(|bt_man| bt_man.set_device_blocked(self, blocked));
+        if result.is_ok() {
+            self.blocked = blocked;
+            // Blocked devices are disconnected by the bluetooth stack
+            self.connected &= !blocked;
+        }
+        result
+    }
+
+    /// Trusts the device, allowing it to connect without confirmation.
+    pub fn trust(&mut self) -> Result<(), BtError> {
+        self.set_trusted(true)
+    }
+
+    /// Stops trusting the device.
+    pub fn untrust(&mut self) -> Result<(), BtError> {
+        self.set_trusted(false)
+    }
+
+    fn set_trusted(&mut self, trusted: bool) -> Result<(), BtError> {
+        let result = self.with_manager(|bt_man| bt_man.set_device_trusted(self, trusted));
+        if result.is_ok() {
+            self.trusted = trusted;
+        }
+        result
+    }
+
+    /// Connects the device if it is disconnected and disconnects it otherwise.
+    pub fn toggle(&mut self) -> Result<(), BtError> {
+        if self.connected {
+            self.disconnect()
         } else {
-            println!("Could not disconnect {}.", self.get_name_colored());
+            self.connect()
         }
-        success
     }
 
     /// ANSI color escape sequence based on device state.
@@ -170,6 +475,60 @@ impl<M: BluetoothManager> Device<M> {
         )
     }
 
+    /// ANSI color escape sequence for a battery percentage if name_in_color is
+    /// true, "" else.
+    pub fn battery_color(&self, percentage: u8) -> &str {
+        if !self.name_in_color {
+            return "";
+        }
+        let thresholds = self.battery_thresholds;
+        if percentage >= thresholds.high {
+            ANSI_GREEN
+        } else if percentage >= thresholds.low {
+            ANSI_YELLOW
+        } else {
+            ANSI_RED
+        }
+    }
+
+    /// Battery percentage like " 85%" as shown after the name in the grid
+    /// output, "" if it is unknown. Includes ANSI color codes if name_in_color
+    /// is true.
+    pub fn battery_suffix(&self) -> String {
+        match self.battery {
+            Some(percentage) => format!(
+                " {}{percentage}%{}",
+                self.battery_color(percentage),
+                self.ansi_color_reset()
+            ),
+            None => String::new(),
+        }
+    }
+
+    /// Returns the length of the device name plus the battery percentage
+    /// shown after it in the grid output.
+    pub fn grid_len(&self) -> u8 {
+        // Space and percent sign are added to the digits
+        let battery_len = self.battery.map_or(0, |b| b.to_string().len() as u8 + 2);
+        self.name_len() + battery_len
+    }
+
+    /// State flags as shown by the long listing format, one character per
+    /// state: p(aired), b(onded), t(rusted), x (blocked), c(onnected) or "-"
+    /// if the device is not in that state.
+    pub fn flags(&self) -> String {
+        [
+            (self.paired, 'p'),
+            (self.bonded, 'b'),
+            (self.trusted, 't'),
+            (self.blocked, 'x'),
+            (self.connected, 'c'),
+        ]
+        .iter()
+        .map(|(state, flag)| if *state { *flag } else { '-' })
+        .collect()
+    }
+
     /// Quoted name if it contains whitespace, otherwise placeholder is added
     /// instead. Includes ANSI color codes if name_in_color is true.
     pub fn quoted_name(&self, quotes: &str, placeholder: &str) -> String {
@@ -189,6 +548,7 @@ impl<M: BluetoothManager> Device<M> {
     /// Will print detailed information about the device.
     pub fn print_info(&self) {
         let mut print_str = format!("{} {}", self.address, self.get_name_colored());
+        let adapters = Some(self.adapters.join(", ")).filter(|a| !a.is_empty());
         let print_props = Vec::from([
             ("\n\tPaired: ", InfoType::Boolean(&self.paired)),
             ("\n\tBonded: ", InfoType::Boolean(&self.bonded)),
@@ -201,11 +561,30 @@ impl<M: BluetoothManager> Device<M> {
                 InfoType::OptBattery(&self.battery),
             ),
             ("\n\tIcon: ", InfoType::OptString(&self.icon)),
+            ("\n\tAdapters: ", InfoType::OptString(&adapters)),
         ]);
-        let (ansi_red, ansi_yellow, ansi_green) = if self.name_in_color {
-            (ANSI_RED, ANSI_YELLOW, ANSI_GREEN)
+        let transports: Vec<Option<String>> = self
+            .transports
+            .iter()
+            .map(|transport| {
+                Some(match transport.volume {
+                    Some(volume) => format!(
+                        "{} ({}, volume {volume}%)",
+                        transport.codec, transport.state
+                    ),
+                    None => format!("{} ({})", transport.codec, transport.state),
+                })
+            })
+            .collect();
+        let print_props = print_props.into_iter().chain(
+            transports
+                .iter()
+                .map(|transport| ("\n\tAudio: ", InfoType::OptString(transport))),
+        );
+        let (ansi_red, ansi_green) = if self.name_in_color {
+            (ANSI_RED, ANSI_GREEN)
         } else {
-            ("", "", "")
+            ("", "")
         };
         let ansi_reset = self.ansi_color_reset();
         for (prefix, property) in print_props {
@@ -221,11 +600,7 @@ impl<M: BluetoothManager> Device<M> {
                     ),
                     InfoType::OptBattery(Some(percentage)) => format!(
                         "{prefix}{}{}{ansi_reset}",
-                        match percentage {
-                            70.. => ansi_green,
-                            30.. => ansi_yellow,
-                            _ => ansi_red,
-                        },
+                        self.battery_color(*percentage),
                         percentage
                     ),
                     _ => String::new(),
@@ -234,6 +609,85 @@ impl<M: BluetoothManager> Device<M> {
         println!("{print_str}");
     }
 
+    /// Returns the value of the field with the given name (see DEVICE_FIELDS)
+    /// as plain text. Unset and unknown fields are returned as empty string.
+    pub fn field(&self, field: &str) -> String {
+        match field {
+            "address" => self.address.clone(),
+            "name" => self.name.clone(),
+            "paired" => self.paired.to_string(),
+            "bonded" => self.bonded.to_string(),
+            "trusted" => self.trusted.to_string(),
+            "blocked" => self.blocked.to_string(),
+            "connected" => self.connected.to_string(),
+            "remote_name" => self.remote_name.clone().unwrap_or_default(),
+            "battery" => self.battery.map(|b| b.to_string()).unwrap_or_default(),
+            "icon" => self.icon.clone().unwrap_or_default(),
+            "index" => self.index.map(|i| i.to_string()).unwrap_or_default(),
+            "adapters" => self.adapters.join(","),
+            "rssi" => self.rssi.map(|r| r.to_string()).unwrap_or_default(),
+            _ => String::new(),
+        }
+    }
+
+    /// Renders template by replacing placeholders of the form {field} with the
+    /// values of the fields (see DEVICE_FIELDS). Unknown placeholders are kept
+    /// as they are, "{{" and "}}" produce literal braces.
+    pub fn format(&self, template: &str) -> String {
+        let mut output = String::new();
+        let mut rest = template;
+        while let Some(start) = rest.find(['{', '}']) {
+            output.push_str(&rest[..start]);
+            rest = &rest[start..];
+            if rest.starts_with("{{") || rest.starts_with("}}") {
+                output.push_str(&rest[..1]);
+                rest = &rest[2..];
+                continue;
+            }
+            let placeholder = if rest.starts_with('{') {
+                rest.find('}').map(|end| (end, &rest[1..end]))
+            } else {
+                None
+            };
+            match placeholder {
+                Some((end, field)) if DEVICE_FIELDS.contains(&field) => {
+                    output.push_str(&self.field(field));
+                    rest = &rest[end + 1..];
+                }
+                _ => {
+                    output.push_str(&rest[..1]);
+                    rest = &rest[1..];
+                }
+            }
+        }
+        output.push_str(rest);
+        output
+    }
+
+    /// Compares two devices by the field with the given name. Names are
+    /// compared case insensitively, connected and paired devices come first
+    /// and devices without battery information come last.
+    pub fn cmp_by_field(&self, other: &Device<M>, field: &str) -> Ordering {
+        match field {
+            "name" => self.name.to_lowercase().cmp(&other.name.to_lowercase()),
+            "address" => self.address.cmp(&other.address),
+            "battery" => match (self.battery, other.battery) {
+                (Some(battery), Some(other_battery)) => battery.cmp(&other_battery),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            },
+            "connected" => other.connected.cmp(&self.connected),
+            "paired" => other.paired.cmp(&self.paired),
+            _ => Ordering::Equal,
+        }
+    }
+
+    /// Returns the device properties as a JSON object.
+    pub fn to_json(&self) -> Value {
+        serde_json::to_value(self).expect("Devices should be serializable")
+    }
+
     /// Returns the length of the device name (as an u8 because
     /// the bluetooth specification limits name length to 248.
     /// See Section 6.23: https://www.bluetooth.com/specifications/core54-html/)
@@ -245,6 +699,41 @@ impl<M: BluetoothManager> Device<M> {
             .expect("Name length should adhere to bluetooth specification")
     }
 
+    /// Returns whether the device is of the given type (see DEVICE_TYPES),
+    /// according to its icon.
+    pub fn is_type(&self, device_type: &str) -> bool {
+        DEVICE_TYPES.iter().any(|(name, icons)| {
+            *name == device_type && icons.contains(&self.icon.as_deref().unwrap_or_default())
+        })
+    }
+
+    /// Returns whether matches returns true for the name or a nickname of
+    /// the device.
+    pub fn any_name(&self, matches: impl Fn(&str) -> bool) -> bool {
+        matches(&self.name) || self.nicknames.iter().any(|nickname| matches(nickname))
+    }
+
+    /// Updates the state of this device from other, a newer copy of it. The
+    /// adapters and index are kept, as they may have been set by a merge or
+    /// the persistent index.
+    fn update_from(&mut self, other: &Device<M>) {
+        self.name.clone_from(&other.name);
+        self.paired = other.paired;
+        self.bonded = other.bonded;
+        self.trusted = other.trusted;
+        self.blocked = other.blocked;
+        self.connected = other.connected;
+        self.remote_name.clone_from(&other.remote_name);
+        self.battery = other.battery;
+        self.icon.clone_from(&other.icon);
+        self.index = other.index.or(self.index);
+        self.rssi = other.rssi;
+        self.transports.clone_from(&other.transports);
+        self.nicknames.clone_from(&other.nicknames);
+        self.name_in_color = other.name_in_color;
+        self.battery_thresholds = other.battery_thresholds;
+    }
+
     /// Sets the bluetooth manager of this device to a Weak downgraded from
     /// the passed Arc
     pub fn set_bluetooth_manager(&mut self, bt_man: &Arc<Mutex<M>>) {
@@ -252,18 +741,19 @@ impl<M: BluetoothManager> Device<M> {
     }
 }
 
+/// Results of an operation on the devices of a DeviceList, with the address
+/// of the device each belongs to. Errors are left to the caller to report.
+pub type DeviceResults = Vec<(String, Result<(), BtError>)>;
+
 /// Macro for DeviceList, used to call a method on all devices in
-/// the list and return the sum of the return values of the successful method
-/// calls (usuallly evaluating to the amount of devices paired or similar)
-macro_rules! _async_all_devices {
-    ($func:ident, $x:ident) => {
-        pub fn $func(&self) -> i32 {
-            let mut ret_count: i32 = 0;
-            for device in &self.devices {
-                let mut device = device.lock().expect("Mutex should not be poisoned.");
-                ret_count += i32::from(device.$x());
-            }
-            ret_count
+/// the list with the given runner and return the result for each device
+macro_rules! all_devices {
+    ($func:ident, $x:ident, $run:ident) => {
+        pub fn $func(&self) -> DeviceResults
+        where
+            M: Send,
+        {
+            self.$run(Device::$x)
         }
     };
 }
@@ -272,20 +762,82 @@ pub type Devices<M> = Vec<Arc<Mutex<Device<M>>>>;
 
 pub struct DeviceList<M: BluetoothManager> {
     devices: Devices<M>,
+    /// Positions of the devices in devices by their uppercase address.
+    /// Devices reachable through several adapters have an entry for each
+    /// adapter unless they are merged.
+    positions: HashMap<String, Vec<usize>>,
     bluetooth_manager: Arc<Mutex<M>>,
+    /// Nicknames with the name or address of the device they refer to
+    nicknames: Vec<(String, String)>,
 
     // Following properties are saved for output
     quote_names: bool,
+    show_index: bool,
+    /// Highest persistent index of the devices, to align the index column
+    max_index: u32,
+    print_header: bool,
     print_in_color: bool,
-    max_name_len: u8,
-    min_name_len: u8,
+    battery_thresholds: BatteryThresholds,
+    icons: Option<IconStyle>,
+    max_entry_len: u8,
+    min_entry_len: u8,
 }
 
+#[derive(Clone, Copy)]
 pub enum FilterBehaviour {
     Full,
     Contains,
     FullRegex,
     ContainsRegex,
+    /// Matches names containing the filter as a subsequence or with a typo,
+    /// keeping only the best matching devices
+    Fuzzy,
+}
+
+impl FilterBehaviour {
+    /// Returns whether name matches filterstr with this behaviour, like the
+    /// filtered_name functions of DeviceList. Invalid regexes match nothing.
+    pub fn matches(&self, name: &str, filterstr: &str) -> bool {
+        match self {
+            FilterBehaviour::Full => name == filterstr,
+            FilterBehaviour::Contains => name.contains(filterstr),
+            FilterBehaviour::FullRegex => Regex::new(filterstr).is_ok_and(|re| re.is_match(name)),
+            FilterBehaviour::ContainsRegex => {
+                Regex::new(filterstr).is_ok_and(|re| re.find(name).is_some())
+            }
+            FilterBehaviour::Fuzzy => fuzzy_score(name, filterstr).is_some(),
+        }
+    }
+
+    /// Returns whether address matches filterstr with this behaviour, like
+    /// the filtered_address functions of DeviceList.
+    pub fn matches_address(&self, address: &str, filterstr: &str) -> bool {
+        match self {
+            FilterBehaviour::Full => address.eq_ignore_ascii_case(filterstr),
+            // Addresses are too similar to each other for fuzzy matching
+            FilterBehaviour::Contains | FilterBehaviour::Fuzzy => {
+                address.to_uppercase().contains(&filterstr.to_uppercase())
+            }
+            _ => self.matches(address, filterstr),
+        }
+    }
+}
+
+/// Returns how well filterstr matches name for FilterBehaviour::Fuzzy, or
+/// None if it does not match. Matches as a subsequence rank above matches of
+/// a word of name with a typo, which are allowed one mistake per four
+/// characters of filterstr.
+fn fuzzy_score(name: &str, filterstr: &str) -> Option<(bool, u32)> {
+    if let Some(score) = text::fuzzy_score(filterstr, name) {
+        return Some((true, score));
+    }
+    let allowed = filterstr.chars().count() / 4;
+    let distance = name
+        .split(|c: char| !c.is_alphanumeric())
+        .chain(std::iter::once(name))
+        .map(|word| text::edit_distance(word, filterstr))
+        .min()?;
+    (distance <= allowed).then(|| (false, (allowed - distance) as u32))
 }
 
 impl<M: BluetoothManager> DeviceList<M> {
@@ -293,27 +845,111 @@ impl<M: BluetoothManager> DeviceList<M> {
     pub fn new(bluetooth_manager: Arc<Mutex<M>>) -> DeviceList<M> {
         DeviceList {
             devices: Vec::new(),
+            positions: HashMap::new(),
             bluetooth_manager,
+            nicknames: Vec::new(),
             quote_names: false,
+            show_index: false,
+            max_index: 0,
+            print_header: false,
             print_in_color: true,
-            max_name_len: 0,
-            min_name_len: 0,
+            battery_thresholds: BatteryThresholds::default(),
+            icons: None,
+            max_entry_len: 0,
+            min_entry_len: 0,
         }
     }
 
-    /// Adds a device to this DeviceList
+    /// Returns the position of the entry for device, which is locked as
+    /// locked. That is device itself or an entry with the same address and
+    /// an adapter in common.
+    fn position_of(&self, device: &Arc<Mutex<Device<M>>>, locked: &Device<M>) -> Option<usize> {
+        self.positions
+            .get(&locked.address.to_uppercase())?
+            .iter()
+            .copied()
+            .find(|&position| {
+                let known = &self.devices[position];
+                if Arc::ptr_eq(known, device) {
+                    return true;
+                }
+                let known = known.lock().expect("Mutex should not be poisoned.");
+                known.adapters == locked.adapters
+                    || known
+                        .adapters
+                        .iter()
+                        .any(|adapter| locked.adapters.contains(adapter))
+            })
+    }
+
+    /// Adds a device to this DeviceList. If the list already contains the
+    /// device (e.g. when filling it again), the state of the contained one is
+    /// updated in place instead.
     pub fn add_device(&mut self, new: Arc<Mutex<Device<M>>>) {
         let mut device = new.lock().expect("Mutex should not be poisoned.");
         device.set_bluetooth_manager(&self.bluetooth_manager);
+        device.nicknames = self
+            .nicknames
+            .iter()
+            .filter(|(_, target)| {
+                *target == device.name || target.eq_ignore_ascii_case(&device.address)
+            })
+            .map(|(nickname, _)| nickname.clone())
+            .collect();
 
         self.quote_names |= device.name.contains(char::is_whitespace);
         device.name_in_color = self.print_in_color;
-        let name_len = device.name_len();
-        self.max_name_len = self.max_name_len.max(name_len);
-        self.min_name_len = self.max_name_len.min(name_len);
+        device.battery_thresholds = self.battery_thresholds;
 
-        drop(device);
-        self.devices.push(new);
+        match self.position_of(&new, &device) {
+            Some(position) => {
+                let known = &self.devices[position];
+                if !Arc::ptr_eq(known, &new) {
+                    known
+                        .lock()
+                        .expect("Mutex should not be poisoned.")
+                        .update_from(&device);
+                }
+                drop(device);
+                // The entry may have become shorter than before
+                self.update_widths();
+            }
+            None => {
+                self.max_index = self.max_index.max(device.index.unwrap_or(0));
+                let entry_len = device.grid_len();
+                self.max_entry_len = self.max_entry_len.max(entry_len);
+                self.min_entry_len = if self.devices.is_empty() {
+                    entry_len
+                } else {
+                    self.min_entry_len.min(entry_len)
+                };
+                self.positions
+                    .entry(device.address.to_uppercase())
+                    .or_default()
+                    .push(self.devices.len());
+                drop(device);
+                self.devices.push(new);
+            }
+        }
+    }
+
+    /// Recomputes the largest index and the entry lengths used for output
+    /// from all devices.
+    fn update_widths(&mut self) {
+        self.max_index = 0;
+        self.max_entry_len = 0;
+        self.min_entry_len = 0;
+        for (position, device) in self.devices.iter().enumerate() {
+            let device = device.lock().expect("Mutex should not be poisoned.");
+            self.max_index = self.max_index.max(device.index.unwrap_or(0));
+            let entry_len = device.grid_len();
+            self.max_entry_len = self.max_entry_len.max(entry_len);
+            self.min_entry_len = if position == 0 {
+                entry_len
+            } else {
+                self.min_entry_len.min(entry_len)
+            };
+        }
     }
 
     /// Fills the device list with devices, optionally scanning for unpaired
@@ -335,43 +971,143 @@ impl<M: BluetoothManager> DeviceList<M> {
     where
         F: Fn(&Device<M>) -> bool,
     {
-        let mut retval = DeviceList::new(Arc::clone(&self.bluetooth_manager));
+        let mut retval = self.empty_like();
         for device_ref in &self.devices {
             let mut matches = false;
             if let Ok(device) = device_ref.lock() {
                 matches = filter(&device);
             }
             if matches {
-                retval.devices.push(Arc::clone(&device_ref));
+                retval.add_device(Arc::clone(device_ref));
             }
         }
         retval
     }
 
-    /// Returns devices in device list with name matching the filterstr, with
-    /// "matching" defined according to behaviour.
-    pub fn filtered_name(&self, filterstr: &str, behaviour: FilterBehaviour) -> DeviceList<M> {
-        match behaviour {
-            FilterBehaviour::Full => self.filtered_name_full(filterstr),
-            FilterBehaviour::Contains => self.filtered_name_contains(filterstr),
-            FilterBehaviour::FullRegex => self.filtered_name_full_regex(filterstr),
-            FilterBehaviour::ContainsRegex => self.filtered_name_contains_regex(filterstr),
+    /// Returns an empty device list with the same bluetooth manager and
+    /// output settings as self
+    fn empty_like(&self) -> DeviceList<M> {
+        let mut retval = DeviceList::new(Arc::clone(&self.bluetooth_manager));
+        retval.nicknames = self.nicknames.clone();
+        retval.quote_names = self.quote_names;
+        retval.show_index = self.show_index;
+        retval.print_header = self.print_header;
+        retval.print_in_color = self.print_in_color;
+        retval.battery_thresholds = self.battery_thresholds;
+        retval.icons = self.icons;
+        retval
+    }
+
+    /// Returns devices in device list with name matching any of filterstrs,
+    /// with "matching" defined according to behaviour. Devices are ordered by
+    /// the first filter they match and contained only once.
+    pub fn filtered_name<S: AsRef<str>>(
+        &self,
+        filterstrs: &[S],
+        behaviour: FilterBehaviour,
+    ) -> DeviceList<M> {
+        let mut retval = self.empty_like();
+        for filterstr in filterstrs {
+            let filterstr = filterstr.as_ref();
+            retval.extend_unique(match behaviour {
+                FilterBehaviour::Full => self.filtered_name_full(filterstr),
+                FilterBehaviour::Contains => self.filtered_name_contains(filterstr),
+                FilterBehaviour::FullRegex => self.filtered_name_full_regex(filterstr),
+                FilterBehaviour::ContainsRegex => self.filtered_name_contains_regex(filterstr),
+                FilterBehaviour::Fuzzy => self.filtered_name_fuzzy(filterstr),
+            });
+        }
+        retval
+    }
+
+    /// Returns devices in device list of any of types (see DEVICE_TYPES).
+    pub fn filtered_type<S: AsRef<str>>(&self, types: &[S]) -> DeviceList<M> {
+        self.filtered(|device| types.iter().any(|t| device.is_type(t.as_ref())))
+    }
+
+    /// Returns devices in device list fulfilling all of queries.
+    pub fn filtered_fields(&self, queries: &[FieldQuery]) -> DeviceList<M> {
+        self.filtered(|device| queries.iter().all(|query| query.matches(device)))
+    }
+
+    /// Returns devices in device list with address matching any of
+    /// filterstrs, with "matching" defined according to behaviour. Devices
+    /// are ordered by the first filter they match and contained only once.
+    pub fn filtered_address<S: AsRef<str>>(
+        &self,
+        filterstrs: &[S],
+        behaviour: FilterBehaviour,
+    ) -> DeviceList<M> {
+        let mut retval = self.empty_like();
+        for filterstr in filterstrs {
+            let filterstr = filterstr.as_ref();
+            retval.extend_unique(match behaviour {
+                FilterBehaviour::Full => self.filtered_address_full(filterstr),
+                FilterBehaviour::Contains | FilterBehaviour::Fuzzy => {
+                    self.filtered_address_contains(filterstr)
+                }
+                FilterBehaviour::FullRegex => self.filtered_address_full_regex(filterstr),
+                FilterBehaviour::ContainsRegex => self.filtered_address_contains_regex(filterstr),
+            });
+        }
+        retval
+    }
+
+    /// Returns devices in device list with given address, ignoring case
+    pub fn filtered_address_full(&self, address: &str) -> DeviceList<M> {
+        self.filtered(|device| device.address.eq_ignore_ascii_case(address))
+    }
+
+    /// Returns devices in device list with address containing substr,
+    /// ignoring case
+    pub fn filtered_address_contains(&self, substr: &str) -> DeviceList<M> {
+        let substr = substr.to_uppercase();
+        self.filtered(|device| device.address.to_uppercase().contains(&substr))
+    }
+
+    /// Returns devices in device list with address matching regex.
+    pub fn filtered_address_full_regex(&self, regex: &str) -> DeviceList<M> {
+        match Regex::new(regex) {
+            Ok(re) => self.filtered(|device| re.is_match(&device.address)),
+            Err(_) => DeviceList::new(Arc::clone(&self.bluetooth_manager)),
+        }
+    }
+
+    /// Returns devices in device list with address containing a match for the
+    /// regex.
+    pub fn filtered_address_contains_regex(&self, regex: &str) -> DeviceList<M> {
+        match Regex::new(regex) {
+            Ok(re) => self.filtered(|device| re.find(&device.address).is_some()),
+            Err(_) => DeviceList::new(Arc::clone(&self.bluetooth_manager)),
+        }
+    }
+
+    /// Adds the devices of other that are not contained in this list yet.
+    pub fn extend_unique(&mut self, other: DeviceList<M>) {
+        for device in other.devices {
+            let locked = device.lock().expect("Mutex should not be poisoned.");
+            let position = self.position_of(&device, &locked);
+            drop(locked);
+            if position.is_none() {
+                self.add_device(device);
+            }
         }
     }
+
     /// Returns devices in device list with given name
     pub fn filtered_name_full(&self, name: &str) -> DeviceList<M> {
-        self.filtered(|device| device.name == name)
+        self.filtered(|device| device.any_name(|device_name| device_name == name))
     }
 
     /// Returns devices in device list with name containing substr
     pub fn filtered_name_contains(&self, substr: &str) -> DeviceList<M> {
-        self.filtered(|device| device.name.contains(substr))
+        self.filtered(|device| device.any_name(|device_name| device_name.contains(substr)))
     }
 
     /// Returns devices in device list with name matching regex.
     pub fn filtered_name_full_regex(&self, regex: &str) -> DeviceList<M> {
         match Regex::new(regex) {
-            Ok(re) => self.filtered(|device| re.is_match(&device.name)),
+            Ok(re) => self.filtered(|device| device.any_name(|name| re.is_match(name))),
             Err(_) => DeviceList::new(Arc::clone(&self.bluetooth_manager)),
         }
     }
@@ -379,17 +1115,166 @@ impl<M: BluetoothManager> DeviceList<M> {
     /// Returns devices in device list with name containing a match for the regex.
     pub fn filtered_name_contains_regex(&self, regex: &str) -> DeviceList<M> {
         match Regex::new(regex) {
-            Ok(re) => self.filtered(|device| re.find(&device.name).is_some()),
+            Ok(re) => self.filtered(|device| device.any_name(|name| re.find(name).is_some())),
             Err(_) => DeviceList::new(Arc::clone(&self.bluetooth_manager)),
         }
     }
 
+    /// Returns the devices in device list whose name matches fuzzy best (see
+    /// FilterBehaviour::Fuzzy). Several devices are only returned if they
+    /// match equally well.
+    pub fn filtered_name_fuzzy(&self, fuzzy: &str) -> DeviceList<M> {
+        let device_score = |device: &Device<M>| {
+            std::iter::once(&device.name)
+                .chain(&device.nicknames)
+                .filter_map(|name| fuzzy_score(name, fuzzy))
+                .max()
+        };
+        let best = self
+            .devices
+            .iter()
+            .filter_map(|device| {
+                device_score(&device.lock().expect("Mutex should not be poisoned."))
+            })
+            .max();
+        match best {
+            Some(best) => self.filtered(|device| device_score(device) == Some(best)),
+            None => self.empty_like(),
+        }
+    }
+
+    /// Returns a device list where devices with the same address (e.g. seen
+    /// through multiple adapters) are collapsed into one entry. The entry
+    /// with the most connected state is kept and annotated with the adapters
+    /// of all merged entries. The entries are copies, so the devices of this
+    /// list keep their own adapters.
+    pub fn merged(&self) -> DeviceList<M> {
+        let mut retval = self.empty_like();
+        let mut merged_addresses: Vec<String> = Vec::new();
+        for device_ref in &self.devices {
+            let address = device_ref
+                .lock()
+                .expect("Mutex should not be poisoned.")
+                .address
+                .to_uppercase();
+            if merged_addresses.contains(&address) {
+                continue;
+            }
+            let duplicates = self.filtered(|device| device.address.to_uppercase() == address);
+            // Rank by state, preferring connected over paired over unpaired
+            let state_rank = |device: &Arc<Mutex<Device<M>>>| {
+                let device = device.lock().expect("Mutex should not be poisoned.");
+                (device.connected, device.paired)
+            };
+            let kept = duplicates
+                .devices
+                .iter()
+                .max_by_key(|device| state_rank(device))
+                .map(Arc::clone)
+                .unwrap_or_else(|| Arc::clone(device_ref));
+            let mut adapters = Vec::new();
+            for duplicate in &duplicates.devices {
+                let duplicate = duplicate.lock().expect("Mutex should not be poisoned.");
+                for adapter in &duplicate.adapters {
+                    if !adapters.contains(adapter) {
+                        adapters.push(adapter.clone());
+                    }
+                }
+            }
+            let kept = kept.lock().expect("Mutex should not be poisoned.");
+            let mut merged = Device::new(
+                kept.address.clone(),
+                kept.name.clone(),
+                false,
+                false,
+                false,
+                false,
+                false,
+            );
+            merged.bluetooth_manager = kept.bluetooth_manager.clone();
+            merged.update_from(&kept);
+            merged.adapters = adapters;
+            merged_addresses.push(address);
+            retval.add_device(Arc::new(Mutex::new(merged)));
+        }
+        retval
+    }
+
+    /// Sorts the devices by the field with the given name (see
+    /// Device::cmp_by_field). Devices comparing equal keep their order.
+    pub fn sort(&mut self, field: &str, reverse: bool) {
+        self.devices.sort_by(|a, b| {
+            if Arc::ptr_eq(a, b) {
+                return Ordering::Equal;
+            }
+            let a = a.lock().expect("Mutex should not be poisoned.");
+            let b = b.lock().expect("Mutex should not be poisoned.");
+            let ordering = a.cmp_by_field(&b, field);
+            if reverse {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+        self.positions.clear();
+        for (position, device) in self.devices.iter().enumerate() {
+            let address = device
+                .lock()
+                .expect("Mutex should not be poisoned.")
+                .address
+                .to_uppercase();
+            self.positions.entry(address).or_default().push(position);
+        }
+    }
+
     /// Returns the name of the device with decorations depending on state of self
     pub fn correctly_quoted_device_name(&self, device: &Device<M>) -> String {
-        if self.quote_names {
+        let name = if self.quote_names {
             device.quoted_name("'", " ")
         } else {
             device.get_name_colored()
+        };
+        let name = match self.icons {
+            Some(style) => format!("{} {name}", style.glyph(device.icon.as_deref())),
+            None => name,
+        };
+        if self.show_index {
+            format!("{}{name}", self.index_prefix(device))
+        } else {
+            name
+        }
+    }
+
+    /// Width of the glyph and space printed before names if icons are shown,
+    /// 0 otherwise.
+    fn icon_width(&self) -> u8 {
+        self.icons.map_or(0, |style| style.width() + 1)
+    }
+
+    /// Width of what correctly_quoted_device_name prints before the name
+    /// itself: the index and icon columns and the opening quote (or the
+    /// space in its place).
+    fn name_offset(&self) -> u8 {
+        self.index_width() + self.icon_width() + u8::from(self.quote_names)
+    }
+
+    /// Width of the "[N] " column printed before names if show_index is set,
+    /// 0 otherwise.
+    fn index_width(&self) -> u8 {
+        if !self.show_index {
+            return 0;
+        }
+        // Digits of max_index plus brackets and separating space
+        self.max_index.to_string().len() as u8 + 3
+    }
+
+    /// Right aligned "[N] " prefix for device, or blank padding of the same
+    /// width if the device has no index.
+    fn index_prefix(&self, device: &Device<M>) -> String {
+        let digits = usize::from(self.index_width().saturating_sub(3));
+        match device.index {
+            Some(index) => format!("[{index:>digits$}] "),
+            None => " ".repeat(digits + 3),
         }
     }
 
@@ -413,19 +1298,113 @@ impl<M: BluetoothManager> DeviceList<M> {
     }
 
     /// Prints each device in long format (on its own line) similar to GNU ls -l
+    /// Columns are state flags (see Device::flags), battery, icon, address and
+    /// name.
     pub fn print_long(&mut self) {
+        let mut icon_width = self
+            .devices
+            .iter()
+            .filter_map(|device| {
+                let device = device.lock().expect("Mutex should not be poisoned.");
+                device.icon.as_ref().map(|icon| icon.chars().count())
+            })
+            .max()
+            .unwrap_or(0);
         let mut stdout = stdout().lock();
+        if self.print_header {
+            icon_width = icon_width.max(4);
+            // The name heading is moved by everything printed before names
+            let index_width = usize::from(self.name_offset());
+            let index = if self.show_index { "#" } else { "" };
+            let _ = writeln!(
+                stdout,
+                "FLAGS {:>4} {:<icon_width$} {:<17} {index:<index_width$}NAME",
+                "BATT", "ICON", "ADDRESS"
+            );
+        }
         for device in &self.devices {
             let device = device.lock().expect("Mutex should not be poisoned.");
+            // Devices reachable through multiple adapters (see merged) are
+            // annotated with the adapters
+            let via = if device.adapters.len() > 1 {
+                format!(" via {}", device.adapters.join(","))
+            } else {
+                String::new()
+            };
+            // Battery column is right aligned to fit "100%"
+            let battery = device.battery_suffix();
+            let battery_padding =
+                " ".repeat(5 - device.battery.map_or(0, |b| b.to_string().len() + 2));
+            let icon = device.icon.as_deref().unwrap_or("");
             let _ = writeln!(
                 stdout,
-                "{} {}",
+                "{}{battery_padding}{battery} {icon:<icon_width$} {} {}{via}",
+                device.flags(),
                 &device.address,
                 self.correctly_quoted_device_name(&device)
             );
         }
     }
 
+    /// Prints devices grouped under the adapters they are reachable through,
+    /// each adapter heading followed by its devices as a tree.
+    pub fn print_tree(&self) {
+        let adapters = self
+            .bluetooth_manager
+            .lock()
+            .expect("Mutex should not be poisoned.")
+            .get_all_adapters();
+        for adapter in &adapters {
+            let mut heading = format!("({}, {})", adapter.name, adapter.address);
+            if !adapter.alias.is_empty() {
+                heading = format!("{} {heading}", adapter.alias);
+            }
+            self.print_tree_group(&heading, |device| device.adapters.contains(&adapter.name));
+        }
+        // Devices of adapters that vanished since the update are not lost
+        let orphan = |device: &Device<M>| {
+            !adapters
+                .iter()
+                .any(|adapter| device.adapters.contains(&adapter.name))
+        };
+        if self
+            .devices
+            .iter()
+            .any(|device| orphan(&device.lock().expect("Mutex should not be poisoned.")))
+        {
+            self.print_tree_group("Unknown adapter", orphan);
+        }
+    }
+
+    /// Prints heading followed by the devices matching filter as tree
+    /// branches.
+    fn print_tree_group<F>(&self, heading: &str, filter: F)
+    where
+        F: Fn(&Device<M>) -> bool,
+    {
+        let mut stdout = stdout().lock();
+        let _ = writeln!(stdout, "{heading}");
+        let devices: Vec<_> = self
+            .devices
+            .iter()
+            .filter(|device| filter(&device.lock().expect("Mutex should not be poisoned.")))
+            .collect();
+        for (i, device) in devices.iter().enumerate() {
+            let device = device.lock().expect("Mutex should not be poisoned.");
+            let branch = if i + 1 == devices.len() {
+                "└──"
+            } else {
+                "├──"
+            };
+            let _ = writeln!(
+                stdout,
+                "{branch} {}{}",
+                self.correctly_quoted_device_name(&device),
+                device.battery_suffix()
+            );
+        }
+    }
+
     /// Prints multiple devices per line similar to GNU ls -x
     pub fn print_lines(&mut self) {
         // First find highest amount of possible columns and the best fit column
@@ -435,26 +1414,30 @@ impl<M: BluetoothManager> DeviceList<M> {
             widths: Vec<u8>,
             total_w: u16,
         }
-        let max_w: u16 = match utils::get_termsize() {
+        let max_w: u16 = match term::get_termsize() {
             Some(size) => size.cols,
             _ => 80,
         }
         .try_into()
         .unwrap_or(80);
         // Checked div prevents divide by zero for empty names
-        // Lower bound: Assume all names as long as longest
-        let min_cols = max_w.checked_div(self.max_name_len.into()).unwrap_or(0);
-        // Upper bound: Assume all names as long as shortest
-        let max_cols = max_w.checked_div(self.min_name_len.into()).unwrap_or(max_w);
-        // Fallback to print_line if max_name_len > max_w
-        // or max_name_len == 0 for the sake of simplicity
+        // Lower bound: Assume all entries as long as longest
+        let min_cols = max_w.checked_div(self.max_entry_len.into()).unwrap_or(0);
+        // Upper bound: Assume all entries as long as shortest
+        let max_cols = max_w
+            .checked_div(self.min_entry_len.into())
+            .unwrap_or(max_w);
+        // Fallback to print_line if max_entry_len > max_w
+        // or max_entry_len == 0 for the sake of simplicity
         if min_cols == 0 {
             self.print_fullline();
             return;
         }
 
         // If there are whitespaced names, also account for space used by quotes
-        let extra_char_num = 2 + 2 * u8::from(self.quote_names);
+        // and the index and icon columns if they are shown
+        let extra_char_num =
+            2 + 2 * u8::from(self.quote_names) + self.index_width() + self.icon_width();
         // Infos for every column amount considered
         let mut col_infos: Vec<ColsInfo> = Vec::new();
         col_infos.reserve((max_cols + 1 - min_cols).try_into().unwrap_or(0));
@@ -469,7 +1452,7 @@ impl<M: BluetoothManager> DeviceList<M> {
 
         for (idx, device) in self.devices.iter().enumerate() {
             let device = device.lock().expect("Mutex should not be poisoned.");
-            let device_name_len = device.name_len();
+            let device_entry_len = device.grid_len();
             for (add_cols, col_info) in col_infos.iter_mut().enumerate() {
                 // This amount of device columns has already been proven
                 // unusable. Skip to next column amount option
@@ -480,8 +1463,8 @@ impl<M: BluetoothManager> DeviceList<M> {
                 // Calculate column device would be displayed in
                 // add_cols + min_cols is amount of columns
                 let idx = idx % (add_cols + usize::from(min_cols));
-                if col_info.widths[idx] < device_name_len {
-                    let size_incr = device_name_len - col_info.widths[idx];
+                if col_info.widths[idx] < device_entry_len {
+                    let size_incr = device_entry_len - col_info.widths[idx];
                     col_info.widths[idx] += size_incr;
                     col_info.total_w += u16::from(size_incr);
                 }
@@ -490,7 +1473,7 @@ impl<M: BluetoothManager> DeviceList<M> {
 
         // Find highest amount of columns with valid display width
         let mut col_info = &ColsInfo {
-            widths: vec![self.max_name_len],
+            widths: vec![self.max_entry_len],
             total_w: max_w,
         };
         for candidate in col_infos.iter().rev() {
@@ -509,13 +1492,241 @@ impl<M: BluetoothManager> DeviceList<M> {
                 let _ = writeln!(stdout, "");
             }
             let idx = idx % col_info.widths.len();
-            let printed_str = self.correctly_quoted_device_name(&device);
-            let padding = " ".repeat((col_info.widths[idx] - device.name_len()).into());
+            let printed_str = format!(
+                "{}{}",
+                self.correctly_quoted_device_name(&device),
+                device.battery_suffix()
+            );
+            let padding = " ".repeat((col_info.widths[idx] - device.grid_len()).into());
             let _ = write!(stdout, "{printed_str}{padding}  ");
         }
         let _ = writeln!(stdout);
     }
 
+    /// Waits until all devices are connected (or disconnected if connected is
+    /// false). The timeout applies to all devices together. Returns the
+    /// result for each device.
+    pub fn wait_for_connected_all(&self, connected: bool, timeout: &Duration) -> DeviceResults {
+        let deadline = Instant::now() + *timeout;
+        self.run_sequentially(|device| {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            device.wait_for_connected(connected, &remaining)
+        })
+    }
+
+    /// Waits until the services of all connected devices are resolved. The
+    /// timeout applies to all devices together. Returns the result for each
+    /// device.
+    pub fn wait_for_services_all(&self, timeout: &Duration) -> DeviceResults {
+        let deadline = Instant::now() + *timeout;
+        self.run_sequentially(|device| {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            device.wait_for_services(&remaining)
+        })
+    }
+
+    /// Returns the amount of devices in this list
+    pub fn len(&self) -> usize {
+        self.devices.len()
+    }
+
+    /// Returns whether this list contains no devices
+    pub fn is_empty(&self) -> bool {
+        self.devices.is_empty()
+    }
+
+    /// Returns an iterator over the devices in this list
+    pub fn iter(&self) -> std::slice::Iter<'_, Arc<Mutex<Device<M>>>> {
+        self.devices.iter()
+    }
+
+    /// Prints the given fields of each device as delimiter separated values,
+    /// one device per line. With ',' as delimiter, fields are quoted as CSV
+    /// (RFC 4180), otherwise delimiters and line breaks in fields are replaced
+    /// by spaces (as usual for TSV).
+    pub fn print_separated(&self, delimiter: char, fields: &[&str]) {
+        let mut stdout = stdout().lock();
+        if self.print_header {
+            let _ = writeln!(stdout, "{}", fields.join(&delimiter.to_string()));
+        }
+        for device in &self.devices {
+            let device = device.lock().expect("Mutex should not be poisoned.");
+            let line: Vec<String> = fields
+                .iter()
+                .map(|field| {
+                    let value = device.field(field);
+                    if delimiter != ',' {
+                        value.replace([delimiter, '\n', '\r'], " ")
+                    } else if value.contains([',', '"', '\n', '\r']) {
+                        format!("\"{}\"", value.replace('"', "\"\""))
+                    } else {
+                        value
+                    }
+                })
+                .collect();
+            let _ = writeln!(stdout, "{}", line.join(&delimiter.to_string()));
+        }
+    }
+
+    /// Prints the plain value of field (see Device::field) of each device
+    /// followed by terminator, without quotes or colors.
+    pub fn print_field(&self, field: &str, terminator: char) {
+        let mut stdout = stdout().lock();
+        for device in &self.devices {
+            let device = device.lock().expect("Mutex should not be poisoned.");
+            let _ = write!(stdout, "{}{terminator}", device.field(field));
+        }
+    }
+
+    /// Prints each device rendered with the template on its own line (see
+    /// Device::format)
+    pub fn print_formatted(&self, template: &str) {
+        let mut stdout = stdout().lock();
+        for device in &self.devices {
+            let device = device.lock().expect("Mutex should not be poisoned.");
+            let _ = writeln!(stdout, "{}", device.format(template));
+        }
+    }
+
+    /// Prints all devices as a JSON array
+    pub fn print_json(&self) {
+        println!("{}", self.to_json());
+    }
+
+    /// Returns all devices as a JSON array
+    pub fn to_json(&self) -> Value {
+        serde_json::to_value(self).expect("Devices should be serializable")
+    }
+
+    /// Returns the lowest battery percentage of all devices reporting one.
+    pub fn lowest_battery(&self) -> Option<u8> {
+        self.devices
+            .iter()
+            .filter_map(|device| {
+                device
+                    .lock()
+                    .expect("Mutex should not be poisoned.")
+                    .battery
+            })
+            .min()
+    }
+
+    /// Prints each device name followed by its battery percentage on its own
+    /// line, or a notice if the list is empty.
+    pub fn print_summary(&self) {
+        if self.devices.is_empty() {
+            println!("No devices connected.");
+        }
+        for device in &self.devices {
+            let device = device.lock().expect("Mutex should not be poisoned.");
+            println!("{}{}", device.get_name_colored(), device.battery_suffix());
+        }
+    }
+
+    /// Prints the battery percentage of each device, only the number if
+    /// machine_readable is true and after the device name otherwise. Devices
+    /// without battery information are reported on stderr. Returns whether
+    /// all devices have battery information.
+    pub fn print_battery(&self, machine_readable: bool) -> bool {
+        let mut all_known = true;
+        for device in &self.devices {
+            let device = device.lock().expect("Mutex should not be poisoned.");
+            match device.battery {
+                Some(percentage) if machine_readable => println!("{percentage}"),
+                Some(_) => println!("{}{}", device.get_name_colored(), device.battery_suffix()),
+                None => {
+                    all_known = false;
+                    eprintln!("{} reports no battery level.", device.get_name_colored());
+                }
+            }
+        }
+        all_known
+    }
+
+    /// Prints a single line with the connected glyph followed by the amount
+    /// of devices and the lowest battery percentage, or only the disconnected
+    /// glyph if the list is empty. Suitable for status bars like polybar.
+    pub fn print_short(
+        &self,
+        connected_glyph: &str,
+        disconnected_glyph: &str,
+        battery_glyph: &str,
+    ) {
+        if self.devices.is_empty() {
+            println!("{disconnected_glyph}");
+            return;
+        }
+        let mut line = format!("{connected_glyph} {}", self.devices.len());
+        let lowest = self
+            .devices
+            .iter()
+            .filter(|device| {
+                device
+                    .lock()
+                    .expect("Mutex should not be poisoned.")
+                    .battery
+                    .is_some()
+            })
+            .min_by_key(|device| {
+                device
+                    .lock()
+                    .expect("Mutex should not be poisoned.")
+                    .battery
+            });
+        if let Some(device) = lowest {
+            let device = device.lock().expect("Mutex should not be poisoned.");
+            if let Some(percentage) = device.battery {
+                line += &format!(
+                    " {battery_glyph}{}{percentage}%{}",
+                    device.battery_color(percentage),
+                    device.ansi_color_reset()
+                );
+            }
+        }
+        println!("{}", line.trim_start());
+    }
+
+    /// Prints the JSON object expected by Waybar custom modules summarizing
+    /// the devices, which are assumed to be the connected ones. The class is
+    /// "connected" or "disconnected" and percentage is the lowest battery.
+    pub fn print_waybar(&self) {
+        let mut names = Vec::new();
+        let mut tooltip = Vec::new();
+        for device in &self.devices {
+            let device = device.lock().expect("Mutex should not be poisoned.");
+            names.push(device.name.clone());
+            tooltip.push(match device.battery {
+                Some(percentage) => {
+                    format!("{}\t{}\t{percentage}%", device.name, device.address)
+                }
+                None => format!("{}\t{}", device.name, device.address),
+            });
+        }
+        let class = if self.devices.is_empty() {
+            "disconnected"
+        } else {
+            "connected"
+        };
+        let mut output = json!({
+            "text": names.join(", "),
+            "tooltip": tooltip.join("\n"),
+            "class": class,
+            "alt": class,
+        });
+        if let Some(percentage) = self.lowest_battery() {
+            output["percentage"] = json!(percentage);
+        }
+        println!("{output}");
+    }
+
+    /// Calls update_transports on all devices.
+    pub fn update_transports_all(&self) {
+        for device in &self.devices {
+            let mut device = device.lock().expect("Mutex should not be poisoned.");
+            device.update_transports();
+        }
+    }
+
     /// Calls print_info on all devices
     pub fn print_info_all(&self) {
         for device in &self.devices {
@@ -524,10 +1735,153 @@ impl<M: BluetoothManager> DeviceList<M> {
         }
     }
 
-    _async_all_devices!(pair_all, pair);
-    _async_all_devices!(unpair_all, unpair);
-    _async_all_devices!(connect_all, connect);
-    _async_all_devices!(disconnect_all, disconnect);
+    /// Runs operation on all devices at the same time, each device on its
+    /// own thread with a fork of the bluetooth manager, so slow devices do not
+    /// hold up the others. Falls back to one device after another if the
+    /// manager cannot be forked. Only meant for operations that cannot prompt
+    /// through the agent, like connecting, as prompts of several devices
+    /// would interleave. Returns the result for each device.
+    fn run_concurrently<F>(&self, operation: F) -> DeviceResults
+    where
+        M: Send,
+        F: Fn(&mut Device<M>) -> Result<(), BtError> + Sync,
+    {
+        let operation = &operation;
+        let forks: Option<Vec<M>> = if self.devices.len() > 1 {
+            let bt_man = self
+                .bluetooth_manager
+                .lock()
+                .expect("Mutex should not be poisoned.");
+            self.devices.iter().map(|_| bt_man.fork()).collect()
+        } else {
+            None
+        };
+        let Some(forks) = forks else {
+            return self.run_sequentially(operation);
+        };
+        thread::scope(|scope| {
+            let handles: Vec<_> = self
+                .devices
+                .iter()
+                .zip(forks)
+                .map(|(device, fork)| {
+                    scope.spawn(move || {
+                        let fork = Arc::new(Mutex::new(fork));
+                        let mut device = device.lock().expect("Mutex should not be poisoned.");
+                        device.set_bluetooth_manager(&fork);
+                        let result = operation(&mut device);
+                        device.set_bluetooth_manager(&self.bluetooth_manager);
+                        (device.address.clone(), result)
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|panic| panic::resume_unwind(panic))
+                })
+                .collect()
+        })
+    }
+
+    /// Runs operation on one device after another. Returns the result for
+    /// each device.
+    fn run_sequentially<F>(&self, mut operation: F) -> DeviceResults
+    where
+        F: FnMut(&mut Device<M>) -> Result<(), BtError>,
+    {
+        self.devices
+            .iter()
+            .map(|device| {
+                let mut device = device.lock().expect("Mutex should not be poisoned.");
+                let result = operation(&mut device);
+                (device.address.clone(), result)
+            })
+            .collect()
+    }
+
+    all_devices!(pair_all, pair, run_sequentially);
+    all_devices!(unpair_all, unpair, run_sequentially);
+    all_devices!(connect_all, connect, run_concurrently);
+    all_devices!(connect_or_pair_all, connect_or_pair, run_sequentially);
+    all_devices!(disconnect_all, disconnect, run_concurrently);
+    all_devices!(toggle_all, toggle, run_concurrently);
+    all_devices!(block_all, block, run_sequentially);
+    all_devices!(unblock_all, unblock, run_sequentially);
+
+    /// Calls pair_with on one device after another, so pairing prompts do
+    /// not interleave. Returns the result for each device.
+    pub fn pair_all_with(&self, trust: bool, connect: bool) -> DeviceResults {
+        self.run_sequentially(|device| device.pair_with(trust, connect))
+    }
+
+    /// Calls connect_profile on all devices at the same time. Returns the
+    /// result for each device.
+    pub fn connect_profile_all(&self, uuid: &str) -> DeviceResults
+    where
+        M: Send,
+    {
+        self.run_concurrently(|device| device.connect_profile(uuid))
+    }
+
+    /// Calls disconnect_profile on all devices at the same time. Returns the
+    /// result for each device.
+    pub fn disconnect_profile_all(&self, uuid: &str) -> DeviceResults
+    where
+        M: Send,
+    {
+        self.run_concurrently(|device| device.disconnect_profile(uuid))
+    }
+
+    /// Calls switch_profile on all devices at the same time. Returns the
+    /// result for each device.
+    pub fn switch_profile_all(&self, connect: &str, disconnect: &str) -> DeviceResults
+    where
+        M: Send,
+    {
+        self.run_concurrently(|device| device.switch_profile(connect, disconnect))
+    }
+
+    /// Calls control_media on all devices. Returns the result for each
+    /// device.
+    pub fn control_media_all(&self, command: MediaCommand) -> DeviceResults {
+        self.run_sequentially(|device| device.control_media(command))
+    }
+
+    /// Calls set_volume on all devices. Returns the result for each device.
+    pub fn set_volume_all(&self, percent: u8) -> DeviceResults {
+        self.run_sequentially(|device| device.set_volume(percent))
+    }
+
+    /// Calls change_volume on all devices. Returns the result for each
+    /// device.
+    pub fn change_volume_all(&self, change: i8) -> DeviceResults {
+        self.run_sequentially(|device| device.change_volume(change))
+    }
+
+    /// Calls rename on all devices. Returns the result for each device.
+    pub fn rename_all(&self, alias: &str) -> DeviceResults {
+        self.run_sequentially(|device| device.rename(alias))
+    }
+
+    /// Sets the glyphs shown before device names, None shows no icons
+    pub fn set_icons(&mut self, icons: Option<IconStyle>) {
+        self.icons = icons;
+    }
+
+    /// Sets the percentages at which the battery color of devices added
+    /// afterwards changes.
+    pub fn set_battery_thresholds(&mut self, battery_thresholds: BatteryThresholds) {
+        self.battery_thresholds = battery_thresholds;
+    }
+
+    /// Sets the nicknames given to devices, as pairs of the nickname and the
+    /// name or address of the device. Applies to devices added afterwards.
+    pub fn set_nicknames(&mut self, nicknames: Vec<(String, String)>) {
+        self.nicknames = nicknames;
+    }
 
     /// Sets whether quotes will be added if there is a
     /// device name containing whitespace
@@ -535,6 +1889,23 @@ impl<M: BluetoothManager> DeviceList<M> {
         self.quote_names = val;
     }
 
+    /// Sets whether the persistent device index is printed before names
+    pub fn set_show_index(&mut self, val: bool) {
+        self.show_index = val;
+        // Indices may have been assigned after the devices were added
+        self.max_index = self
+            .devices
+            .iter()
+            .filter_map(|device| device.lock().expect("Mutex should not be poisoned.").index)
+            .max()
+            .unwrap_or(0);
+    }
+
+    /// Sets whether tabular output formats start with a row of column names
+    pub fn set_print_header(&mut self, val: bool) {
+        self.print_header = val;
+    }
+
     /// Sets whether output will be colored with ANSI color codes
     pub fn set_print_in_color(&mut self, val: bool) {
         self.print_in_color = val;
@@ -552,3 +1923,124 @@ impl<M: BluetoothManager> IntoIterator for DeviceList<M> {
         self.devices.into_iter()
     }
 }
+
+/// Serializes as a sequence of the devices, like DeviceList::to_json
+impl<M: BluetoothManager> Serialize for DeviceList<M> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.devices.len()))?;
+        for device in &self.devices {
+            seq.serialize_element(&*device.lock().expect("Mutex should not be poisoned."))?;
+        }
+        seq.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bluetooth::mock::MockBluetoothManager;
+
+    fn device_list(names: &[&str]) -> DeviceList<MockBluetoothManager> {
+        let fixture = std::env::temp_dir().join("bt-devices-test.toml");
+        std::fs::write(&fixture, "").unwrap();
+        let manager = MockBluetoothManager::load(&fixture).unwrap();
+        let mut list = DeviceList::new(Arc::new(Mutex::new(manager)));
+        list.set_nicknames(vec![("desk".to_string(), "Keyboard".to_string())]);
+        for (i, name) in names.iter().enumerate() {
+            let address = format!("00:00:00:00:00:{i:02X}");
+            let device = Device::new(address, name.to_string(), true, true, false, false, false);
+            list.add_device(Arc::new(Mutex::new(device)));
+        }
+        list
+    }
+
+    fn names(list: &DeviceList<MockBluetoothManager>) -> Vec<String> {
+        list.iter()
+            .map(|device| {
+                device
+                    .lock()
+                    .expect("Mutex should not be poisoned.")
+                    .name
+                    .clone()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn fuzzy_score_allows_no_typos_in_short_filters() {
+        for (name, filterstr, expected) in [
+            ("Headphones", "hed", Some((true, 9))),
+            ("Xbox", "xbx", Some((true, 9))),
+            ("Pad", "pod", None),
+            ("Mouse", "mpu", None),
+            ("Headphones", "hex", None),
+        ] {
+            assert_eq!(fuzzy_score(name, filterstr), expected, "{filterstr}");
+        }
+    }
+
+    #[test]
+    fn fuzzy_score_allows_one_typo_per_four_characters() {
+        for (name, filterstr, expected) in [
+            ("Pods", "pids", Some((false, 0))),
+            ("Speaker", "speeker", Some((false, 0))),
+            ("Speaker", "sleeker", None),
+            ("Keyboard", "keybaord", Some((false, 0))),
+            ("Keyboard", "keyboarf", Some((false, 1))),
+            ("Keyboard", "kaybaord", None),
+            // Words are compared on their own and as the whole name
+            ("Living Room", "roon", Some((false, 0))),
+            ("Living Room", "livingroon", Some((false, 0))),
+        ] {
+            assert_eq!(fuzzy_score(name, filterstr), expected, "{filterstr}");
+        }
+    }
+
+    #[test]
+    fn fuzzy_score_ranks_subsequences_above_typos() {
+        let subsequence = fuzzy_score("Pods", "ps").unwrap();
+        let typo = fuzzy_score("Keyboard", "keyboarf").unwrap();
+        assert!(subsequence > typo);
+    }
+
+    #[test]
+    fn filtered_name_fuzzy_keeps_best_matches_only() {
+        let list = device_list(&["Headphones", "HP Laptop", "Pods", "Pads"]);
+        for (fuzzy, expected) in [
+            ("hp", vec!["HP Laptop"]),
+            ("phones", vec!["Headphones"]),
+            // Pads only matches with a typo
+            ("pods", vec!["Pods"]),
+            ("pxds", vec!["Pods", "Pads"]),
+            ("xyz", vec![]),
+        ] {
+            assert_eq!(names(&list.filtered_name_fuzzy(fuzzy)), expected, "{fuzzy}");
+        }
+    }
+
+    #[test]
+    fn filtered_name_fuzzy_keeps_ties() {
+        let list = device_list(&["Speaker Left", "Speaker Right", "Soundbar"]);
+        assert_eq!(
+            names(&list.filtered_name_fuzzy("speaker")),
+            ["Speaker Left", "Speaker Right"]
+        );
+        assert_eq!(
+            names(&list.filtered_name_fuzzy("speeker")),
+            ["Speaker Left", "Speaker Right"]
+        );
+    }
+
+    #[test]
+    fn filtered_name_fuzzy_matches_nicknames() {
+        let list = device_list(&["Keyboard", "Desk Lamp", "Mouse"]);
+        assert_eq!(
+            names(&list.filtered_name_fuzzy("desk")),
+            ["Keyboard", "Desk Lamp"]
+        );
+        assert_eq!(
+            names(&list.filtered_name_fuzzy("dsk")),
+            ["Keyboard", "Desk Lamp"]
+        );
+    }
+}
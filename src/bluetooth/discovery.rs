@@ -0,0 +1,45 @@
+// vim: cc=81
+
+/// Transports discovery can be restricted to
+#[derive(Clone, Copy, PartialEq)]
+pub enum Transport {
+    /// Both transports, interleaved
+    Auto,
+    /// Classic bluetooth (BR/EDR) only
+    BrEdr,
+    /// Bluetooth Low Energy only
+    Le,
+}
+
+impl Transport {
+    /// Parses the transport names used on the command line (auto, bredr, le)
+    pub fn from_name(name: &str) -> Option<Transport> {
+        match name {
+            "auto" => Some(Transport::Auto),
+            "bredr" => Some(Transport::BrEdr),
+            "le" => Some(Transport::Le),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Transport::Auto => "auto",
+            Transport::BrEdr => "bredr",
+            Transport::Le => "le",
+        }
+    }
+}
+
+/// Restrictions on which devices are reported while scanning. Unset fields
+/// do not restrict discovery.
+#[derive(Clone, Default)]
+pub struct DiscoveryFilter {
+    pub transport: Option<Transport>,
+    /// Minimum signal strength in dBm, excludes pathloss
+    pub rssi: Option<i16>,
+    /// Maximum path loss in dB, excludes rssi
+    pub pathloss: Option<u16>,
+    /// Service UUIDs of which devices must advertise at least one
+    pub uuids: Vec<String>,
+}
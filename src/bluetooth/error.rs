@@ -0,0 +1,142 @@
+// vim: cc=81
+use std::fmt;
+
+/// Explanations for bluez errors, matched against the error message or name
+const ERROR_EXPLANATIONS: [(&str, &str); 12] = [
+    (
+        "br-connection-profile-unavailable",
+        "no usable profile; is the PipeWire/PulseAudio Bluetooth module loaded?",
+    ),
+    (
+        "le-connection-abort-by-local",
+        "the connection was aborted; is the device in range and turned on?",
+    ),
+    (
+        "br-connection-page-timeout",
+        "the device did not respond; is it in range and turned on?",
+    ),
+    (
+        "br-connection-canceled",
+        "the connection attempt was canceled",
+    ),
+    (
+        "br-connection-refused",
+        "the device refused the connection; it may be connected to another host",
+    ),
+    (
+        "br-connection-key-missing",
+        "the link key is missing; try unpairing and pairing the device again",
+    ),
+    ("br-connection-busy", "the adapter is busy; try again later"),
+    (
+        "Host is down",
+        "the device did not respond; is it in range and turned on?",
+    ),
+    (
+        "org.bluez.Error.AuthenticationFailed",
+        "authentication failed; is the device in pairing mode?",
+    ),
+    (
+        "org.bluez.Error.AuthenticationRejected",
+        "the device rejected the pairing",
+    ),
+    (
+        "org.bluez.Error.ConnectionAttemptFailed",
+        "the device could not be reached; is it in range and turned on?",
+    ),
+    (
+        "org.bluez.Error.NotReady",
+        "the adapter is not ready; is it powered on?",
+    ),
+];
+
+/// Errors of operations on devices
+#[derive(Debug)]
+pub enum BtError {
+    /// The device was already connected
+    AlreadyConnected,
+    /// The device was already paired
+    AlreadyPaired,
+    /// The operation requires a connected device
+    NotConnected,
+    /// The operation did not finish before the timeout elapsed
+    Timeout,
+    /// The device rejected the pairing or authentication failed
+    AuthFailed(dbus::Error),
+    /// The operation was canceled, e.g. by pressing Ctrl+C
+    Canceled,
+    /// The device requested a secret (like a pin code) that cannot be asked
+    /// for with --no-input, arg being the argument giving it instead
+    NoInput {
+        secret: &'static str,
+        arg: &'static str,
+    },
+    /// The device is not known to the bluetooth manager
+    NotFound,
+    /// The bluetooth manager is no longer available
+    ManagerUnavailable,
+    /// Any other error reported over D-Bus
+    DBus(dbus::Error),
+}
+
+impl BtError {
+    /// Returns whether the error only means that the device already was in
+    /// the requested state.
+    pub fn is_already_done(&self) -> bool {
+        matches!(self, BtError::AlreadyConnected | BtError::AlreadyPaired)
+    }
+}
+
+impl From<dbus::Error> for BtError {
+    fn from(error: dbus::Error) -> Self {
+        match error.name() {
+            Some("org.bluez.Error.AlreadyConnected") => BtError::AlreadyConnected,
+            Some("org.bluez.Error.AlreadyExists") => BtError::AlreadyPaired,
+            Some("org.bluez.Error.Canceled" | "org.bluez.Error.AuthenticationCanceled") => {
+                BtError::Canceled
+            }
+            Some(
+                "org.bluez.Error.AuthenticationFailed"
+                | "org.bluez.Error.AuthenticationRejected"
+                | "org.bluez.Error.AuthenticationTimeout",
+            ) => BtError::AuthFailed(error),
+            Some("org.bluez.Error.NotConnected") => BtError::NotConnected,
+            Some("org.bluez.Error.DoesNotExist") => BtError::NotFound,
+            Some("org.freedesktop.DBus.Error.NoReply") => BtError::Timeout,
+            _ => BtError::DBus(error),
+        }
+    }
+}
+
+impl fmt::Display for BtError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BtError::AlreadyConnected => write!(f, "already connected"),
+            BtError::AlreadyPaired => write!(f, "already paired"),
+            BtError::NotConnected => write!(f, "not connected"),
+            BtError::Timeout => write!(f, "timed out"),
+            BtError::Canceled => write!(f, "canceled"),
+            BtError::NoInput { secret, arg } => write!(
+                f,
+                "requested a {secret}, which cannot be entered with \
+                --no-input; pass it with {arg} instead"
+            ),
+            BtError::NotFound => write!(f, "device not found"),
+            BtError::ManagerUnavailable => write!(f, "bluetooth manager unavailable"),
+            BtError::AuthFailed(error) | BtError::DBus(error) => {
+                let name = error.name().unwrap_or("unknown error");
+                let message = error.message().unwrap_or_default();
+                write!(f, "{message} ({name})")?;
+                if let Some((_, explanation)) = ERROR_EXPLANATIONS
+                    .iter()
+                    .find(|(cause, _)| message.contains(cause) || name == *cause)
+                {
+                    write!(f, ": {explanation}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for BtError {}
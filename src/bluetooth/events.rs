@@ -0,0 +1,68 @@
+// vim: cc=81
+use serde_json::{json, Value};
+
+/// Kinds of device state changes reported by a BluetoothManager
+#[derive(Clone, PartialEq)]
+pub enum DeviceEventKind {
+    Discovered,
+    Connected,
+    Disconnected,
+    Paired,
+    Unpaired,
+    /// Battery percentage changed to the given value
+    Battery(u8),
+    /// Battery percentage dropped below a warning threshold
+    LowBattery(u8),
+}
+
+/// A change of the state of a device
+#[derive(Clone)]
+pub struct DeviceEvent {
+    pub address: String,
+    pub name: String,
+    /// Freedesktop icon name of the device like "audio-headset"
+    pub icon: Option<String>,
+    pub kind: DeviceEventKind,
+}
+
+impl DeviceEvent {
+    /// Short name of the event kind like "connected" or "battery"
+    pub fn kind_name(&self) -> &str {
+        match self.kind {
+            DeviceEventKind::Discovered => "discovered",
+            DeviceEventKind::Connected => "connected",
+            DeviceEventKind::Disconnected => "disconnected",
+            DeviceEventKind::Paired => "paired",
+            DeviceEventKind::Unpaired => "unpaired",
+            DeviceEventKind::Battery(_) => "battery",
+            DeviceEventKind::LowBattery(_) => "low-battery",
+        }
+    }
+
+    /// Returns a human readable description like "Headset (00:11:22:33:44:55)
+    /// connected."
+    pub fn description(&self) -> String {
+        let change = match self.kind {
+            DeviceEventKind::Battery(percentage) => format!("battery at {percentage}%"),
+            DeviceEventKind::LowBattery(percentage) => format!("battery low at {percentage}%"),
+            _ => self.kind_name().to_string(),
+        };
+        format!("{} ({}) {change}.", self.name, self.address)
+    }
+
+    /// Returns the event as a JSON object
+    pub fn to_json(&self) -> Value {
+        let battery = match self.kind {
+            DeviceEventKind::Battery(percentage) | DeviceEventKind::LowBattery(percentage) => {
+                Some(percentage)
+            }
+            _ => None,
+        };
+        json!({
+            "event": self.kind_name(),
+            "address": self.address,
+            "name": self.name,
+            "battery": battery,
+        })
+    }
+}
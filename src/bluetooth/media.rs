@@ -0,0 +1,141 @@
+// vim: cc=81
+use serde::{Deserialize, Serialize};
+
+/// Playback commands for the media player of a device, e.g. a phone
+#[derive(Clone, Copy, PartialEq)]
+pub enum MediaCommand {
+    Play,
+    Pause,
+    Next,
+    Previous,
+    Stop,
+}
+
+impl MediaCommand {
+    /// Parses the command names used on the command line (play, pause,
+    /// next, previous, stop)
+    pub fn from_name(name: &str) -> Option<MediaCommand> {
+        match name {
+            "play" => Some(MediaCommand::Play),
+            "pause" => Some(MediaCommand::Pause),
+            "next" => Some(MediaCommand::Next),
+            "previous" => Some(MediaCommand::Previous),
+            "stop" => Some(MediaCommand::Stop),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MediaCommand::Play => "play",
+            MediaCommand::Pause => "pause",
+            MediaCommand::Next => "next",
+            MediaCommand::Previous => "previous",
+            MediaCommand::Stop => "stop",
+        }
+    }
+}
+
+/// What the media player of a device is playing. Times are in milliseconds,
+/// as reported by bluez.
+#[derive(Clone, Default, PartialEq, Serialize)]
+pub struct MediaStatus {
+    /// playing, stopped, paused, forward-seek, reverse-seek or error
+    pub status: String,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub position: u32,
+    pub duration: Option<u32>,
+}
+
+impl MediaStatus {
+    /// Returns a single line like "Artist - Title (1:23/4:56)", with the
+    /// status appended unless the track is playing.
+    pub fn summary(&self) -> String {
+        let track = match (&self.artist, &self.title) {
+            (Some(artist), Some(title)) => format!("{artist} - {title} "),
+            (None, Some(title)) => format!("{title} "),
+            (Some(artist), None) => format!("{artist} "),
+            (None, None) => String::new(),
+        };
+        let time = match self.duration {
+            Some(duration) => {
+                format!("{}/{}", format_time(self.position), format_time(duration))
+            }
+            None => format_time(self.position),
+        };
+        match self.status.as_str() {
+            "playing" => format!("{track}({time})"),
+            status => format!("{track}({time}, {status})"),
+        }
+    }
+}
+
+/// Formats milliseconds as m:ss, or h:mm:ss from an hour on.
+fn format_time(millis: u32) -> String {
+    let secs = millis / 1000;
+    match (secs / 3600, secs / 60 % 60, secs % 60) {
+        (0, mins, secs) => format!("{mins}:{secs:02}"),
+        (hours, mins, secs) => format!("{hours}:{mins:02}:{secs:02}"),
+    }
+}
+
+/// An audio stream of a device, which bluez offers while an audio profile is
+/// connected.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MediaTransport {
+    /// Name of the codec in use, like SBC, AAC or aptX
+    pub codec: String,
+    /// idle, pending, broadcasting or active (streaming)
+    pub state: String,
+    /// Volume in percent, None without AVRCP absolute volume
+    pub volume: Option<u8>,
+}
+
+/// Returns the name of an A2DP (or LE Audio) codec. Vendor codecs (0xff) are
+/// told apart by the vendor and codec id at the start of configuration.
+pub fn codec_name(codec: u8, configuration: &[u8]) -> String {
+    let name = match codec {
+        0x00 => "SBC",
+        0x01 => "MP3",
+        0x02 => "AAC",
+        0x03 => "USAC",
+        0x04 => "ATRAC",
+        0x06 => "LC3",
+        0xff => {
+            let (Some(vendor), Some(id)) = (configuration.get(..4), configuration.get(4..6)) else {
+                return "vendor specific".to_string();
+            };
+            let vendor = u32::from_le_bytes([vendor[0], vendor[1], vendor[2], vendor[3]]);
+            let id = u16::from_le_bytes([id[0], id[1]]);
+            match (vendor, id) {
+                (0x004f, 0x0001) => "aptX",
+                (0x00d7, 0x0024) => "aptX HD",
+                (0x000a, 0x0002) => "aptX Low Latency",
+                (0x000a, 0x0001) => "FastStream",
+                (0x012d, 0x00aa) => "LDAC",
+                (0x05f1, 0x1005) => "Opus",
+                _ => return format!("vendor {vendor:#06x} codec {id:#06x}"),
+            }
+        }
+        codec => return format!("{codec:#04x}"),
+    };
+    name.to_string()
+}
+
+/// Highest AVRCP absolute volume, which ranges from 0 to 127
+pub const MAX_VOLUME: u16 = 127;
+
+/// Converts an AVRCP absolute volume to a rounded percentage.
+pub fn volume_to_percent(volume: u16) -> u8 {
+    let max = u32::from(MAX_VOLUME);
+    let percent = (u32::from(volume).min(max) * 100 + max / 2) / max;
+    u8::try_from(percent).unwrap_or(100)
+}
+
+/// Converts a percentage (capped at 100) to the nearest AVRCP absolute
+/// volume.
+pub fn percent_to_volume(percent: u8) -> u16 {
+    (u16::from(percent.min(100)) * MAX_VOLUME + 50) / 100
+}
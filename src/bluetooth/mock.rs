@@ -0,0 +1,595 @@
+// vim: cc=81
+use super::{
+    media, Adapter, BluetoothManager, BtError, Device, DeviceEvent, DeviceEventKind, Devices,
+    DiscoveryFilter, MediaCommand, MediaStatus, MediaTransport,
+};
+use std::{
+    collections::HashMap,
+    fs,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use toml::{Table, Value};
+
+/// A device as scripted by the fixture file
+struct MockDevice {
+    address: String,
+    name: String,
+    remote_name: Option<String>,
+    icon: Option<String>,
+    /// Name of the adapter the device is known to
+    adapter: String,
+    paired: bool,
+    bonded: bool,
+    trusted: bool,
+    blocked: bool,
+    connected: bool,
+    battery: Option<u8>,
+    rssi: Option<i16>,
+    /// Whether the device is only known after scanning
+    scan_only: bool,
+    /// AVRCP absolute volume, None if the device has no volume control
+    volume: Option<u16>,
+    /// What the media player plays, None if the device has no media player
+    media: Option<MediaStatus>,
+    /// Audio streams while the device is connected
+    transports: Vec<MediaTransport>,
+    /// Errors operations like "connect" fail with instead of succeeding
+    errors: HashMap<String, String>,
+}
+
+impl MockDevice {
+    /// Returns the error the operation is scripted to fail with, if any.
+    fn check(&self, operation: &str) -> Result<(), BtError> {
+        match self.errors.get(operation) {
+            Some(error) => Err(error_from(error)),
+            None => Ok(()),
+        }
+    }
+
+    fn to_device(&self) -> Device<MockBluetoothManager> {
+        let mut device = Device::new(
+            self.address.clone(),
+            self.name.clone(),
+            self.paired,
+            self.bonded,
+            self.trusted,
+            self.blocked,
+            self.connected,
+        );
+        device.remote_name = self.remote_name.clone();
+        device.icon = self.icon.clone();
+        device.battery = self.battery;
+        device.rssi = self.rssi;
+        device.adapters = vec![self.adapter.clone()];
+        device
+    }
+}
+
+/// Adapters, devices and events of the fixture, changed by operations like
+/// connecting. Shared by forks, so concurrent operations see each other.
+struct MockState {
+    adapters: Vec<Adapter>,
+    devices: Vec<MockDevice>,
+    events: Vec<DeviceEvent>,
+}
+
+/// Converts errors written like "org.bluez.Error.Failed
+/// br-connection-page-timeout" to the BtError bluez would cause.
+fn error_from(error: &str) -> BtError {
+    let (name, message) = error.split_once(' ').unwrap_or((error, ""));
+    dbus::Error::new_custom(name, message).into()
+}
+
+/// Returns the string of key in table, if it is set.
+fn string(table: &Table, key: &str) -> Option<String> {
+    table.get(key)?.as_str().map(String::from)
+}
+
+/// Returns the boolean of key in table, false if it is not set.
+fn flag(table: &Table, key: &str) -> bool {
+    table.get(key).and_then(Value::as_bool).unwrap_or(false)
+}
+
+/// Returns the integer of key in table, if it is set and fits into T.
+fn integer<T: TryFrom<i64>>(table: &Table, key: &str) -> Option<T> {
+    table.get(key)?.as_integer()?.try_into().ok()
+}
+
+/// Returns the tables of the array key in table, e.g. of [[devices]].
+fn tables<'a>(table: &'a Table, key: &str) -> Vec<&'a Table> {
+    table
+        .get(key)
+        .and_then(Value::as_array)
+        .map_or(Vec::new(), |array| {
+            array.iter().filter_map(Value::as_table).collect()
+        })
+}
+
+/// BluetoothManager whose adapters and devices are scripted by a fixture
+/// file instead of coming from bluetoothd, so commands can be tested and
+/// demonstrated without bluetooth hardware. Operations succeed immediately
+/// unless the fixture makes them fail, and their effects last until the
+/// process exits.
+pub struct MockBluetoothManager {
+    state: Arc<Mutex<MockState>>,
+    devices: Devices<Self>,
+    adapters: Vec<Adapter>,
+    /// Name or address of the only adapter to use, all are used if None
+    selected_adapter: Option<String>,
+    discovery_filter: DiscoveryFilter,
+}
+
+impl MockBluetoothManager {
+    /// Loads the fixture at path, a TOML file with [[adapters]], [[devices]]
+    /// and [[events]] tables. Returns a description of the problem if it
+    /// cannot be read or is invalid.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|error| format!("Could not read {}: {error}", path.display()))?;
+        let table: Table = content
+            .parse()
+            .map_err(|error| format!("Invalid fixture {}: {error}", path.display()))?;
+
+        let mut adapters: Vec<Adapter> = tables(&table, "adapters")
+            .iter()
+            .enumerate()
+            .map(|(i, adapter)| Adapter {
+                name: string(adapter, "name").unwrap_or_else(|| format!("hci{i}")),
+                address: string(adapter, "address")
+                    .unwrap_or_else(|| format!("00:00:00:00:00:{i:02X}")),
+                alias: string(adapter, "alias").unwrap_or_default(),
+                // Adapters are usually powered
+                powered: adapter
+                    .get("powered")
+                    .and_then(Value::as_bool)
+                    .unwrap_or(true),
+                discoverable: flag(adapter, "discoverable"),
+                pairable: flag(adapter, "pairable"),
+                discovering: false,
+            })
+            .collect();
+        if adapters.is_empty() {
+            adapters.push(Adapter {
+                name: "hci0".to_string(),
+                address: "00:00:00:00:00:00".to_string(),
+                alias: "mock".to_string(),
+                powered: true,
+                discoverable: false,
+                pairable: true,
+                discovering: false,
+            });
+        }
+
+        let mut devices = Vec::new();
+        for device in tables(&table, "devices") {
+            let address = string(device, "address")
+                .ok_or_else(|| format!("A device in {} has no address", path.display()))?;
+            let errors =
+                device
+                    .get("errors")
+                    .and_then(Value::as_table)
+                    .map_or(HashMap::new(), |errors| {
+                        errors
+                            .iter()
+                            .filter_map(|(operation, error)| {
+                                Some((operation.clone(), error.as_str()?.to_string()))
+                            })
+                            .collect()
+                    });
+            let paired = flag(device, "paired");
+            devices.push(MockDevice {
+                name: string(device, "name").unwrap_or_else(|| address.clone()),
+                remote_name: string(device, "name"),
+                icon: string(device, "icon"),
+                adapter: string(device, "adapter").unwrap_or_else(|| adapters[0].name.clone()),
+                paired,
+                bonded: device
+                    .get("bonded")
+                    .and_then(Value::as_bool)
+                    .unwrap_or(paired),
+                trusted: flag(device, "trusted"),
+                blocked: flag(device, "blocked"),
+                connected: flag(device, "connected"),
+                battery: integer(device, "battery"),
+                rssi: integer(device, "rssi"),
+                scan_only: flag(device, "scan_only"),
+                volume: integer(device, "volume"),
+                media: device
+                    .get("media")
+                    .and_then(Value::as_table)
+                    .map(|media| MediaStatus {
+                        status: string(media, "status").unwrap_or_else(|| "playing".into()),
+                        title: string(media, "title"),
+                        artist: string(media, "artist"),
+                        album: string(media, "album"),
+                        position: integer(media, "position").unwrap_or(0),
+                        duration: integer(media, "duration"),
+                    }),
+                transports: tables(device, "transports")
+                    .iter()
+                    .map(|transport| MediaTransport {
+                        codec: string(transport, "codec").unwrap_or_else(|| "SBC".into()),
+                        state: string(transport, "state").unwrap_or_else(|| "idle".into()),
+                        volume: integer(transport, "volume").map(media::volume_to_percent),
+                    })
+                    .collect(),
+                errors,
+                address,
+            });
+        }
+
+        let mut events = Vec::new();
+        for event in tables(&table, "events") {
+            let address = string(event, "address").unwrap_or_default();
+            let kind = match event.get("kind").and_then(Value::as_str) {
+                Some("discovered") => DeviceEventKind::Discovered,
+                Some("connected") => DeviceEventKind::Connected,
+                Some("disconnected") => DeviceEventKind::Disconnected,
+                Some("paired") => DeviceEventKind::Paired,
+                Some("unpaired") => DeviceEventKind::Unpaired,
+                Some("battery") => DeviceEventKind::Battery(integer(event, "battery").unwrap_or(0)),
+                Some("low-battery") => {
+                    DeviceEventKind::LowBattery(integer(event, "battery").unwrap_or(0))
+                }
+                kind => {
+                    return Err(format!(
+                        "Unknown event kind {} in {}",
+                        kind.unwrap_or("(none)"),
+                        path.display()
+                    ))
+                }
+            };
+            let known = devices.iter().find(|device| device.address == address);
+            events.push(DeviceEvent {
+                name: known.map_or(address.clone(), |device| device.name.clone()),
+                icon: known.and_then(|device| device.icon.clone()),
+                address,
+                kind,
+            });
+        }
+
+        Ok(Self {
+            state: Arc::new(Mutex::new(MockState {
+                adapters,
+                devices,
+                events,
+            })),
+            devices: Vec::new(),
+            adapters: Vec::new(),
+            selected_adapter: None,
+            discovery_filter: DiscoveryFilter::default(),
+        })
+    }
+
+    /// Runs change on the scripted state of device, failing with
+    /// BtError::NotFound if the fixture does not contain it.
+    fn with_device<T>(
+        &self,
+        device: &Device<Self>,
+        change: impl FnOnce(&mut MockDevice) -> Result<T, BtError>,
+    ) -> Result<T, BtError> {
+        let mut state = self.state.lock().expect("Mutex should not be poisoned.");
+        let mock = state
+            .devices
+            .iter_mut()
+            .find(|mock| mock.address == device.address)
+            .ok_or(BtError::NotFound)?;
+        change(mock)
+    }
+
+    /// Runs change on the scripted state of adapter. The returned value
+    /// indicates whether the adapter exists.
+    fn with_adapter(&self, adapter: &Adapter, change: impl FnOnce(&mut Adapter)) -> bool {
+        let mut state = self.state.lock().expect("Mutex should not be poisoned.");
+        match state
+            .adapters
+            .iter_mut()
+            .find(|mock| mock.address == adapter.address)
+        {
+            Some(mock) => {
+                change(mock);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl BluetoothManager for MockBluetoothManager {
+    fn update(&mut self) -> &mut Self {
+        let state = self.state.lock().expect("Mutex should not be poisoned.");
+        self.adapters = state
+            .adapters
+            .iter()
+            .filter(|adapter| {
+                self.selected_adapter
+                    .as_ref()
+                    .is_none_or(|filter| adapter.matches(filter))
+            })
+            .cloned()
+            .collect();
+        self.devices = state
+            .devices
+            .iter()
+            .filter(|device| !device.scan_only)
+            .filter(|device| {
+                self.adapters
+                    .iter()
+                    .any(|adapter| adapter.name == device.adapter)
+            })
+            .map(|device| Arc::new(Mutex::new(device.to_device())))
+            .collect();
+        drop(state);
+        self
+    }
+
+    fn fork(&self) -> Option<Self> {
+        Some(Self {
+            state: Arc::clone(&self.state),
+            devices: Vec::new(),
+            adapters: self.adapters.clone(),
+            selected_adapter: self.selected_adapter.clone(),
+            discovery_filter: self.discovery_filter.clone(),
+        })
+    }
+
+    fn get_all_devices(&self) -> Devices<Self> {
+        self.devices.iter().map(Arc::clone).collect()
+    }
+
+    fn get_all_adapters(&self) -> Vec<Adapter> {
+        self.adapters.clone()
+    }
+
+    fn set_selected_adapter(&mut self, selected_adapter: Option<String>) {
+        self.selected_adapter = selected_adapter;
+    }
+
+    fn set_adapter_alias(&self, adapter: &Adapter, alias: &str) -> bool {
+        self.with_adapter(adapter, |mock| mock.alias = alias.to_string())
+    }
+
+    fn set_adapter_powered(&self, adapter: &Adapter, powered: bool) -> bool {
+        self.with_adapter(adapter, |mock| mock.powered = powered)
+    }
+
+    fn set_adapter_discoverable(
+        &self,
+        adapter: &Adapter,
+        discoverable: bool,
+        _timeout: Option<u32>,
+    ) -> bool {
+        self.with_adapter(adapter, |mock| mock.discoverable = discoverable)
+    }
+
+    fn set_adapter_pairable(&self, adapter: &Adapter, pairable: bool) -> bool {
+        self.with_adapter(adapter, |mock| mock.pairable = pairable)
+    }
+
+    fn set_discovery_filter(&mut self, filter: DiscoveryFilter) {
+        self.discovery_filter = filter;
+    }
+
+    fn scan(&self, duration: &Duration) -> &Self {
+        self.scan_live(duration, &mut |_| true)
+    }
+
+    fn scan_live(
+        &self,
+        _duration: &Duration,
+        found: &mut dyn FnMut(&Device<Self>) -> bool,
+    ) -> &Self {
+        // Scans finish at once, so tests do not have to wait for them
+        let discovered: Vec<Device<Self>> = {
+            let mut state = self.state.lock().expect("Mutex should not be poisoned.");
+            state
+                .devices
+                .iter_mut()
+                .filter(|device| {
+                    self.adapters
+                        .iter()
+                        .any(|adapter| adapter.name == device.adapter)
+                })
+                .filter(|device| {
+                    self.discovery_filter
+                        .rssi
+                        .is_none_or(|min| device.rssi.is_some_and(|rssi| rssi >= min))
+                })
+                .map(|device| {
+                    device.scan_only = false;
+                    device.to_device()
+                })
+                .collect()
+        };
+        for device in &discovered {
+            if !found(device) {
+                break;
+            }
+        }
+        self
+    }
+
+    fn monitor(&self, handle: &mut dyn FnMut(Option<&DeviceEvent>) -> bool) {
+        // Monitoring ends after the scripted events instead of blocking
+        let events = self
+            .state
+            .lock()
+            .expect("Mutex should not be poisoned.")
+            .events
+            .clone();
+        for event in &events {
+            if !handle(Some(event)) {
+                break;
+            }
+        }
+    }
+
+    fn pair_device(&self, device: &Device<Self>) -> Result<(), BtError> {
+        self.with_device(device, |mock| {
+            if mock.paired {
+                return Err(BtError::AlreadyPaired);
+            }
+            mock.check("pair")?;
+            mock.paired = true;
+            mock.bonded = true;
+            Ok(())
+        })
+    }
+
+    fn unpair_device(&self, device: &Device<Self>) -> Result<(), BtError> {
+        let mut state = self.state.lock().expect("Mutex should not be poisoned.");
+        let position = state
+            .devices
+            .iter()
+            .position(|mock| mock.address == device.address)
+            .ok_or(BtError::NotFound)?;
+        state.devices[position].check("unpair")?;
+        // Like bluez, removing the device forgets it until it is found again
+        state.devices.remove(position);
+        Ok(())
+    }
+
+    fn connect_device(&self, device: &Device<Self>) -> Result<(), BtError> {
+        self.with_device(device, |mock| {
+            if mock.connected {
+                return Err(BtError::AlreadyConnected);
+            }
+            mock.check("connect")?;
+            mock.connected = true;
+            Ok(())
+        })
+    }
+
+    fn connect_device_profile(&self, device: &Device<Self>, _uuid: &str) -> Result<(), BtError> {
+        // Profiles are not scripted, connecting one connects the device
+        self.with_device(device, |mock| {
+            mock.check("connect")?;
+            mock.connected = true;
+            Ok(())
+        })
+    }
+
+    fn disconnect_device(&self, device: &Device<Self>) -> Result<(), BtError> {
+        self.with_device(device, |mock| {
+            mock.check("disconnect")?;
+            mock.connected = false;
+            Ok(())
+        })
+    }
+
+    fn disconnect_device_profile(&self, device: &Device<Self>, _uuid: &str) -> Result<(), BtError> {
+        // The device stays connected, as other profiles are not scripted
+        self.with_device(device, |mock| mock.check("disconnect"))
+    }
+
+    fn set_device_blocked(&self, device: &Device<Self>, blocked: bool) -> Result<(), BtError> {
+        self.with_device(device, |mock| {
+            mock.check(if blocked { "block" } else { "unblock" })?;
+            mock.blocked = blocked;
+            Ok(())
+        })
+    }
+
+    fn set_device_trusted(&self, device: &Device<Self>, trusted: bool) -> Result<(), BtError> {
+        self.with_device(device, |mock| {
+            mock.check(if trusted { "trust" } else { "untrust" })?;
+            mock.trusted = trusted;
+            Ok(())
+        })
+    }
+
+    fn set_device_alias(&self, device: &Device<Self>, alias: &str) -> Result<(), BtError> {
+        self.with_device(device, |mock| {
+            mock.check("rename")?;
+            mock.name = match alias {
+                "" => mock
+                    .remote_name
+                    .clone()
+                    .unwrap_or_else(|| mock.address.clone()),
+                alias => alias.to_string(),
+            };
+            Ok(())
+        })
+    }
+
+    fn control_media(&self, device: &Device<Self>, _command: MediaCommand) -> Result<(), BtError> {
+        self.with_device(device, |mock| {
+            if !mock.connected {
+                return Err(BtError::NotConnected);
+            }
+            mock.check("media")
+        })
+    }
+
+    fn media_status(&self, device: &Device<Self>) -> Result<MediaStatus, BtError> {
+        self.with_device(device, |mock| {
+            if !mock.connected {
+                return Err(BtError::NotConnected);
+            }
+            mock.check("media")?;
+            mock.media.clone().ok_or_else(|| {
+                dbus::Error::new_custom("org.bluez.Error.NotAvailable", "no media player").into()
+            })
+        })
+    }
+
+    fn media_transports(&self, device: &Device<Self>) -> Result<Vec<MediaTransport>, BtError> {
+        self.with_device(device, |mock| {
+            if !mock.connected {
+                return Ok(Vec::new());
+            }
+            Ok(mock.transports.clone())
+        })
+    }
+
+    fn media_volume(&self, device: &Device<Self>) -> Result<u16, BtError> {
+        self.with_device(device, |mock| {
+            if !mock.connected {
+                return Err(BtError::NotConnected);
+            }
+            mock.check("volume")?;
+            mock.volume.ok_or_else(|| {
+                dbus::Error::new_custom("org.bluez.Error.NotAvailable", "no volume control").into()
+            })
+        })
+    }
+
+    fn set_media_volume(&self, device: &Device<Self>, volume: u16) -> Result<(), BtError> {
+        self.media_volume(device)?;
+        self.with_device(device, |mock| {
+            mock.volume = Some(volume);
+            Ok(())
+        })
+    }
+
+    fn wait_for_connected(
+        &self,
+        device: &Device<Self>,
+        connected: bool,
+        _timeout: &Duration,
+    ) -> Result<(), BtError> {
+        // Nothing changes on its own, so waiting would always time out
+        self.with_device(device, |mock| {
+            if mock.connected == connected {
+                Ok(())
+            } else {
+                Err(BtError::Timeout)
+            }
+        })
+    }
+
+    fn wait_for_services_resolved(
+        &self,
+        device: &Device<Self>,
+        _timeout: &Duration,
+    ) -> Result<(), BtError> {
+        self.with_device(device, |mock| {
+            if !mock.connected {
+                return Err(BtError::NotConnected);
+            }
+            mock.check("resolve")
+        })
+    }
+}
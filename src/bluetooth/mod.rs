@@ -1,45 +1,171 @@
 // vim: cc=81
+pub mod adapters;
+pub mod bluetoothctl;
 pub mod bluez;
 pub mod devices;
+pub mod discovery;
+pub mod error;
+pub mod events;
+pub mod media;
+pub mod mock;
+pub mod query;
+pub mod uuids;
 
 use std::time::Duration;
 
-pub use devices::{Device, DeviceList, Devices};
+pub use adapters::Adapter;
+pub use devices::{Device, DeviceList, DeviceResults, Devices};
+pub use discovery::{DiscoveryFilter, Transport};
+pub use error::BtError;
+pub use events::{DeviceEvent, DeviceEventKind};
+pub use media::{MediaCommand, MediaStatus, MediaTransport};
+pub use query::FieldQuery;
 
 pub trait BluetoothManager {
     /// Updates the BluetoothManager lists of devices and adapters
     /// Note that created devices may not have their bluetooth_manager set this
     /// instance. To set their bluetooth_manager, add them to a DeviceList.
     fn update(&mut self) -> &mut Self;
+    /// Returns an independent manager with the same settings (e.g. using its
+    /// own D-Bus connection), so operations on several devices can run on
+    /// separate threads at the same time. Returns None if that is impossible.
+    fn fork(&self) -> Option<Self>
+    where
+        Self: Sized;
     /// Returns all Devices
     fn get_all_devices(&self) -> Devices<Self>
     where
         Self: Sized;
-    /// Sets whether the host machine is pairable.
-    fn set_pairable(&self, pairable: bool);
+    /// Returns all adapters
+    fn get_all_adapters(&self) -> Vec<Adapter>;
+    /// Restricts devices, scanning and adapter commands to the adapter with
+    /// the given name or address. Takes effect on the next update.
+    fn set_selected_adapter(&mut self, selected_adapter: Option<String>);
+    /// Sets the name an adapter is visible as to other devices. An empty alias
+    /// restores the system name. The returned value indicates success.
+    fn set_adapter_alias(&self, adapter: &Adapter, alias: &str) -> bool;
+    /// Powers an adapter on or off. The returned value indicates success.
+    fn set_adapter_powered(&self, adapter: &Adapter, powered: bool) -> bool;
+    /// Makes an adapter visible to other devices, for timeout seconds if given
+    /// (0 meaning forever), or hides it. The returned value indicates success.
+    fn set_adapter_discoverable(
+        &self,
+        adapter: &Adapter,
+        discoverable: bool,
+        timeout: Option<u32>,
+    ) -> bool;
+    /// Sets whether an adapter accepts pairing requests. The returned value
+    /// indicates success.
+    fn set_adapter_pairable(&self, adapter: &Adapter, pairable: bool) -> bool;
+    /// Sets the filter applied to all following scans.
+    fn set_discovery_filter(&mut self, filter: DiscoveryFilter);
     /// Scans for pairable devices for a given duration
     fn scan(&self, duration: &Duration) -> &Self;
+    /// Scans for devices for a given duration like scan, calling found for
+    /// every device discovered or whose name or signal strength changes during
+    /// the scan. Scanning stops early once found returns false.
+    fn scan_live(&self, duration: &Duration, found: &mut dyn FnMut(&Device<Self>) -> bool) -> &Self
+    where
+        Self: Sized;
     fn scan_mut(&mut self, duration: &Duration) -> &mut Self {
         self.scan(duration);
         self
     }
 
-    /// Attempts to pair a device. The returned value indicates whether the
-    /// device is now paired, also returning true it was already paired.
-    fn pair_device(&self, device: &Device<Self>) -> bool
+    /// Blocks and calls handle for every device that is discovered, connects,
+    /// disconnects, pairs, unpairs or changes its battery percentage until
+    /// handle returns false. Without events, handle is called with None about
+    /// every 100ms, so it can stop monitoring for other reasons.
+    fn monitor(&self, handle: &mut dyn FnMut(Option<&DeviceEvent>) -> bool);
+
+    /// Attempts to pair a device. Fails with BtError::AlreadyPaired if the
+    /// device was already paired.
+    fn pair_device(&self, device: &Device<Self>) -> Result<(), BtError>
     where
         Self: Sized;
     /// Unpairs a device.
-    fn unpair_device(&self, device: &Device<Self>)
+    fn unpair_device(&self, device: &Device<Self>) -> Result<(), BtError>
+    where
+        Self: Sized;
+    /// Attempts to connect a device. Fails with BtError::AlreadyConnected if
+    /// the device was already connected.
+    fn connect_device(&self, device: &Device<Self>) -> Result<(), BtError>
     where
         Self: Sized;
-    /// Attempts to connect a device. The returned value indicates whether the
-    /// device is now connected, also returning true it was already connected.
-    fn connect_device(&self, device: &Device<Self>) -> bool
+    /// Connects only the profile with the given service UUID of a device,
+    /// e.g. A2DP without HFP. Fails with BtError::AlreadyConnected if the
+    /// profile was already connected.
+    fn connect_device_profile(&self, device: &Device<Self>, uuid: &str) -> Result<(), BtError>
     where
         Self: Sized;
     /// Disconnects a device.
-    fn disconnect_device(&self, device: &Device<Self>)
+    fn disconnect_device(&self, device: &Device<Self>) -> Result<(), BtError>
+    where
+        Self: Sized;
+    /// Disconnects only the profile with the given service UUID of a device,
+    /// keeping its other profiles connected.
+    fn disconnect_device_profile(&self, device: &Device<Self>, uuid: &str) -> Result<(), BtError>
+    where
+        Self: Sized;
+    /// Blocks or unblocks a device. Also succeeds if the device already is in
+    /// the requested state.
+    fn set_device_blocked(&self, device: &Device<Self>, blocked: bool) -> Result<(), BtError>
+    where
+        Self: Sized;
+    /// Sets whether a device is trusted, allowing it to connect without
+    /// confirmation. Also succeeds if the device already is in the requested
+    /// state.
+    fn set_device_trusted(&self, device: &Device<Self>, trusted: bool) -> Result<(), BtError>
+    where
+        Self: Sized;
+    /// Sets the local name of a device. An empty alias restores the name
+    /// announced by the device.
+    fn set_device_alias(&self, device: &Device<Self>, alias: &str) -> Result<(), BtError>
+    where
+        Self: Sized;
+    /// Sends a playback command to the media player of a connected device,
+    /// e.g. a phone playing music.
+    fn control_media(&self, device: &Device<Self>, command: MediaCommand) -> Result<(), BtError>
+    where
+        Self: Sized;
+    /// Returns the playback status and current track of the media player of
+    /// a connected device.
+    fn media_status(&self, device: &Device<Self>) -> Result<MediaStatus, BtError>
+    where
+        Self: Sized;
+    /// Returns the audio streams of a device, which only exist while it is
+    /// connected with an audio profile.
+    fn media_transports(&self, device: &Device<Self>) -> Result<Vec<MediaTransport>, BtError>
+    where
+        Self: Sized;
+    /// Returns the AVRCP absolute volume (0 to 127) of the audio stream of a
+    /// connected device.
+    fn media_volume(&self, device: &Device<Self>) -> Result<u16, BtError>
+    where
+        Self: Sized;
+    /// Sets the AVRCP absolute volume (0 to 127) of the audio stream of a
+    /// connected device.
+    fn set_media_volume(&self, device: &Device<Self>, volume: u16) -> Result<(), BtError>
+    where
+        Self: Sized;
+    /// Blocks until the device is connected (or disconnected if connected is
+    /// false), failing with BtError::Timeout if the timeout elapses first.
+    /// Also succeeds if the state was already reached.
+    fn wait_for_connected(
+        &self,
+        device: &Device<Self>,
+        connected: bool,
+        timeout: &Duration,
+    ) -> Result<(), BtError>
+    where
+        Self: Sized;
+    /// Blocks until the services of a connected device are resolved, failing
+    /// with BtError::Timeout if the timeout elapses first.
+    fn wait_for_services_resolved(
+        &self,
+        device: &Device<Self>,
+        timeout: &Duration,
+    ) -> Result<(), BtError>
     where
         Self: Sized;
 }
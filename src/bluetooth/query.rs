@@ -0,0 +1,234 @@
+// vim: cc=81
+use super::{devices::DEVICE_FIELDS, BluetoothManager, Device};
+
+/// Comparisons of a device field with a value, ordered so operators that are
+/// prefixes of others (like < of <=) come after them and are found last
+const OPERATORS: [(&str, Comparison); 6] = [
+    ("!=", Comparison::NotEqual),
+    ("<=", Comparison::LessEqual),
+    (">=", Comparison::GreaterEqual),
+    ("=", Comparison::Equal),
+    ("<", Comparison::Less),
+    (">", Comparison::Greater),
+];
+
+/// Fields whose values are true or false, also given as yes/no or on/off
+const BOOL_FIELDS: [&str; 5] = ["paired", "bonded", "trusted", "blocked", "connected"];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+/// Condition on a device field, like connected=yes or battery<30
+pub struct FieldQuery {
+    field: String,
+    comparison: Comparison,
+    value: String,
+}
+
+impl FieldQuery {
+    /// Parses a query of the form field<operator>value, where operator is one
+    /// of =, !=, <, <=, > and >=. Returns a description of the problem if the
+    /// query is invalid.
+    pub fn parse(query: &str) -> Result<FieldQuery, String> {
+        let Some((start, operator, comparison)) = OPERATORS
+            .iter()
+            .filter_map(|&(operator, comparison)| {
+                Some((query.find(operator)?, operator, comparison))
+            })
+            .min_by_key(|&(start, _, _)| start)
+        else {
+            return Err(format!(
+                "{query} is not a query like connected=yes or battery<30"
+            ));
+        };
+        let field = query[..start].trim();
+        if !DEVICE_FIELDS.contains(&field) {
+            return Err(format!(
+                "Unknown field {field}, available fields are {}",
+                DEVICE_FIELDS.join(", ")
+            ));
+        }
+        let value = query[start + operator.len()..].trim();
+        let ordering = !matches!(comparison, Comparison::Equal | Comparison::NotEqual);
+        if ordering && value.parse::<i64>().is_err() {
+            return Err(format!("{field}{operator} needs a number, not {value}"));
+        }
+        Ok(FieldQuery {
+            field: field.to_string(),
+            comparison,
+            value: if BOOL_FIELDS.contains(&field) {
+                bool_value(value).unwrap_or(value).to_string()
+            } else {
+                value.to_string()
+            },
+        })
+    }
+
+    /// Returns whether the field of device fulfills the query. Numeric
+    /// comparisons never match unset fields, like the battery of devices
+    /// that do not report it.
+    pub fn matches<M: BluetoothManager>(&self, device: &Device<M>) -> bool {
+        let actual = device.field(&self.field);
+        let number = |text: &str| text.parse::<i64>().ok();
+        match self.comparison {
+            Comparison::Equal => actual.eq_ignore_ascii_case(&self.value),
+            Comparison::NotEqual => !actual.eq_ignore_ascii_case(&self.value),
+            comparison => match (number(&actual), number(&self.value)) {
+                (Some(actual), Some(value)) => match comparison {
+                    Comparison::Less => actual < value,
+                    Comparison::LessEqual => actual <= value,
+                    Comparison::Greater => actual > value,
+                    _ => actual >= value,
+                },
+                _ => false,
+            },
+        }
+    }
+}
+
+/// Returns the field value of boolean fields for yes/no style values.
+fn bool_value(value: &str) -> Option<&'static str> {
+    match value.to_lowercase().as_str() {
+        "yes" | "y" | "on" | "true" => Some("true"),
+        "no" | "n" | "off" | "false" => Some("false"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bluetooth::mock::MockBluetoothManager;
+
+    fn device(connected: bool, battery: Option<u8>) -> Device<MockBluetoothManager> {
+        let mut device = Device::new(
+            "00:11:22:33:44:55".to_string(),
+            "Headphones 2".to_string(),
+            true,
+            true,
+            false,
+            false,
+            connected,
+        );
+        device.battery = battery;
+        device
+    }
+
+    fn matches(query: &str, device: &Device<MockBluetoothManager>) -> bool {
+        FieldQuery::parse(query).unwrap().matches(device)
+    }
+
+    #[test]
+    fn parses_each_operator() {
+        for (query, operator, comparison) in [
+            ("battery!=30", "!=", Comparison::NotEqual),
+            ("battery<=30", "<=", Comparison::LessEqual),
+            ("battery>=30", ">=", Comparison::GreaterEqual),
+            ("battery=30", "=", Comparison::Equal),
+            ("battery<30", "<", Comparison::Less),
+            ("battery>30", ">", Comparison::Greater),
+        ] {
+            let parsed = FieldQuery::parse(query).unwrap();
+            assert!(parsed.comparison == comparison, "{query} uses {operator}");
+            assert_eq!(parsed.field, "battery");
+            assert_eq!(parsed.value, "30");
+        }
+    }
+
+    #[test]
+    fn trims_field_and_value() {
+        let parsed = FieldQuery::parse(" name = Headphones 2 ").unwrap();
+        assert_eq!(parsed.field, "name");
+        assert_eq!(parsed.value, "Headphones 2");
+    }
+
+    #[test]
+    fn takes_first_operator_in_query() {
+        let parsed = FieldQuery::parse("name=a<b").unwrap();
+        assert!(parsed.comparison == Comparison::Equal);
+        assert_eq!(parsed.value, "a<b");
+    }
+
+    #[test]
+    fn rejects_invalid_queries() {
+        for query in ["connected", "", "battery 30"] {
+            let error = FieldQuery::parse(query).err().unwrap();
+            assert!(error.contains("is not a query"), "{query}: {error}");
+        }
+        for query in ["charge<30", "=yes", "Name=x"] {
+            let error = FieldQuery::parse(query).err().unwrap();
+            assert!(error.starts_with("Unknown field"), "{query}: {error}");
+        }
+        for query in ["battery<low", "name>=a", "rssi>"] {
+            let error = FieldQuery::parse(query).err().unwrap();
+            assert!(error.contains("needs a number"), "{query}: {error}");
+        }
+    }
+
+    #[test]
+    fn normalizes_bool_values() {
+        for value in ["yes", "Y", "on", "TRUE"] {
+            let parsed = FieldQuery::parse(&format!("connected={value}")).unwrap();
+            assert_eq!(parsed.value, "true");
+        }
+        for value in ["no", "n", "OFF", "false"] {
+            let parsed = FieldQuery::parse(&format!("connected={value}")).unwrap();
+            assert_eq!(parsed.value, "false");
+        }
+        assert_eq!(FieldQuery::parse("connected=maybe").unwrap().value, "maybe");
+        assert_eq!(FieldQuery::parse("name=yes").unwrap().value, "yes");
+    }
+
+    #[test]
+    fn matches_bool_fields() {
+        let connected = device(true, None);
+        let disconnected = device(false, None);
+        assert!(matches("connected=yes", &connected));
+        assert!(!matches("connected=yes", &disconnected));
+        assert!(matches("connected!=on", &disconnected));
+        assert!(matches("paired=true", &disconnected));
+        assert!(!matches("trusted=y", &connected));
+    }
+
+    #[test]
+    fn compares_equality_as_text() {
+        let device = device(true, Some(30));
+        assert!(matches("name=headphones 2", &device));
+        assert!(matches("name!=Headphones", &device));
+        assert!(matches("battery=30", &device));
+        assert!(!matches("battery=030", &device));
+        assert!(matches("address=00:11:22:33:44:55", &device));
+    }
+
+    #[test]
+    fn compares_order_as_numbers() {
+        let device = device(true, Some(30));
+        for (query, expected) in [
+            ("battery<30", false),
+            ("battery<=30", true),
+            ("battery>=030", true),
+            ("battery>29", true),
+            ("battery>30", false),
+            ("battery<100", true),
+            ("battery>-1", true),
+        ] {
+            assert_eq!(matches(query, &device), expected, "{query}");
+        }
+    }
+
+    #[test]
+    fn order_never_matches_unset_fields() {
+        let device = device(true, None);
+        assert!(!matches("battery<100", &device));
+        assert!(!matches("battery>=0", &device));
+        assert!(!matches("rssi<0", &device));
+        assert!(matches("battery=", &device));
+    }
+}
@@ -0,0 +1,81 @@
+// vim: cc=81
+
+/// Suffix turning 16 bit assigned numbers into full 128 bit UUIDs
+const BASE_UUID_SUFFIX: &str = "-0000-1000-8000-00805f9b34fb";
+
+/// Well-known service names and their 16 bit assigned numbers
+pub const KNOWN_SERVICES: [(&str, u16); 14] = [
+    ("serial", 0x1101),
+    ("headset", 0x1108),
+    ("audio-source", 0x110a),
+    ("audio-sink", 0x110b),
+    ("remote-control", 0x110e),
+    ("handsfree", 0x111e),
+    ("pan", 0x1116),
+    ("phonebook", 0x1130),
+    ("device-info", 0x180a),
+    ("heart-rate", 0x180d),
+    ("battery", 0x180f),
+    ("hid", 0x1812),
+    ("cycling-speed", 0x1816),
+    ("fitness-machine", 0x1826),
+];
+
+/// The usual abbreviations of the audio and remote control profiles. They are
+/// accepted like KNOWN_SERVICES, but service_name names their UUIDs after the
+/// service (e.g. audio-sink for a2dp), as both are equally right.
+pub const PROFILE_ALIASES: [(&str, u16); 4] = [
+    ("a2dp", 0x110b),
+    ("hfp", 0x111e),
+    ("hsp", 0x1108),
+    ("avrcp", 0x110e),
+];
+
+/// Returns the well-known service name or profile alias matching name
+/// ignoring case, e.g. "a2dp" for "A2DP".
+pub fn known_name(name: &str) -> Option<&'static str> {
+    KNOWN_SERVICES
+        .iter()
+        .chain(&PROFILE_ALIASES)
+        .find(|(known, _)| known.eq_ignore_ascii_case(name))
+        .map(|(known, _)| *known)
+}
+
+/// Parses a service UUID given as a well-known name or profile alias (see
+/// KNOWN_SERVICES and PROFILE_ALIASES), a 16 or 32 bit assigned number in hex
+/// (e.g. "180d") or a full UUID. Returns the full lowercase UUID.
+pub fn parse_uuid(uuid: &str) -> Result<String, String> {
+    let uuid = uuid.to_lowercase();
+    if let Some((_, number)) = KNOWN_SERVICES
+        .iter()
+        .chain(&PROFILE_ALIASES)
+        .find(|(name, _)| *name == uuid)
+    {
+        return Ok(format!("{number:08x}{BASE_UUID_SUFFIX}"));
+    }
+    let is_hex = |part: &str| part.chars().all(|c| c.is_ascii_hexdigit());
+    match uuid.len() {
+        4 | 8 if is_hex(&uuid) => Ok(format!("{uuid:0>8}{BASE_UUID_SUFFIX}")),
+        36 if uuid.split('-').map(str::len).eq([8, 4, 4, 4, 12]) && uuid.split('-').all(is_hex) => {
+            Ok(uuid)
+        }
+        _ => Err(format!(
+            "expected a UUID, a 16 bit assigned number or one of {}",
+            KNOWN_SERVICES
+                .iter()
+                .chain(&PROFILE_ALIASES)
+                .map(|(name, _)| *name)
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
+}
+
+/// Returns the well-known name of the full service UUID, if there is one.
+/// Profile aliases are not returned (see PROFILE_ALIASES).
+pub fn service_name(uuid: &str) -> Option<&'static str> {
+    KNOWN_SERVICES
+        .iter()
+        .find(|(_, number)| uuid.eq_ignore_ascii_case(&format!("{number:08x}{BASE_UUID_SUFFIX}")))
+        .map(|(name, _)| *name)
+}
@@ -0,0 +1,103 @@
+// vim: cc=81
+use crate::bluetooth::{BluetoothManager, DeviceList};
+use crate::utils;
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+const INDEX_FILE: &str = "index";
+
+/// Persistent mapping of device addresses to small integers, so devices can
+/// be referred to by a stable number across invocations. Indices are assigned
+/// on first sighting and kept until the device is forgotten (unpaired).
+pub struct DeviceIndex {
+    indices: BTreeMap<u32, String>,
+    path: Option<PathBuf>,
+    changed: bool,
+}
+
+impl DeviceIndex {
+    /// Loads the index from the cache directory. Missing or unreadable files
+    /// result in an empty index.
+    pub fn load() -> DeviceIndex {
+        let path = utils::cache_dir().map(|dir| dir.join(INDEX_FILE));
+        let mut indices = BTreeMap::new();
+        if let Some(content) = path.as_ref().and_then(|p| fs::read_to_string(p).ok()) {
+            // Each line has the form "[INDEX] [ADDRESS]"
+            for line in content.lines() {
+                let mut iter = line.split_whitespace();
+                if let (Some(index), Some(address)) = (iter.next(), iter.next()) {
+                    if let Ok(index) = index.parse() {
+                        indices.insert(index, address.to_string());
+                    }
+                }
+            }
+        }
+        DeviceIndex {
+            indices,
+            path,
+            changed: false,
+        }
+    }
+
+    /// Returns the index of address, assigning the lowest free index if the
+    /// address has not been seen before.
+    pub fn get_or_assign(&mut self, address: &str) -> u32 {
+        if let Some(index) = self.get(address) {
+            return index;
+        }
+        let mut index = 1;
+        while self.indices.contains_key(&index) {
+            index += 1;
+        }
+        self.indices.insert(index, address.to_string());
+        self.changed = true;
+        index
+    }
+
+    /// Returns the index of address, if one has been assigned.
+    pub fn get(&self, address: &str) -> Option<u32> {
+        self.indices
+            .iter()
+            .find(|(_, a)| a.as_str() == address)
+            .map(|(index, _)| *index)
+    }
+
+    /// Returns the address assigned to index.
+    pub fn address(&self, index: u32) -> Option<&str> {
+        self.indices.get(&index).map(String::as_str)
+    }
+
+    /// Removes the address from the index, freeing its number for reuse.
+    pub fn forget(&mut self, address: &str) {
+        if let Some(index) = self.get(address) {
+            self.indices.remove(&index);
+            self.changed = true;
+        }
+    }
+
+    /// Assigns indices to all devices in the list and stores them in the
+    /// devices.
+    pub fn apply<M: BluetoothManager>(&mut self, devices: &DeviceList<M>) {
+        for device in devices.iter() {
+            let mut device = device.lock().expect("Mutex should not be poisoned.");
+            device.index = Some(self.get_or_assign(&device.address));
+        }
+    }
+
+    /// Writes the index back to disk if it has been modified.
+    pub fn save(&self) {
+        if !self.changed {
+            return;
+        }
+        if let Some(path) = &self.path {
+            if let Some(dir) = path.parent() {
+                let _ = fs::create_dir_all(dir);
+            }
+            let content: String = self
+                .indices
+                .iter()
+                .map(|(index, address)| format!("{index} {address}\n"))
+                .collect();
+            let _ = fs::write(path, content);
+        }
+    }
+}
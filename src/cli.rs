@@ -1,11 +1,25 @@
 // vim: cc=81
+use crate::{
+    bluetooth::{
+        bluez::AGENT_CAPABILITIES,
+        devices::{DEVICE_FIELDS, DEVICE_TYPES},
+        uuids,
+    },
+    utils,
+};
 use clap::{command, value_parser, Arg, ArgAction, ArgGroup, Command};
 
 pub fn build_cli() -> Command {
     let name_arg = Arg::new("filter")
         .index(1)
         .required(true)
-        .help("Device filter.");
+        .num_args(1..)
+        .help("Device filters.")
+        .long_help(
+            "Device filters. Devices matching any of the filters are used. \
+            A number selects the device with that persistent index (see \
+            list --index)",
+        );
     let timeout_arg = Arg::new("timeout")
         .short('t')
         .long("timeout")
@@ -16,23 +30,198 @@ pub fn build_cli() -> Command {
             Default can be controlled with environment variable BT_TIMEOUT",
         );
 
+    let retries_arg = Arg::new("retries")
+        .long("retries")
+        .value_name("N")
+        .value_parser(value_parser!(u32))
+        .help("Retry up to N times on transient errors [default: 0]")
+        .long_help(
+            "Retry up to N times if bluez reports a transient error, like a \
+            page timeout or an aborted LE connection, which often happens \
+            right after a device was powered on [default: 0]",
+        );
+
+    let retry_delay_arg = Arg::new("retry-delay")
+        .long("retry-delay")
+        .value_name("SECONDS")
+        .value_parser(value_parser!(u64))
+        .help("Wait SECONDS before the first retry, doubled for each retry [default: 1]");
+
+    let pin_fd_arg = Arg::new("pin-fd")
+        .long("pin-fd")
+        .value_name("FD")
+        .value_parser(value_parser!(u32))
+        .help("Read a requested pin code from file descriptor FD")
+        .long_help(
+            "Read a requested pin code from the first line of file descriptor \
+            FD instead of prompting. Unlike command line arguments, this does \
+            not expose the pin code in process listings",
+        );
+
+    let passkey_fd_arg = Arg::new("passkey-fd")
+        .long("passkey-fd")
+        .value_name("FD")
+        .value_parser(value_parser!(u32))
+        .help("Read a requested passkey from file descriptor FD")
+        .long_help(
+            "Read a requested passkey from the first line of file descriptor \
+            FD instead of prompting. Unlike command line arguments, this does \
+            not expose the passkey in process listings",
+        );
+
+    let pin_arg = Arg::new("pin")
+        .long("pin")
+        .value_name("PIN")
+        .conflicts_with("pin-fd")
+        .help("Answer a requested pin code with PIN")
+        .long_help(
+            "Answer a requested pin code with PIN instead of prompting, e.g. \
+            for devices with a fixed pin code like 0000. The pin code can \
+            also be given in the BT_PIN environment variable, or with \
+            --pin-fd to keep it out of process listings",
+        );
+
+    let passkey_arg = Arg::new("passkey")
+        .long("passkey")
+        .value_name("PASSKEY")
+        .value_parser(value_parser!(u32).range(..1_000_000))
+        .conflicts_with("passkey-fd")
+        .help("Answer a requested passkey with PASSKEY")
+        .long_help(
+            "Answer a requested passkey with PASSKEY instead of prompting. \
+            The passkey can also be given in the BT_PASSKEY environment \
+            variable, or with --passkey-fd to keep it out of process listings",
+        );
+
+    let agent_capability_arg = Arg::new("agent-capability")
+        .long("agent-capability")
+        .value_name("CAPABILITY")
+        .value_parser(AGENT_CAPABILITIES)
+        .help("IO capability of the pairing agent")
+        .long_help(
+            "IO capability the pairing agent registers with, which decides \
+            the pairing method negotiated with the device. Defaults to \
+            KeyboardDisplay if there is a terminal to prompt on without \
+            --no-input or a pin code or passkey is given and to \
+            NoInputNoOutput otherwise",
+        );
+
+    let no_input_arg = Arg::new("no-input")
+        .long("no-input")
+        .action(ArgAction::SetTrue)
+        .help("Pair without asking for any input")
+        .long_help(
+            "Pair without asking for any input, e.g. in scripts or over SSH. \
+            Passkeys are confirmed automatically and pin code or passkey \
+            requests fail unless given with --pin or --passkey. Unless \
+            --agent-capability is given, the agent registers as \
+            NoInputNoOutput",
+        );
+
+    let pair_timeout_arg = Arg::new("pair-timeout")
+        .long("pair-timeout")
+        .value_name("SECONDS")
+        .value_parser(value_parser!(u64))
+        .help("Cancel pairing after SECONDS [default: 60]")
+        .long_help(
+            "Cancel pairing with a device that has not completed after \
+            SECONDS, e.g. because a pin code was never entered on the device \
+            [default: 60]",
+        );
+
+    let profile_arg = Arg::new("profile")
+        .long("profile")
+        .value_name("PROFILE")
+        .value_parser(uuids::parse_uuid)
+        .help("Only connect PROFILE, e.g. a2dp or hfp")
+        .long_help(
+            "Only connect the profile with this service UUID or name, e.g. \
+            a2dp, hfp, hsp or avrcp, instead of all profiles of the device",
+        );
+
+    let low_battery_arg = Arg::new("low-battery")
+        .long("low-battery")
+        .value_name("PERCENT")
+        .value_parser(value_parser!(u8).range(1..=100))
+        .help("Warn when a battery drops below PERCENT")
+        .long_help(
+            "Print a low-battery event when the battery of a device drops \
+            below PERCENT",
+        );
+
+    let notify_arg = Arg::new("notify")
+        .long("notify")
+        .action(ArgAction::SetTrue)
+        .help("Send desktop notifications for device events")
+        .long_help(
+            "Send desktop notifications when devices connect, disconnect, \
+            finish pairing or their battery runs low, e.g. for window \
+            managers without a bluetooth applet",
+        );
+
+    let transport_arg = Arg::new("transport")
+        .long("transport")
+        .value_parser(["auto", "bredr", "le"])
+        .help("Only discover devices using this transport")
+        .long_help(
+            "Only discover devices using this transport: le (Bluetooth Low \
+            Energy), bredr (classic bluetooth) or auto (both). Scanning only \
+            LE is usually much faster for modern peripherals",
+        );
+
+    let min_rssi_arg = Arg::new("min-rssi")
+        .long("min-rssi")
+        .value_name("DBM")
+        .allow_negative_numbers(true)
+        .value_parser(value_parser!(i16))
+        .conflicts_with("pathloss")
+        .help("Only discover devices with at least this signal strength")
+        .long_help(
+            "Only discover devices with at least this signal strength in dBm, \
+            e.g. -60 for devices in the same room",
+        );
+    let pathloss_arg = Arg::new("pathloss")
+        .long("pathloss")
+        .value_name("DB")
+        .value_parser(value_parser!(u16))
+        .conflicts_with("min-rssi")
+        .help("Only discover devices with at most this path loss in dB");
+
+    let uuid_arg = Arg::new("uuid")
+        .long("uuid")
+        .action(ArgAction::Append)
+        .value_parser(uuids::parse_uuid)
+        .help("Only discover devices advertising this service")
+        .long_help(
+            "Only discover devices advertising this service. Accepts full \
+            UUIDs, 16 bit assigned numbers like 180d or the names serial, \
+            headset, audio-source, audio-sink, remote-control, handsfree, \
+            pan, phonebook, device-info, heart-rate, battery, hid, \
+            cycling-speed and fitness-machine. Can be given multiple times",
+        );
+
     let color_arg = Arg::new("color")
         .short('c')
         .long("color")
-        .action(ArgAction::SetTrue)
+        .value_name("WHEN")
+        .value_parser(["auto", "always", "never"])
+        .num_args(0..=1)
+        .require_equals(true)
+        .default_missing_value("always")
         .conflicts_with("no-color")
-        .help(
-            "Uses ANSI escape sequences to print with text formatting and \
-            color if used",
+        .help("When to print with ANSI colors and text formatting [default: auto]")
+        .long_help(
+            "When to use ANSI escape sequences to print with text formatting \
+            and color. auto uses them if stdout is a terminal and the NO_COLOR \
+            environment variable is not set. Without WHEN, -c is the same as \
+            --color=always [default: auto]",
         );
     let no_color_arg = Arg::new("no-color")
         .short('C')
         .long("no-color")
         .action(ArgAction::SetTrue)
         .conflicts_with("color")
-        .help("Disallow usage of ANSI escape sequences");
-    let color_arg_group = ArgGroup::new("color group").args(["color", "no-color"]);
-
+        .help("Disallow usage of ANSI escape sequences, same as --color=never");
     let partial_arg = Arg::new("partial")
         .short('p')
         .long("partial")
@@ -55,23 +244,184 @@ pub fn build_cli() -> Command {
         .long("no-regex")
         .action(ArgAction::SetTrue)
         .help("If set the filter is applied literally");
-    let regex_arg_group = ArgGroup::new("regex group").args(["regex", "no-regex"]);
+    let fuzzy_arg = Arg::new("fuzzy")
+        .short('z')
+        .long("fuzzy")
+        .action(ArgAction::SetTrue)
+        .help("Use the device best matching the filter, tolerating typos")
+        .long_help(
+            "Use the device whose name best matches the filter. The \
+            characters of the filter only have to appear in order, e.g. \
+            wh1000 matches WH-1000XM4, and words with a typo match too",
+        );
+    let regex_arg_group = ArgGroup::new("regex group").args(["regex", "no-regex", "fuzzy"]);
 
     let address_arg = Arg::new("address")
         .short('a')
         .long("address")
         .action(ArgAction::SetTrue)
-        .help("Filter is matched against the address instead of name.");
+        .help("Filter is matched against the address instead of name.")
+        .long_help(
+            "Filter is matched against the address instead of name, \
+            ignoring case unless it is a regex. Numbers are not used as \
+            persistent indices",
+        );
     let fields_arg = Arg::new("filter-fields")
         .short('f')
         .long("filter-fields")
-        .action(ArgAction::SetTrue);
+        .action(ArgAction::SetTrue)
+        .help("Filters are queries like connected=yes or battery<30")
+        .long_help(
+            "Filters are queries on device fields like connected=yes, \
+            battery<30 or icon=audio-headset, which devices must all \
+            fulfill. Fields can be compared with =, !=, <, <=, > and >=",
+        );
     let filter_arg_group = ArgGroup::new("filter group").args(["address", "filter-fields"]);
 
+    let count_arg = Arg::new("count")
+        .long("count")
+        .action(ArgAction::SetTrue)
+        .help("Only print the number of matching devices")
+        .long_help(
+            "Only print the number of matching devices instead of running \
+            the command. Exits with a non-zero status if no device matches",
+        );
+
+    let all_arg = Arg::new("all")
+        .long("all")
+        .action(ArgAction::SetTrue)
+        .conflicts_with("first")
+        .help("Use all matching devices without asking")
+        .long_help(
+            "Use all matching devices instead of asking which one to use if \
+            several devices match on a terminal",
+        );
+    let first_arg = Arg::new("first")
+        .short('1')
+        .long("first")
+        .action(ArgAction::SetTrue)
+        .help("Only use the best matching device")
+        .long_help(
+            "Only use the best matching device: devices whose name equals \
+            the filter come first, then those with shorter names, then by \
+            address",
+        );
+
+    let pick_arg = Arg::new("pick")
+        .long("pick")
+        .action(ArgAction::SetTrue)
+        .help("Choose the device with a fuzzy finder")
+        .long_help(
+            "Choose the device with a fuzzy finder over the devices matching \
+            the filter, or over all devices if no filter is given",
+        );
+
+    let yes_arg = Arg::new("yes")
+        .short('y')
+        .long("yes")
+        .action(ArgAction::SetTrue)
+        .help("Do not ask for confirmation if several devices match")
+        .long_help(
+            "Do not ask for confirmation if several devices match. Without \
+            it, the command is refused if stdin is not a terminal",
+        );
+
+    let type_arg = Arg::new("type")
+        .long("type")
+        .value_name("TYPE")
+        .value_parser(DEVICE_TYPES.map(|(name, _)| name))
+        .action(ArgAction::Append)
+        .help("Only use devices of TYPE")
+        .long_help(
+            "Only use devices of TYPE, as reported by the device. Can be \
+            given multiple times to use devices of any of the types. \
+            Without a filter, all devices of the type are used",
+        );
+
+    let exclude_arg = Arg::new("exclude")
+        .long("exclude")
+        .value_name("PATTERN")
+        .action(ArgAction::Append)
+        .help("Skip devices matching PATTERN")
+        .long_help(
+            "Skip devices whose name or address matches PATTERN, after the \
+            other filters are applied. Can be given multiple times",
+        );
+
+    let exact_one_arg = Arg::new("exact-one")
+        .long("exact-one")
+        .action(ArgAction::SetTrue)
+        .help("Fail unless exactly one device matches")
+        .long_help(
+            "Fail without running the command unless exactly one device \
+            matches, listing the matching devices if there are several",
+        );
+
+    let quiet_arg = Arg::new("quiet")
+        .short('q')
+        .long("quiet")
+        .action(ArgAction::SetTrue)
+        .help("Only print the addresses of devices, one per line")
+        .long_help(
+            "Only print the addresses of the devices the command succeeded \
+            for (the matching devices for list), one per line and without \
+            colors or quotes",
+        );
+
+    let adapter_arg = Arg::new("adapter")
+        .index(1)
+        .help("Adapter name (e.g. hci0) or address")
+        .long_help(
+            "Adapter name (e.g. hci0) or address. Selects all adapters if \
+            omitted",
+        );
+    let switch_arg = Arg::new("state")
+        .index(1)
+        .required(true)
+        .value_parser(["on", "off"]);
+
+    let json_arg = Arg::new("json")
+        .long("json")
+        .global(true)
+        .action(ArgAction::SetTrue)
+        .help("Print machine-readable JSON instead of formatted text");
+
+    // Id differs from the adapter argument of the adapter subcommands
+    let select_adapter_arg = Arg::new("select-adapter")
+        .long("adapter")
+        .global(true)
+        .value_name("ADAPTER")
+        .help("Only use the adapter with this name (e.g. hci0) or address");
+
+    let debug_dbus_arg = Arg::new("debug-dbus")
+        .long("debug-dbus")
+        .global(true)
+        .action(ArgAction::SetTrue)
+        .help("Log D-Bus method calls, replies and signals to stderr");
+
+    let backend_arg = Arg::new("backend")
+        .long("backend")
+        .global(true)
+        .value_name("BACKEND")
+        .value_parser(["auto", "bluez", "bluetoothctl"])
+        .help("How to talk to bluetoothd [default: auto]")
+        .long_help(
+            "How to talk to bluetoothd. bluez uses the system D-Bus, \
+            bluetoothctl runs bluetoothctl for every operation and auto \
+            falls back to bluetoothctl if the system D-Bus is unreachable. \
+            Overrides the BT_BACKEND environment variable [default: auto]",
+        );
+
     command!()
         .propagate_version(true)
-        .args([color_arg, no_color_arg])
-        .group(color_arg_group)
+        .args([
+            color_arg,
+            no_color_arg,
+            json_arg,
+            select_adapter_arg,
+            debug_dbus_arg,
+            backend_arg,
+        ])
         .subcommands([
             Command::new("list")
                 .visible_alias("ls")
@@ -100,20 +450,206 @@ pub fn build_cli() -> Command {
                             and include them in the output",
                         )
                         .action(ArgAction::SetTrue),
+                    Arg::new("index")
+                        .short('i')
+                        .long("index")
+                        .help("Print the persistent index of each device")
+                        .long_help(
+                            "Print the persistent index of each device. \
+                            The index can be used instead of a filter to \
+                            select the device in other commands",
+                        )
+                        .action(ArgAction::SetTrue),
+                    Arg::new("icons")
+                        .long("icons")
+                        .value_name("STYLE")
+                        .value_parser(["nerd", "emoji"])
+                        .num_args(0..=1)
+                        .require_equals(true)
+                        .default_missing_value("nerd")
+                        .help("Show the device type as a glyph before names")
+                        .long_help(
+                            "Show the device type (headset, mouse, phone, ...) \
+                            as a glyph before names. nerd (the default) needs \
+                            a Nerd Font, emoji works with most fonts",
+                        ),
+                    Arg::new("merge")
+                        .short('m')
+                        .long("merge")
+                        .help(
+                            "Merge entries of the same device reachable \
+                            through multiple adapters",
+                        )
+                        .long_help(
+                            "Merge entries of the same device reachable \
+                            through multiple adapters. The long listing \
+                            format shows the adapters of merged devices",
+                        )
+                        .action(ArgAction::SetTrue),
+                    Arg::new("connected")
+                        .long("connected")
+                        .action(ArgAction::SetTrue)
+                        .help("Only list connected devices"),
+                    Arg::new("paired")
+                        .long("paired")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("unpaired")
+                        .help("Only list paired devices"),
+                    Arg::new("unpaired")
+                        .long("unpaired")
+                        .action(ArgAction::SetTrue)
+                        .help("Only list unpaired devices"),
+                    Arg::new("trusted")
+                        .long("trusted")
+                        .action(ArgAction::SetTrue)
+                        .help("Only list trusted devices"),
+                    Arg::new("blocked")
+                        .long("blocked")
+                        .action(ArgAction::SetTrue)
+                        .help("Only list blocked devices"),
+                    type_arg.clone(),
+                    exclude_arg.clone(),
+                    transport_arg.clone().requires("all"),
+                    min_rssi_arg.clone().requires("all"),
+                    pathloss_arg.clone().requires("all"),
+                    uuid_arg.clone().requires("all"),
+                    count_arg.clone(),
+                    quiet_arg.clone(),
+                    Arg::new("sort")
+                        .short('s')
+                        .long("sort")
+                        .value_parser(["name", "address", "battery", "connected", "paired"])
+                        .help("Sort devices by the given field")
+                        .long_help(
+                            "Sort devices by the given field. Names are \
+                            sorted case insensitively, connected and paired \
+                            devices are listed first and devices without \
+                            battery information last",
+                        ),
+                    Arg::new("reverse")
+                        .short('r')
+                        .long("reverse")
+                        .action(ArgAction::SetTrue)
+                        .requires("sort")
+                        .help("Reverse the sort order"),
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .value_parser(["csv", "tsv"])
+                        .help("Print devices as comma or tab separated values"),
+                    Arg::new("columns")
+                        .long("columns")
+                        .value_delimiter(',')
+                        .value_parser(DEVICE_FIELDS)
+                        .default_value("address,name,paired,connected,battery")
+                        .help("Comma separated device fields printed by --output"),
+                    Arg::new("print0")
+                        .short('0')
+                        .long("print0")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all(["output", "format"])
+                        .help("Print device names separated by NUL characters")
+                        .long_help(
+                            "Print device names (or addresses with --quiet) \
+                            separated by NUL characters without quotes or \
+                            colors, e.g. for use with xargs -0",
+                        ),
+                    Arg::new("watch")
+                        .short('w')
+                        .long("watch")
+                        .value_name("INTERVAL")
+                        .num_args(0..=1)
+                        .default_missing_value("2")
+                        .value_parser(value_parser!(u64))
+                        .conflicts_with_all(["output", "format", "print0", "count", "json"])
+                        .help("Redraw the list every INTERVAL seconds (default 2)")
+                        .long_help(
+                            "Clear the screen and redraw the list every \
+                            INTERVAL seconds (default 2) until interrupted",
+                        ),
+                    Arg::new("tree")
+                        .long("tree")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all(["output", "format", "print0"])
+                        .help("Group devices by the adapter they belong to"),
+                    Arg::new("header")
+                        .short('H')
+                        .long("header")
+                        .action(ArgAction::SetTrue)
+                        .help("Print a row of column names")
+                        .long_help(
+                            "Print a row of column names before the devices. \
+                            Only has an effect on the long listing format \
+                            and --output",
+                        ),
+                    Arg::new("format")
+                        .short('F')
+                        .long("format")
+                        .value_name("TEMPLATE")
+                        .conflicts_with("output")
+                        .help("Print each device formatted with TEMPLATE")
+                        .long_help(
+                            "Print each device on its own line formatted \
+                            with TEMPLATE. Placeholders like {name} are \
+                            replaced by the device field of the same name, \
+                            {{ and }} print literal braces. Available \
+                            fields are the values of --columns",
+                        ),
                     timeout_arg.clone().requires("all"),
                 ]),
             Command::new("connect")
                 .visible_alias("c")
                 .before_help("Connect to a bluetooth device")
                 .args([
-                    name_arg.clone(),
+                    name_arg.clone().required(false).long_help(
+                        "Device filters. Devices matching any of the filters \
+                        are used. A number selects the device with that \
+                        persistent index (see list --index). Without a \
+                        filter, the device most recently connected with bt \
+                        connect is used",
+                    ),
                     partial_arg.clone(),
                     no_partial_arg.clone(),
                     regex_arg.clone(),
                     no_regex_arg.clone(),
+                    fuzzy_arg.clone(),
                     address_arg.clone(),
                     fields_arg.clone(),
+                    count_arg.clone(),
+                    exact_one_arg.clone(),
+                    type_arg.clone(),
+                    exclude_arg.clone(),
+                    quiet_arg.clone(),
                     timeout_arg.clone(),
+                    retries_arg.clone(),
+                    retry_delay_arg.clone(),
+                    Arg::new("wait-services")
+                        .long("wait-services")
+                        .value_name("SECONDS")
+                        .num_args(0..=1)
+                        .default_missing_value("10")
+                        .value_parser(value_parser!(u64))
+                        .help("Wait up to SECONDS for services to be resolved (default 10)")
+                        .long_help(
+                            "After connecting, wait up to SECONDS (default \
+                            10) until the services of the device are resolved \
+                            before reporting success. Audio profiles are often \
+                            not usable before",
+                        ),
+                    Arg::new("pair-if-needed")
+                        .long("pair-if-needed")
+                        .action(ArgAction::SetTrue)
+                        .help("Pair with devices that are not paired yet before connecting")
+                        .long_help(
+                            "Pair with devices that are not paired yet before \
+                            connecting, like bt pair --connect. As always, \
+                            devices that are not known yet are scanned for \
+                            first",
+                        ),
+                    profile_arg.clone().conflicts_with("pair-if-needed"),
+                    all_arg.clone(),
+                    first_arg.clone(),
+                    pick_arg.clone(),
                 ])
                 .groups([
                     partial_arg_group.clone(),
@@ -124,19 +660,227 @@ pub fn build_cli() -> Command {
                 .visible_alias("dc")
                 .before_help("Disconnect from a bluetooth device")
                 .args([
-                    name_arg.clone(),
+                    name_arg
+                        .clone()
+                        .required(false)
+                        .required_unless_present_any(["pick", "type"]),
+                    partial_arg.clone(),
+                    no_partial_arg.clone(),
+                    regex_arg.clone(),
+                    no_regex_arg.clone(),
+                    fuzzy_arg.clone(),
+                    address_arg.clone(),
+                    fields_arg.clone(),
+                    count_arg.clone(),
+                    exact_one_arg.clone(),
+                    type_arg.clone(),
+                    exclude_arg.clone(),
+                    quiet_arg.clone(),
+                    profile_arg
+                        .clone()
+                        .help("Only disconnect PROFILE, e.g. hfp")
+                        .long_help(
+                            "Only disconnect the profile with this service UUID \
+                            or name, e.g. hfp to keep only a2dp connected for \
+                            better audio quality",
+                        ),
+                    first_arg.clone(),
+                    pick_arg.clone(),
+                ])
+                .groups([
+                    partial_arg_group.clone(),
+                    regex_arg_group.clone(),
+                    filter_arg_group.clone(),
+                ]),
+            Command::new("audio-profile")
+                .before_help(
+                    "Switch the audio profile of a headset by connecting the \
+                    profile and disconnecting the other one: a2dp for high \
+                    quality playback, handsfree for calls using the microphone",
+                )
+                .args([
+                    name_arg
+                        .clone()
+                        .num_args(1)
+                        .help("Device filter.")
+                        .long_help(
+                            "Device filter. A number selects the device with \
+                            that persistent index (see list --index)",
+                        ),
+                    partial_arg.clone(),
+                    no_partial_arg.clone(),
+                    regex_arg.clone(),
+                    no_regex_arg.clone(),
+                    fuzzy_arg.clone(),
+                    address_arg.clone(),
+                    fields_arg.clone(),
+                    count_arg.clone(),
+                    exact_one_arg.clone(),
+                    quiet_arg.clone(),
+                    Arg::new("profile")
+                        .index(2)
+                        .required(true)
+                        .value_parser(["a2dp", "handsfree"])
+                        .help("Audio profile to switch to"),
+                ])
+                .groups([
+                    partial_arg_group.clone(),
+                    regex_arg_group.clone(),
+                    filter_arg_group.clone(),
+                ]),
+            Command::new("media")
+                .before_help(
+                    "Control the playback of a device, e.g. a phone, or show \
+                    what it is playing with status",
+                )
+                .args([
+                    name_arg
+                        .clone()
+                        .num_args(1)
+                        .help("Device filter.")
+                        .long_help(
+                            "Device filter. A number selects the device with \
+                            that persistent index (see list --index)",
+                        ),
+                    partial_arg.clone(),
+                    no_partial_arg.clone(),
+                    regex_arg.clone(),
+                    no_regex_arg.clone(),
+                    fuzzy_arg.clone(),
+                    address_arg.clone(),
+                    fields_arg.clone(),
+                    count_arg.clone(),
+                    exact_one_arg.clone(),
+                    quiet_arg.clone(),
+                    Arg::new("command")
+                        .index(2)
+                        .required(true)
+                        .value_parser(["play", "pause", "next", "previous", "stop", "status"])
+                        .help("Playback command to send, or status to show the current track"),
+                    Arg::new("follow")
+                        .long("follow")
+                        .action(ArgAction::SetTrue)
+                        .help("Keep printing the status whenever it changes")
+                        .long_help(
+                            "Keep printing the status of status whenever it \
+                            changes (checked every second) until interrupted, \
+                            e.g. for status bars",
+                        ),
+                ])
+                .groups([
+                    partial_arg_group.clone(),
+                    regex_arg_group.clone(),
+                    filter_arg_group.clone(),
+                ]),
+            Command::new("volume")
+                .before_help(
+                    "Get or change the volume of a connected audio device in \
+                    percent, using AVRCP absolute volume. Only works while the \
+                    device has an audio stream",
+                )
+                .args([
+                    name_arg
+                        .clone()
+                        .num_args(1)
+                        .help("Device filter.")
+                        .long_help(
+                            "Device filter. A number selects the device with \
+                            that persistent index (see list --index)",
+                        ),
                     partial_arg.clone(),
                     no_partial_arg.clone(),
                     regex_arg.clone(),
                     no_regex_arg.clone(),
+                    fuzzy_arg.clone(),
                     address_arg.clone(),
                     fields_arg.clone(),
+                    count_arg.clone(),
+                    exact_one_arg.clone(),
+                    quiet_arg.clone(),
+                    Arg::new("action")
+                        .index(2)
+                        .value_parser(["get", "set", "up", "down"])
+                        .default_value("get")
+                        .help("Print, set, raise or lower the volume"),
+                    Arg::new("percent")
+                        .index(3)
+                        .value_parser(value_parser!(u8).range(0..=100))
+                        .required_if_eq("action", "set")
+                        .help("Volume to set in percent")
+                        .long_help(
+                            "Volume to set in percent, or how many percentage \
+                            points to raise or lower it by [default: 5]",
+                        ),
                 ])
                 .groups([
                     partial_arg_group.clone(),
                     regex_arg_group.clone(),
                     filter_arg_group.clone(),
                 ]),
+            Command::new("toggle")
+                .visible_alias("t")
+                .before_help("Connect or disconnect a bluetooth device")
+                .args([
+                    name_arg
+                        .clone()
+                        .required(false)
+                        .required_unless_present_any(["pick", "type"]),
+                    partial_arg.clone(),
+                    no_partial_arg.clone(),
+                    regex_arg.clone(),
+                    no_regex_arg.clone(),
+                    fuzzy_arg.clone(),
+                    address_arg.clone(),
+                    fields_arg.clone(),
+                    count_arg.clone(),
+                    exact_one_arg.clone(),
+                    type_arg.clone(),
+                    quiet_arg.clone(),
+                    pick_arg.clone(),
+                    Arg::new("toggle-mode")
+                        .short('m')
+                        .long("toggle-mode")
+                        .value_parser(["independent", "all-on", "all-off", "majority"])
+                        .default_value("independent")
+                        .help("How to toggle if multiple devices match")
+                        .long_help(
+                            "How to toggle if multiple devices match. \
+                            independent toggles each device, all-on connects \
+                            all devices, all-off disconnects all devices and \
+                            majority disconnects all devices if at least \
+                            half of them are connected and connects all \
+                            devices otherwise",
+                        ),
+                ])
+                .groups([
+                    partial_arg_group.clone(),
+                    regex_arg_group.clone(),
+                    filter_arg_group.clone(),
+                ]),
+            Command::new("history")
+                .before_help("Show when devices were connected and disconnected with bt")
+                .args([
+                    name_arg
+                        .clone()
+                        .required(false)
+                        .help("Only show devices matching any of the filters")
+                        .long_help(
+                            "Only show devices whose name or address matches one \
+                            of the filters",
+                        ),
+                    partial_arg.clone(),
+                    no_partial_arg.clone(),
+                    regex_arg.clone(),
+                    no_regex_arg.clone(),
+                    fuzzy_arg.clone(),
+                    Arg::new("limit")
+                        .short('n')
+                        .long("limit")
+                        .value_name("N")
+                        .value_parser(value_parser!(usize))
+                        .help("Only show the N most recent entries"),
+                ])
+                .groups([partial_arg_group.clone(), regex_arg_group.clone()]),
             Command::new("info")
                 .visible_alias("i")
                 .before_help("Get detailed information about a bluetooth device")
@@ -146,8 +890,31 @@ pub fn build_cli() -> Command {
                     no_partial_arg.clone(),
                     regex_arg.clone(),
                     no_regex_arg.clone(),
+                    fuzzy_arg.clone(),
+                    address_arg.clone(),
+                    fields_arg.clone(),
+                    count_arg.clone(),
+                    exact_one_arg.clone(),
+                    quiet_arg.clone(),
+                ])
+                .groups([
+                    partial_arg_group.clone(),
+                    regex_arg_group.clone(),
+                    filter_arg_group.clone(),
+                ]),
+            Command::new("battery")
+                .before_help("Print the battery level of a bluetooth device")
+                .args([
+                    name_arg.clone(),
+                    partial_arg.clone(),
+                    no_partial_arg.clone(),
+                    regex_arg.clone(),
+                    no_regex_arg.clone(),
+                    fuzzy_arg.clone(),
                     address_arg.clone(),
                     fields_arg.clone(),
+                    count_arg.clone(),
+                    exact_one_arg.clone(),
                 ])
                 .groups([
                     partial_arg_group.clone(),
@@ -163,31 +930,435 @@ pub fn build_cli() -> Command {
                     no_partial_arg.clone(),
                     regex_arg.clone(),
                     no_regex_arg.clone(),
+                    fuzzy_arg.clone(),
                     address_arg.clone(),
                     fields_arg.clone(),
+                    count_arg.clone(),
+                    exact_one_arg.clone(),
+                    quiet_arg.clone(),
                     timeout_arg.clone(),
+                    retries_arg.clone(),
+                    retry_delay_arg.clone(),
+                    pin_arg.clone(),
+                    pin_fd_arg.clone(),
+                    passkey_arg.clone(),
+                    passkey_fd_arg.clone(),
+                    agent_capability_arg.clone(),
+                    no_input_arg.clone(),
+                    Arg::new("trust")
+                        .long("trust")
+                        .action(ArgAction::SetTrue)
+                        .help("Trust paired devices, so they can reconnect on their own"),
+                    Arg::new("connect")
+                        .long("connect")
+                        .action(ArgAction::SetTrue)
+                        .help("Connect devices once they are paired")
+                        .long_help(
+                            "Connect devices once they are paired, reporting \
+                            the result of pairing and connecting each device. \
+                            Only succeeds for devices that are connected as well",
+                        ),
+                    pair_timeout_arg.clone(),
+                    all_arg.clone(),
+                    first_arg.clone(),
+                ])
+                .groups([
+                    partial_arg_group.clone(),
+                    regex_arg_group.clone(),
+                    filter_arg_group.clone(),
+                ]),
+            Command::new("setup")
+                .before_help(
+                    "Scan for, pair, trust and connect a new device and wait \
+                    until its services are resolved",
+                )
+                .args([
+                    name_arg.clone(),
+                    partial_arg.clone(),
+                    no_partial_arg.clone(),
+                    regex_arg.clone(),
+                    no_regex_arg.clone(),
+                    fuzzy_arg.clone(),
+                    address_arg.clone(),
+                    fields_arg.clone(),
+                    exact_one_arg.clone(),
+                    quiet_arg.clone(),
+                    timeout_arg.clone(),
+                    retries_arg.clone(),
+                    retry_delay_arg.clone(),
+                    pin_arg.clone(),
+                    pin_fd_arg.clone(),
+                    passkey_arg.clone(),
+                    passkey_fd_arg.clone(),
+                    agent_capability_arg.clone(),
+                    no_input_arg.clone(),
+                    pair_timeout_arg.clone(),
+                    all_arg.clone(),
+                    first_arg.clone(),
                 ])
                 .groups([
                     partial_arg_group.clone(),
                     regex_arg_group.clone(),
                     filter_arg_group.clone(),
                 ]),
+            Command::new("agent")
+                .before_help(
+                    "Register a pairing agent and keep handling pairing \
+                    requests, e.g. from phones, until Ctrl+C is pressed. \
+                    Prompts are printed as the requests arrive",
+                )
+                .args([
+                    Arg::new("default")
+                        .long("default")
+                        .action(ArgAction::SetTrue)
+                        .help("Make the agent the default agent of bluez")
+                        .long_help(
+                            "Make the agent the default agent of bluez, which \
+                            handles pairings started from other devices \
+                            unless a desktop environment registered its own",
+                        ),
+                    pin_arg.clone(),
+                    pin_fd_arg.clone(),
+                    passkey_arg.clone(),
+                    passkey_fd_arg.clone(),
+                    agent_capability_arg.clone(),
+                    no_input_arg
+                        .clone()
+                        .help("Handle requests without asking")
+                        .long_help(
+                            "Handle requests without asking. Pairing requests \
+                            are rejected unless --accept-all is given and \
+                            only devices that were paired before may use \
+                            services. Pin code or passkey requests fail \
+                            unless given with --pin or --passkey. Unless \
+                            --agent-capability is given, the agent registers \
+                            as NoInputNoOutput",
+                        ),
+                    Arg::new("accept-all")
+                        .long("accept-all")
+                        .action(ArgAction::SetTrue)
+                        .help("Accept all pairing and service requests without asking")
+                        .long_help(
+                            "Accept all pairing and service requests without \
+                            asking. As this lets any device in range pair and \
+                            use services, it is best only used while pairing",
+                        ),
+                ]),
             Command::new("unpair")
                 .visible_alias("up")
                 .before_help("Unpair from a bluetooth device")
+                .args([
+                    name_arg.clone(),
+                    partial_arg.clone().help(
+                        "If set the filter must only match part of the device \
+                        name. Unlike for other commands, this is not the default",
+                    ),
+                    // Removing a bond by accident is hard to undo, so
+                    // unpairing all devices like the filter has to be asked for
+                    no_partial_arg.clone().default_value("true"),
+                    regex_arg.clone(),
+                    no_regex_arg.clone(),
+                    fuzzy_arg.clone(),
+                    address_arg.clone(),
+                    fields_arg.clone(),
+                    count_arg.clone(),
+                    exact_one_arg.clone(),
+                    quiet_arg.clone(),
+                    yes_arg.clone(),
+                ])
+                .groups([
+                    partial_arg_group.clone(),
+                    regex_arg_group.clone(),
+                    filter_arg_group.clone(),
+                ]),
+            Command::new("prune")
+                .before_help("Unpair devices that have not been used for a while")
+                .args([
+                    Arg::new("not-seen-for")
+                        .long("not-seen-for")
+                        .value_name("DURATION")
+                        .required(true)
+                        .value_parser(utils::parse_duration)
+                        .help("Unpair devices not used for DURATION, e.g. 30d")
+                        .long_help(
+                            "Unpair paired devices that were last connected or \
+                            disconnected with bt longer than DURATION ago, \
+                            e.g. 90m, 12h, 30d or 8w. Devices that are \
+                            connected, trusted or were never connected with \
+                            bt are kept",
+                        ),
+                    quiet_arg.clone(),
+                    yes_arg
+                        .clone()
+                        .help("Do not ask for confirmation before unpairing")
+                        .long_help(
+                            "Do not ask for confirmation before unpairing. \
+                            Without it, the command is refused if stdin is not \
+                            a terminal",
+                        ),
+                ]),
+            Command::new("alias")
+                .visible_alias("rename")
+                .before_help("Rename a bluetooth device locally")
+                .args([
+                    name_arg
+                        .clone()
+                        .num_args(1)
+                        .help("Device filter.")
+                        .long_help(
+                            "Device filter. A number selects the device with \
+                            that persistent index (see list --index)",
+                        ),
+                    partial_arg.clone(),
+                    no_partial_arg.clone(),
+                    regex_arg.clone(),
+                    no_regex_arg.clone(),
+                    fuzzy_arg.clone(),
+                    address_arg.clone(),
+                    fields_arg.clone(),
+                    count_arg.clone(),
+                    exact_one_arg.clone(),
+                    quiet_arg.clone(),
+                    Arg::new("alias")
+                        .index(2)
+                        .required(true)
+                        .help("New name of the device")
+                        .long_help(
+                            "New name of the device. An empty name restores \
+                            the name announced by the device",
+                        ),
+                ])
+                .groups([
+                    partial_arg_group.clone(),
+                    regex_arg_group.clone(),
+                    filter_arg_group.clone(),
+                ]),
+            Command::new("block")
+                .before_help("Block a bluetooth device, refusing its connections")
+                .args([
+                    name_arg.clone(),
+                    partial_arg.clone(),
+                    no_partial_arg.clone(),
+                    regex_arg.clone(),
+                    no_regex_arg.clone(),
+                    fuzzy_arg.clone(),
+                    address_arg.clone(),
+                    fields_arg.clone(),
+                    count_arg.clone(),
+                    exact_one_arg.clone(),
+                    quiet_arg.clone(),
+                    yes_arg.clone(),
+                ])
+                .groups([
+                    partial_arg_group.clone(),
+                    regex_arg_group.clone(),
+                    filter_arg_group.clone(),
+                ]),
+            Command::new("unblock")
+                .before_help("Unblock a bluetooth device")
                 .args([
                     name_arg.clone(),
                     partial_arg.clone(),
                     no_partial_arg.clone(),
                     regex_arg.clone(),
                     no_regex_arg.clone(),
+                    fuzzy_arg.clone(),
                     address_arg.clone(),
                     fields_arg.clone(),
+                    count_arg.clone(),
+                    exact_one_arg.clone(),
+                    quiet_arg.clone(),
                 ])
                 .groups([
                     partial_arg_group.clone(),
                     regex_arg_group.clone(),
                     filter_arg_group.clone(),
                 ]),
+            Command::new("wait")
+                .visible_alias("w")
+                .before_help("Wait until a bluetooth device is (dis)connected")
+                .args([
+                    name_arg.clone(),
+                    partial_arg.clone(),
+                    no_partial_arg.clone(),
+                    regex_arg.clone(),
+                    no_regex_arg.clone(),
+                    fuzzy_arg.clone(),
+                    address_arg.clone(),
+                    fields_arg.clone(),
+                    count_arg.clone(),
+                    exact_one_arg.clone(),
+                    quiet_arg.clone(),
+                    Arg::new("for")
+                        .long("for")
+                        .value_parser(["connected", "disconnected"])
+                        .default_value("connected")
+                        .help("State to wait for")
+                        .long_help(
+                            "State to wait for. If multiple devices match, \
+                            all of them must reach the state",
+                        ),
+                    Arg::new("connected")
+                        .long("connected")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all(["for", "disconnected"])
+                        .help("Wait until connected, same as --for connected"),
+                    Arg::new("disconnected")
+                        .long("disconnected")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("for")
+                        .help("Wait until disconnected, same as --for disconnected"),
+                    Arg::new("timeout")
+                        .short('t')
+                        .long("timeout")
+                        .value_parser(value_parser!(u64))
+                        .help("Maximum time to wait in seconds")
+                        .long_help(
+                            "Maximum time to wait in seconds. Exits with a \
+                            non-zero status if the timeout elapses\n\
+                            Default can be controlled with environment \
+                            variable BT_TIMEOUT",
+                        ),
+                ])
+                .groups([
+                    partial_arg_group.clone(),
+                    regex_arg_group.clone(),
+                    filter_arg_group.clone(),
+                ]),
+            Command::new("adapter")
+                .before_help("Show local bluetooth adapters")
+                .subcommands([
+                    Command::new("list")
+                        .visible_alias("ls")
+                        .before_help("List adapters with their state flags"),
+                    Command::new("info")
+                        .before_help("Get detailed information about adapters")
+                        .arg(adapter_arg.clone()),
+                    Command::new("power")
+                        .before_help("Power adapters on or off")
+                        .args([
+                            switch_arg.clone().help("Whether to power on or off"),
+                            adapter_arg.clone().index(2),
+                        ]),
+                    Command::new("discoverable")
+                        .before_help("Make adapters visible to other devices")
+                        .args([
+                            switch_arg.clone().help("Whether to be discoverable"),
+                            adapter_arg.clone().index(2),
+                            Arg::new("timeout")
+                                .short('t')
+                                .long("timeout")
+                                .value_parser(value_parser!(u32))
+                                .help("Seconds until the adapter is hidden again")
+                                .long_help(
+                                    "Seconds until the adapter is hidden \
+                                    again, 0 keeps it discoverable. Defaults \
+                                    to the adapter's current setting",
+                                ),
+                        ]),
+                    Command::new("pairable")
+                        .before_help("Allow or refuse pairing requests")
+                        .args([
+                            switch_arg
+                                .clone()
+                                .help("Whether to accept pairing requests"),
+                            adapter_arg.clone().index(2),
+                        ]),
+                    Command::new("name")
+                        .visible_alias("alias")
+                        .before_help("Set the name adapters are visible as")
+                        .args([
+                            Arg::new("name")
+                                .index(1)
+                                .required(true)
+                                .help("New name of the adapter")
+                                .long_help(
+                                    "New name of the adapter. An empty name \
+                                    restores the system name",
+                                ),
+                            adapter_arg.clone().index(2).long_help(
+                                "Adapter name (e.g. hci0) or address. May \
+                                only be omitted if there is a single adapter",
+                            ),
+                        ]),
+                ]),
+            Command::new("doctor")
+                .before_help("Check that bluetooth is set up correctly and suggest fixes"),
+            Command::new("gen-service")
+                .before_help("Install a systemd user service running bt monitor")
+                .args([
+                    Arg::new("uninstall")
+                        .long("uninstall")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with_all(["notify", "low-battery"])
+                        .help("Remove the service instead"),
+                    low_battery_arg.clone(),
+                    notify_arg.clone(),
+                ]),
+            Command::new("scan")
+                .before_help("Scan for devices, printing them as they are discovered")
+                .args([
+                    timeout_arg
+                        .clone()
+                        .help("Duration of the scan in seconds")
+                        .long_help(
+                            "Duration of the scan in seconds, defaults to 10\n\
+                            Default can be controlled with environment \
+                            variable BT_TIMEOUT",
+                        ),
+                    quiet_arg.clone(),
+                    transport_arg.clone(),
+                    min_rssi_arg.clone(),
+                    pathloss_arg.clone(),
+                    uuid_arg.clone(),
+                ]),
+            Command::new("monitor")
+                .before_help(
+                    "Print device events (connections, pairing and battery \
+                    changes) as they happen",
+                )
+                .args([low_battery_arg.clone(), notify_arg.clone()]),
+            Command::new("tui").before_help(
+                "Show a full-screen table of bluetooth devices with their \
+                state, battery and signal strength, updated on device \
+                events. Keys: c connect, d disconnect, p pair, t toggle \
+                trust, s scan, j/k or arrow keys move, q quit",
+            ),
+            Command::new("status")
+                .before_help("Summarize adapters, connected devices and their battery")
+                .args([
+                    Arg::new("waybar")
+                        .long("waybar")
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("short")
+                        .help("Print the JSON object expected by Waybar")
+                        .long_help(
+                            "Print the JSON object expected by Waybar custom \
+                            modules with text, tooltip, class, alt and \
+                            percentage (lowest battery) keys",
+                        ),
+                    Arg::new("short")
+                        .short('s')
+                        .long("short")
+                        .action(ArgAction::SetTrue)
+                        .help("Print a single status line for status bars")
+                        .long_help(
+                            "Print a single line with the amount of connected \
+                            devices and their lowest battery, e.g. for \
+                            polybar or i3blocks",
+                        ),
+                    Arg::new("connected-glyph")
+                        .long("connected-glyph")
+                        .default_value("●")
+                        .help("Glyph shown by --short if devices are connected"),
+                    Arg::new("disconnected-glyph")
+                        .long("disconnected-glyph")
+                        .default_value("○")
+                        .help("Glyph shown by --short if no device is connected"),
+                    Arg::new("battery-glyph")
+                        .long("battery-glyph")
+                        .default_value("")
+                        .help("Glyph shown by --short before the lowest battery"),
+                ]),
         ])
 }
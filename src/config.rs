@@ -0,0 +1,123 @@
+// vim: cc=81
+use crate::{bluetooth::devices::BatteryThresholds, utils};
+use std::{fs, sync::OnceLock};
+use toml::{Table, Value};
+
+const CONFIG_FILE: &str = "config.toml";
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// Returns the config, loading it on first use. Like environment variables,
+/// it is the same for the whole process.
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(Config::load)
+}
+
+/// Settings from the config file in the config directory. Every setting is
+/// optional, so a missing file is equivalent to an empty one.
+#[derive(Default)]
+pub struct Config {
+    table: Table,
+}
+
+impl Config {
+    /// Loads the config file. An invalid file is reported on stderr and
+    /// results in an empty config.
+    pub fn load() -> Config {
+        let Some(path) = utils::config_dir().map(|dir| dir.join(CONFIG_FILE)) else {
+            return Config::default();
+        };
+        let Ok(content) = fs::read_to_string(&path) else {
+            return Config::default();
+        };
+        match content.parse() {
+            Ok(table) => Config { table },
+            Err(error) => {
+                eprintln!("Ignoring invalid config file {}: {error}", path.display());
+                Config::default()
+            }
+        }
+    }
+
+    /// Returns the value of key in the table section, if it is set.
+    fn get(&self, section: &str, key: &str) -> Option<&Value> {
+        self.table.get(section)?.get(key)
+    }
+
+    /// Returns the default timeout in seconds for commands that scan or wait.
+    pub fn timeout(&self) -> Option<u64> {
+        self.get("defaults", "timeout")?
+            .as_integer()
+            .and_then(|timeout| timeout.try_into().ok())
+    }
+
+    /// Returns when to print in color, "auto", "always" or "never".
+    pub fn color(&self) -> Option<&str> {
+        self.get("defaults", "color")?
+            .as_str()
+            .filter(|color| ["auto", "always", "never"].contains(color))
+    }
+
+    /// Returns whether filters match part of device names by default.
+    pub fn partial(&self) -> Option<bool> {
+        self.get("defaults", "partial")?.as_bool()
+    }
+
+    /// Returns whether filters are regex patterns by default.
+    pub fn regex(&self) -> Option<bool> {
+        self.get("defaults", "regex")?.as_bool()
+    }
+
+    /// Returns the name or address of the adapter to use by default.
+    pub fn adapter(&self) -> Option<&str> {
+        self.get("defaults", "adapter")?.as_str()
+    }
+
+    /// Returns the default output format of list, "csv" or "tsv".
+    pub fn output(&self) -> Option<&str> {
+        self.get("defaults", "output")?
+            .as_str()
+            .filter(|output| ["csv", "tsv"].contains(output))
+    }
+
+    /// Returns the battery thresholds from the battery section, using the
+    /// defaults for missing or invalid values.
+    pub fn battery_thresholds(&self) -> BatteryThresholds {
+        let threshold = |key| {
+            self.get("battery", key)?
+                .as_integer()
+                .and_then(|percentage| u8::try_from(percentage).ok())
+                .filter(|percentage| *percentage <= 100)
+        };
+        let defaults = BatteryThresholds::default();
+        BatteryThresholds {
+            high: threshold("high").unwrap_or(defaults.high),
+            low: threshold("low").unwrap_or(defaults.low),
+        }
+    }
+
+    /// Returns the glyphs list shows before device names, "nerd" or "emoji".
+    pub fn icons(&self) -> Option<&str> {
+        self.get("defaults", "icons")?
+            .as_str()
+            .filter(|icons| ["nerd", "emoji"].contains(icons))
+    }
+
+    /// Returns the shell command to run on device events with the given kind
+    /// name like "connected", if one is configured in the hooks section.
+    pub fn hook(&self, event: &str) -> Option<&str> {
+        self.get("hooks", event)?.as_str()
+    }
+
+    /// Returns the nicknames section as pairs of a nickname and the name or
+    /// address of the device it refers to.
+    pub fn nicknames(&self) -> Vec<(String, String)> {
+        let Some(Value::Table(nicknames)) = self.table.get("nicknames") else {
+            return Vec::new();
+        };
+        nicknames
+            .iter()
+            .filter_map(|(nickname, target)| Some((nickname.clone(), target.as_str()?.to_string())))
+            .collect()
+    }
+}
@@ -0,0 +1,47 @@
+// vim: cc=81
+//! Ctrl+C handling. Operations that leave state behind in BlueZ, like running
+//! discovery or a registered pairing agent, hold a Guard and check interrupted
+//! to clean up first. Without a Guard, Ctrl+C exits right away.
+use std::{
+    process,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+/// Exit code of a process terminated by SIGINT
+const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+static GUARDS: AtomicUsize = AtomicUsize::new(0);
+
+/// Keeps Ctrl+C from exiting the process until it is dropped, which exits
+/// if an interrupt happened and no other Guard is alive.
+pub struct Guard(());
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        if GUARDS.fetch_sub(1, Ordering::SeqCst) == 1 && interrupted() {
+            process::exit(INTERRUPTED_EXIT_CODE);
+        }
+    }
+}
+
+/// Installs the SIGINT handler. A second Ctrl+C exits even while guarded,
+/// in case cleaning up hangs.
+pub fn install_handler() {
+    let _ = ctrlc::set_handler(|| {
+        if GUARDS.load(Ordering::SeqCst) == 0 || INTERRUPTED.swap(true, Ordering::SeqCst) {
+            process::exit(INTERRUPTED_EXIT_CODE);
+        }
+    });
+}
+
+/// Returns a Guard deferring Ctrl+C until the guarded operation cleaned up.
+pub fn guard() -> Guard {
+    GUARDS.fetch_add(1, Ordering::SeqCst);
+    Guard(())
+}
+
+/// Returns whether Ctrl+C was pressed while guarded.
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
@@ -0,0 +1,10 @@
+// vim: cc=81
+//! Device model and bluetooth backends of bt, usable by other programs like
+//! status bars without running bt. Only the print_* methods of devices, lists
+//! and adapters and the prompts of the pairing agent write to the terminal:
+//! operations return their results, bluez::doctor returns its checks and the
+//! D-Bus debug log goes to the writer given to set_debug_dbus.
+pub mod bluetooth;
+pub mod interrupt;
+pub mod term;
+pub mod text;
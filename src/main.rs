@@ -1,122 +1,2241 @@
 // vim: cc=81
-mod bluetooth;
+mod cache;
 mod cli;
+mod config;
+mod notifications;
+mod picker;
+mod service;
+mod state;
+mod tui;
 mod utils;
 
-use bluetooth::{devices::FilterBehaviour, *};
-use bluez::DBusBluetoothManager;
-use clap::ArgMatches;
+use bt::{bluetooth, interrupt, term, text};
+
+use bluetooth::{
+    devices::{FilterBehaviour, IconStyle},
+    *,
+};
+use bluetoothctl::BluetoothctlManager;
+use bluez::{doctor, DBusBluetoothManager, SecretSource};
+use cache::DeviceIndex;
+use clap::{error::ErrorKind, parser::ValueSource, ArgMatches, Command};
+use mock::MockBluetoothManager;
+use serde_json::json;
 use std::{
+    collections::HashMap,
     env,
-    io::{stdout, IsTerminal},
-    sync::{Arc, Mutex},
-    time::Duration,
+    io::{self, stdout, IsTerminal, Write},
+    path::Path,
+    process::{self, ExitCode},
+    sync::{Arc, Mutex, OnceLock},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
+use term::ansi::{ANSI_GREEN, ANSI_RED, ANSI_RESET};
+
+/// Exit status if no device (or adapter) matches the filter
+const EXIT_NO_MATCH: u8 = 2;
+/// Exit status if bluetoothd cannot be reached over D-Bus
+const EXIT_UNAVAILABLE: u8 = 3;
+/// Exit status if an operation only succeeded for some of the devices
+const EXIT_PARTIAL_FAILURE: u8 = 4;
+/// Exit status if --exact-one is given and several devices match
+const EXIT_AMBIGUOUS: u8 = 5;
+
+/// How long bt setup waits for the services of connected devices
+const SETUP_SERVICES_TIMEOUT: Duration = Duration::from_secs(10);
+/// Percentage points bt volume up and down change the volume by by default
+const VOLUME_STEP: u8 = 5;
+/// Commands that scan for devices matching the filter, so --exact-one can
+/// only be checked after scanning
+const SCANNING_COMMANDS: [&str; 3] = ["connect", "pair", "setup"];
+
+/// Lines of stdin given by the filter "-". They are only read once, as a
+/// command may filter devices several times.
+static STDIN_FILTERS: OnceLock<Vec<String>> = OnceLock::new();
 
-fn main() {
+fn main() -> ExitCode {
+    interrupt::install_handler();
     let mut command = cli::build_cli();
     let matches = command.get_matches_mut();
+    // Diagnosis has to work especially when the manager cannot be created
+    if let Some(("doctor", _)) = matches.subcommand() {
+        return print_diagnosis(&doctor::diagnose(), use_color(&matches));
+    }
+    if let Some(("gen-service", sub_matches)) = matches.subcommand() {
+        return gen_service(sub_matches);
+    }
+    // History is read from a file and does not need bluetooth
+    if let Some(("history", sub_matches)) = matches.subcommand() {
+        print_history(sub_matches, matches.get_flag("json"));
+        return ExitCode::SUCCESS;
+    }
+    // The mock backend is meant for tests, so it is only selectable here
+    let backend = matches
+        .get_one::<String>("backend")
+        .cloned()
+        .or_else(|| env::var("BT_BACKEND").ok().filter(|var| !var.is_empty()))
+        .unwrap_or_else(|| "auto".to_string());
+    match backend.as_str() {
+        "mock" => {
+            let Some(fixture) = env::var_os("BT_MOCK_FIXTURE") else {
+                eprintln!("BT_BACKEND=mock needs the fixture file in BT_MOCK_FIXTURE.");
+                return ExitCode::FAILURE;
+            };
+            match MockBluetoothManager::load(Path::new(&fixture)) {
+                Ok(bluetooth_manager) => run(bluetooth_manager, &matches, &mut command),
+                Err(error) => {
+                    eprintln!("{error}");
+                    ExitCode::FAILURE
+                }
+            }
+        }
+        "bluetoothctl" => {
+            if !bluetoothctl::is_available() {
+                eprintln!("Could not run bluetoothctl. Is it installed?");
+                return ExitCode::from(EXIT_UNAVAILABLE);
+            }
+            run(BluetoothctlManager::new(), &matches, &mut command)
+        }
+        "auto" | "bluez" => match DBusBluetoothManager::new() {
+            Ok(mut bluetooth_manager) => {
+                configure_bluez(&mut bluetooth_manager, &matches);
+                if let Some(("agent", sub_matches)) = matches.subcommand() {
+                    return run_agent(&mut bluetooth_manager, sub_matches);
+                }
+                run(bluetooth_manager, &matches, &mut command)
+            }
+            // E.g. in containers without access to the system bus
+            Err(_) if backend == "auto" && bluetoothctl::is_available() => {
+                eprintln!("Could not connect to the system D-Bus, falling back to bluetoothctl.");
+                run(BluetoothctlManager::new(), &matches, &mut command)
+            }
+            Err(_) => {
+                eprintln!(
+                    "Could not connect to the system D-Bus. Run bt doctor to diagnose the problem."
+                );
+                ExitCode::from(EXIT_UNAVAILABLE)
+            }
+        },
+        backend => {
+            eprintln!("Unknown backend {backend} in BT_BACKEND.");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Applies the arguments only the D-Bus backend supports, like retries and
+/// where pairing gets pin codes from.
+fn configure_bluez(bluetooth_manager: &mut DBusBluetoothManager, matches: &ArgMatches) {
+    if matches.get_flag("debug-dbus") {
+        bluetooth_manager.set_debug_dbus(Some(|line| eprintln!("{line}")));
+    }
+    let Some((_, sub_matches)) = matches.subcommand() else {
+        return;
+    };
+    if let Ok(Some(retries)) = sub_matches.try_get_one::<u32>("retries") {
+        let retry_delay = sub_matches
+            .get_one::<u64>("retry-delay")
+            .map_or(Duration::from_secs(1), |seconds| {
+                Duration::from_secs(*seconds)
+            });
+        bluetooth_manager.set_retries(*retries, retry_delay);
+    }
+    // Arguments take precedence over the environment
+    if let Some(pin) = env::var("BT_PIN").ok().filter(|pin| !pin.is_empty()) {
+        bluetooth_manager.set_pin_source(SecretSource::Value(pin));
+    }
+    if let Some(passkey) = env::var("BT_PASSKEY").ok().filter(|key| !key.is_empty()) {
+        bluetooth_manager.set_passkey_source(SecretSource::Value(passkey));
+    }
+    if let Ok(Some(pin)) = sub_matches.try_get_one::<String>("pin") {
+        bluetooth_manager.set_pin_source(SecretSource::Value(pin.clone()));
+    }
+    if let Ok(Some(passkey)) = sub_matches.try_get_one::<u32>("passkey") {
+        bluetooth_manager.set_passkey_source(SecretSource::Value(passkey.to_string()));
+    }
+    if let Ok(Some(fd)) = sub_matches.try_get_one::<u32>("pin-fd") {
+        bluetooth_manager.set_pin_source(SecretSource::Fd(*fd));
+    }
+    if let Ok(Some(fd)) = sub_matches.try_get_one::<u32>("passkey-fd") {
+        bluetooth_manager.set_passkey_source(SecretSource::Fd(*fd));
+    }
+    bluetooth_manager.set_no_input(has_flag(sub_matches, "no-input"));
+    bluetooth_manager.set_accept_all(has_flag(sub_matches, "accept-all"));
+    if let Ok(Some(capability)) = sub_matches.try_get_one::<String>("agent-capability") {
+        bluetooth_manager.set_agent_capability(capability.clone());
+    }
+    if let Ok(Some(seconds)) = sub_matches.try_get_one::<u64>("pair-timeout") {
+        bluetooth_manager.set_pair_timeout(Duration::from_secs(*seconds));
+    }
+}
+
+/// Returns the name to show for the profile argument of matches, which was
+/// parsed into uuid: the name it was given by (like a2dp), or else the
+/// well-known name of uuid or uuid itself.
+fn profile_name<'a>(matches: &ArgMatches, uuid: &'a str) -> &'a str {
+    matches
+        .get_raw("profile")
+        .and_then(|mut raw| raw.next())
+        .and_then(|raw| raw.to_str())
+        .and_then(uuids::known_name)
+        .or_else(|| uuids::service_name(uuid))
+        .unwrap_or(uuid)
+}
+
+/// Prints the checks of bt doctor with fixes for failed ones. Returns failure
+/// unless all checks passed.
+fn print_diagnosis(checks: &[doctor::Check], in_color: bool) -> ExitCode {
+    let (ansi_green, ansi_red, ansi_reset) = if in_color {
+        (ANSI_GREEN, ANSI_RED, ANSI_RESET)
+    } else {
+        ("", "", "")
+    };
+    for check in checks {
+        match &check.fix {
+            None => println!("[{ansi_green} ok {ansi_reset}] {}", check.message),
+            Some(fix) => println!(
+                "[{ansi_red}FAIL{ansi_reset}] {}\n       Fix: {fix}",
+                check.message
+            ),
+        }
+    }
+    if checks.iter().all(|check| check.fix.is_none()) {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+/// Handles pairing requests from other devices until Ctrl+C is pressed.
+fn run_agent(bluetooth_manager: &mut DBusBluetoothManager, sub_matches: &ArgMatches) -> ExitCode {
+    // stdout is left to JSON output
+    eprintln!("Waiting for pairing requests, press Ctrl+C to stop.");
+    match bluetooth_manager.run_agent(sub_matches.get_flag("default")) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!(
+                "Could not register the agent: {}",
+                error.message().unwrap_or_default()
+            );
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Returns whether the user may be asked questions. Prompts would mix with
+/// output meant for other programs otherwise.
+fn is_interactive(matches: &ArgMatches) -> bool {
+    let no_prompts = matches.subcommand().is_some_and(|(_, sub_matches)| {
+        has_flag(sub_matches, "quiet") || has_flag(sub_matches, "no-input")
+    });
+    stdout().lock().is_terminal() && !matches.get_flag("json") && !no_prompts
+}
+
+/// Returns whether to print in color according to color_choice.
+fn use_color(matches: &ArgMatches) -> bool {
+    match color_choice(matches) {
+        "always" => true,
+        "never" => false,
+        _ => stdout().lock().is_terminal(),
+    }
+}
+
+/// Runs the command of matches with bluetooth_manager and returns its exit
+/// status.
+fn run<M: BluetoothManager + Send>(
+    mut bluetooth_manager: M,
+    matches: &ArgMatches,
+    command: &mut Command,
+) -> ExitCode {
     let stdout_is_terminal = stdout().lock().is_terminal();
-    if let Ok(mut bluetooth_manager) = DBusBluetoothManager::new() {
-        bluetooth_manager.set_scan_display_hint(stdout_is_terminal);
+    let json_output = matches.get_flag("json");
+    let quiet = matches
+        .subcommand()
+        .is_some_and(|(_, sub_matches)| has_flag(sub_matches, "quiet"));
+    // Whether progress messages of operations on devices are printed
+    let verbose = !json_output && !quiet;
+    let interactive = is_interactive(matches);
+    let config = config::get();
+    let print_in_color = use_color(matches);
+    let mut exit_code = ExitCode::SUCCESS;
+    let selected_adapter = matches.get_one::<String>("select-adapter");
+    // The adapter from the config is only preferred, unlike --adapter
+    let preferred_adapter = config.adapter().filter(|_| selected_adapter.is_none());
+    bluetooth_manager.set_selected_adapter(
+        selected_adapter
+            .map(String::as_str)
+            .or(preferred_adapter)
+            .map(String::from),
+    );
+    if let Some((_, sub_matches)) = matches.subcommand() {
+        bluetooth_manager.set_discovery_filter(discovery_filter(sub_matches));
+    }
+    bluetooth_manager.update();
+    if preferred_adapter.is_some() && bluetooth_manager.get_all_adapters().is_empty() {
+        bluetooth_manager.set_selected_adapter(None);
         bluetooth_manager.update();
-        let bluetooth_manager = Arc::new(Mutex::new(bluetooth_manager));
-
-        // Initialize empty device list and set values
-        let mut devicelist = DeviceList::new(Arc::clone(&bluetooth_manager));
-        devicelist.set_quote_names(stdout_is_terminal);
-        devicelist.set_print_in_color(stdout_is_terminal);
-
-        match matches.subcommand() {
-            Some(("list", sub_matches)) => {
-                let long_output = sub_matches.get_flag("long_output");
-                let linewise = sub_matches.get_flag("linewise");
-                if sub_matches.get_flag("all") {
-                    let timeout = get_timeout(&sub_matches.get_one("timeout").copied(), 30);
+    }
+    let no_adapters = bluetooth_manager.get_all_adapters().is_empty();
+    if let Some(adapter) = selected_adapter.filter(|_| no_adapters) {
+        eprintln!("No adapter matches {adapter}.");
+        return ExitCode::from(EXIT_NO_MATCH);
+    } else if no_adapters {
+        eprintln!("No bluetooth adapter found. Run bt doctor to diagnose the problem.");
+    }
+    let bluetooth_manager = Arc::new(Mutex::new(bluetooth_manager));
+
+    // Initialize empty device list and set values
+    let mut devicelist = DeviceList::new(Arc::clone(&bluetooth_manager));
+    devicelist.set_quote_names(stdout_is_terminal);
+    devicelist.set_nicknames(config.nicknames());
+    devicelist.set_battery_thresholds(config.battery_thresholds());
+    devicelist.set_print_in_color(print_in_color);
+    let mut index = DeviceIndex::load();
+
+    // The devices filtered for --exact-one are reused by the command, so the
+    // filter is only applied once. Commands scanning for the filter check
+    // after scanning instead.
+    let mut prefiltered = matches
+        .subcommand()
+        .filter(|(name, sub_matches)| {
+            !SCANNING_COMMANDS.contains(name) && has_flag(sub_matches, "exact-one")
+        })
+        .map(|(_, sub_matches)| filtered_devices(&mut devicelist, sub_matches, &mut index));
+    let exact_one_exit = prefiltered
+        .as_ref()
+        .zip(matches.subcommand())
+        .and_then(|(devices, (_, sub_matches))| check_exact_one(devices, sub_matches));
+
+    match matches.subcommand() {
+        // Scripts requiring a unique match fail before anything is done
+        Some(_) if exact_one_exit.is_some() => {
+            exit_code = exact_one_exit.unwrap_or(ExitCode::FAILURE);
+        }
+        // Counting replaces the command for all commands with a filter,
+        // list handles counting itself after applying its own filters
+        Some((name, sub_matches)) if name != "list" && has_flag(sub_matches, "count") => {
+            let devices =
+                command_devices(&mut prefiltered, &mut devicelist, sub_matches, &mut index);
+            exit_code = print_count(&devices);
+        }
+        Some(("list", sub_matches)) => {
+            let output = sub_matches
+                .get_one::<String>("output")
+                .map(String::as_str)
+                .or_else(|| config.output());
+            // The output format may also come from the config, so clap cannot
+            // require it
+            let columns_given =
+                sub_matches.value_source("columns") == Some(ValueSource::CommandLine);
+            if columns_given && output.is_none() {
+                command
+                    .error(
+                        ErrorKind::MissingRequiredArgument,
+                        "--columns requires --output or the output setting",
+                    )
+                    .exit();
+            }
+            let long_output = sub_matches.get_flag("long_output");
+            let linewise = sub_matches.get_flag("linewise");
+            if sub_matches.get_flag("all") {
+                let timeout = get_timeout(&sub_matches.get_one("timeout").copied(), 30);
+                with_scan_hint(interactive, || {
                     bluetooth_manager
                         .lock()
                         .expect("Mutex should not be poisoned.")
                         .scan_mut(&Duration::from_secs(timeout))
                         .update();
+                });
+            }
+            if let Some(interval) = sub_matches.get_one::<u64>("watch") {
+                // Ctrl+C ends watching instead of the process, so the indices
+                // of devices that showed up are saved before exiting
+                let _guard = interrupt::guard();
+                let tree = sub_matches.get_flag("tree");
+                watch(&Duration::from_secs(*interval), || {
+                    bluetooth_manager
+                        .lock()
+                        .expect("Mutex should not be poisoned.")
+                        .update();
+                    // Filtering out all devices gives an empty list with
+                    // the same settings
+                    let mut frame = devicelist.filtered(|_| false);
+                    let mut frame = listed_devices(&mut frame, sub_matches, &mut index);
+                    if tree {
+                        frame.print_tree();
+                    } else {
+                        frame.print(linewise, long_output);
+                    }
+                });
+                index.save();
+            } else {
+                devicelist = listed_devices(&mut devicelist, sub_matches, &mut index);
+                let columns: Vec<&str> = sub_matches
+                    .get_many::<String>("columns")
+                    .map(|columns| columns.map(String::as_str).collect())
+                    .unwrap_or_default();
+                match output {
+                    _ if sub_matches.get_flag("count") => exit_code = print_count(&devicelist),
+                    _ if json_output => devicelist.print_json(),
+                    _ if sub_matches.get_flag("print0") => {
+                        let field = if quiet { "address" } else { "name" };
+                        devicelist.print_field(field, '\0');
+                    }
+                    _ if quiet => devicelist.print_field("address", '\n'),
+                    _ if sub_matches.get_flag("tree") => devicelist.print_tree(),
+                    _ if sub_matches.contains_id("format") => devicelist.print_formatted(
+                        sub_matches
+                            .get_one::<String>("format")
+                            .expect("format is present"),
+                    ),
+                    Some("csv") => devicelist.print_separated(',', &columns),
+                    Some("tsv") => devicelist.print_separated('\t', &columns),
+                    _ => devicelist.print(linewise, long_output),
                 }
-                devicelist.fill();
-                devicelist.print(linewise, long_output);
-            }
-            Some(("connect", sub_matches)) => {
-                let filter = sub_matches
-                    .get_one::<String>("filter")
-                    .expect("filter is required");
-                let count = devicelist
-                    .fill()
-                    .filtered_name(filter, get_behaviour(sub_matches))
-                    .connect_all();
-                println!("Connected {} devices.", count);
-            }
-            Some(("disconnect", sub_matches)) => {
-                let filter = sub_matches
-                    .get_one::<String>("filter")
-                    .expect("filter is required");
-                let count = devicelist
-                    .fill()
-                    .filtered_name(filter, get_behaviour(sub_matches))
-                    .disconnect_all();
-                println!("Disconnected {} devices.", count);
-            }
-            Some(("info", sub_matches)) => {
-                let filter = sub_matches
-                    .get_one::<String>("filter")
-                    .expect("filter is required");
-                devicelist
-                    .fill()
-                    .filtered_name(filter, get_behaviour(sub_matches))
-                    .print_info_all();
-            }
-            Some(("pair", sub_matches)) => {
-                let filter = sub_matches
-                    .get_one::<String>("filter")
-                    .expect("filter is required");
+            }
+        }
+        Some(("connect", sub_matches)) => {
+            let mut devices = filtered_devices(&mut devicelist, sub_matches, &mut index);
+            // Unknown devices (e.g. removed ones) have to be discovered
+            // before they can be connected
+            if devices.is_empty()
+                && sub_matches.contains_id("filter")
+                && !has_flag(sub_matches, "filter-fields")
+            {
                 let timeout = get_timeout(&sub_matches.get_one("timeout").copied(), 5);
-                bluetooth_manager
+                scan_for_filter(
+                    &mut *bluetooth_manager
+                        .lock()
+                        .expect("Mutex should not be poisoned."),
+                    sub_matches,
+                    &index,
+                    timeout,
+                    interactive,
+                );
+                let mut rescanned = devicelist.filtered(|_| false);
+                devices = filtered_devices(&mut rescanned, sub_matches, &mut index);
+            }
+            let picked = match check_exact_one(&devices, sub_matches) {
+                Some(exit) => Err(exit),
+                None => pick_devices(devices, sub_matches, "Connect", interactive)
+                    .ok_or(ExitCode::FAILURE),
+            };
+            match picked {
+                Ok(devices) => {
+                    let connected_before = connection_states(&devices);
+                    let profile = sub_matches
+                        .get_one::<String>("profile")
+                        .map(|uuid| (uuid, profile_name(sub_matches, uuid)));
+                    let mut results = if let Some((uuid, name)) = profile {
+                        print_attempts(&devices, verbose, &format!("connect {name} of"));
+                        let results = devices.connect_profile_all(uuid);
+                        report_results(
+                            &devices,
+                            &results,
+                            verbose,
+                            &format!("{name} of {{}} connected."),
+                            &format!("Could not connect {name} of {{}}."),
+                        );
+                        results
+                    } else {
+                        print_attempts(&devices, verbose, "connect with");
+                        let results = if sub_matches.get_flag("pair-if-needed") {
+                            devices.connect_or_pair_all()
+                        } else {
+                            devices.connect_all()
+                        };
+                        report_results(
+                            &devices,
+                            &results,
+                            verbose,
+                            "{} connected.",
+                            "Could not connect {}.",
+                        );
+                        results
+                    };
+                    if let Some(seconds) = sub_matches.get_one::<u64>("wait-services") {
+                        results = devices.wait_for_services_all(&Duration::from_secs(*seconds));
+                        report_results(
+                            &devices,
+                            &results,
+                            verbose,
+                            "",
+                            "Timed out waiting for services of {}.",
+                        );
+                    }
+                    record_history(&devices, &connected_before);
+                    // Devices that already were connected are not in the
+                    // history, but still become the most recently used one
+                    for device in devices.iter() {
+                        let device = device.lock().expect("Mutex should not be poisoned.");
+                        if device.connected {
+                            state::record_used(&device.address);
+                        }
+                    }
+                    exit_code = print_result(
+                        json_output,
+                        quiet,
+                        "Connected",
+                        &results,
+                        &devices,
+                        sub_matches,
+                    );
+                }
+                Err(exit) => exit_code = exit,
+            }
+        }
+        Some(("disconnect", sub_matches)) => {
+            let mut devices =
+                command_devices(&mut prefiltered, &mut devicelist, sub_matches, &mut index);
+            if sub_matches.get_flag("first") {
+                devices = first_device(devices, sub_matches);
+            }
+            let connected_before = connection_states(&devices);
+            let results = match sub_matches.get_one::<String>("profile") {
+                Some(uuid) => {
+                    let name = profile_name(sub_matches, uuid);
+                    let results = devices.disconnect_profile_all(uuid);
+                    report_results(
+                        &devices,
+                        &results,
+                        verbose,
+                        &format!("{name} of {{}} disconnected."),
+                        &format!("Could not disconnect {name} of {{}}."),
+                    );
+                    results
+                }
+                None => {
+                    let results = devices.disconnect_all();
+                    report_results(
+                        &devices,
+                        &results,
+                        verbose,
+                        "{} disconnected.",
+                        "Could not disconnect {}.",
+                    );
+                    results
+                }
+            };
+            record_history(&devices, &connected_before);
+            exit_code = print_result(
+                json_output,
+                quiet,
+                "Disconnected",
+                &results,
+                &devices,
+                sub_matches,
+            );
+        }
+        Some(("audio-profile", sub_matches)) => {
+            let profile = sub_matches
+                .get_one::<String>("profile")
+                .expect("profile is required");
+            // Headsets only use one of the profiles at a time anyway
+            let (connect, disconnect) = match profile.as_str() {
+                "a2dp" => ("a2dp", "hfp"),
+                _ => ("hfp", "a2dp"),
+            };
+            let uuid = |name| uuids::parse_uuid(name).expect("Profile names should be known");
+            let devices =
+                command_devices(&mut prefiltered, &mut devicelist, sub_matches, &mut index);
+            let results = devices.switch_profile_all(&uuid(connect), &uuid(disconnect));
+            report_results(
+                &devices,
+                &results,
+                verbose,
+                &format!("{{}} switched to {profile}."),
+                &format!("Could not switch {{}} to {profile}."),
+            );
+            exit_code = print_result(
+                json_output,
+                quiet,
+                &format!("Switched to {profile} on"),
+                &results,
+                &devices,
+                sub_matches,
+            );
+        }
+        Some(("media", sub_matches)) => {
+            let name = sub_matches
+                .get_one::<String>("command")
+                .expect("command is required");
+            let follow = sub_matches.get_flag("follow");
+            let devices =
+                command_devices(&mut prefiltered, &mut devicelist, sub_matches, &mut index);
+            exit_code = match MediaCommand::from_name(name) {
+                Some(_) if follow => {
+                    eprintln!("--follow can only be used with status.");
+                    ExitCode::FAILURE
+                }
+                Some(command) => {
+                    let results = devices.control_media_all(command);
+                    report_results(
+                        &devices,
+                        &results,
+                        verbose,
+                        &format!("Sent {} to {{}}.", command.name()),
+                        &format!("Could not send {} to {{}}.", command.name()),
+                    );
+                    print_result(
+                        json_output,
+                        quiet,
+                        &format!("Sent {} to", command.name()),
+                        &results,
+                        &devices,
+                        sub_matches,
+                    )
+                }
+                None => print_media_status(&devices, json_output, follow, sub_matches),
+            };
+        }
+        Some(("volume", sub_matches)) => {
+            let devices =
+                command_devices(&mut prefiltered, &mut devicelist, sub_matches, &mut index);
+            let percent = sub_matches.get_one::<u8>("percent").copied();
+            let step = percent.unwrap_or(VOLUME_STEP);
+            exit_code = match sub_matches.get_one::<String>("action").map(String::as_str) {
+                Some("set") => {
+                    let percent = percent.expect("percent is required for set");
+                    let results = devices.set_volume_all(percent);
+                    report_results(
+                        &devices,
+                        &results,
+                        verbose,
+                        &format!("Volume of {{}} set to {percent}%."),
+                        "Could not set the volume of {}.",
+                    );
+                    print_result(
+                        json_output,
+                        quiet,
+                        "Set volume of",
+                        &results,
+                        &devices,
+                        sub_matches,
+                    )
+                }
+                Some("up") => {
+                    let results = devices.change_volume_all(step.try_into().unwrap_or(i8::MAX));
+                    report_results(
+                        &devices,
+                        &results,
+                        verbose,
+                        "Raised the volume of {}.",
+                        "Could not raise the volume of {}.",
+                    );
+                    print_result(
+                        json_output,
+                        quiet,
+                        "Raised volume of",
+                        &results,
+                        &devices,
+                        sub_matches,
+                    )
+                }
+                Some("down") => {
+                    let results = devices.change_volume_all(-step.try_into().unwrap_or(i8::MAX));
+                    report_results(
+                        &devices,
+                        &results,
+                        verbose,
+                        "Lowered the volume of {}.",
+                        "Could not lower the volume of {}.",
+                    );
+                    print_result(
+                        json_output,
+                        quiet,
+                        "Lowered volume of",
+                        &results,
+                        &devices,
+                        sub_matches,
+                    )
+                }
+                _ => print_volumes(&devices, json_output, sub_matches),
+            };
+        }
+        Some(("toggle", sub_matches)) => {
+            let devices =
+                command_devices(&mut prefiltered, &mut devicelist, sub_matches, &mut index);
+            let connected_count = devices
+                .iter()
+                .filter(|device| {
+                    device
+                        .lock()
+                        .expect("Mutex should not be poisoned.")
+                        .connected
+                })
+                .count();
+            // Decide on one action for all devices before issuing calls,
+            // None toggles every device independently
+            let connect = match sub_matches
+                .get_one::<String>("toggle-mode")
+                .map(String::as_str)
+            {
+                Some("all-on") => Some(true),
+                Some("all-off") => Some(false),
+                Some("majority") => Some(connected_count * 2 < devices.len()),
+                _ => None,
+            };
+            let connected_before = connection_states(&devices);
+            let results = match connect {
+                Some(connect) => {
+                    // Devices already in the requested state are left alone,
+                    // but count as toggled
+                    let pending = devices.filtered(|device| device.connected != connect);
+                    let mut results: DeviceResults = devices
+                        .iter()
+                        .map(|device| device.lock().expect("Mutex should not be poisoned."))
+                        .filter(|device| device.connected == connect)
+                        .map(|device| (device.address.clone(), Ok(())))
+                        .collect();
+                    if connect {
+                        print_attempts(&pending, verbose, "connect with");
+                        results.extend(pending.connect_all());
+                    } else {
+                        results.extend(pending.disconnect_all());
+                    }
+                    results
+                }
+                None => devices.toggle_all(),
+            };
+            report_results(
+                &devices,
+                &results,
+                verbose,
+                "Toggled {}.",
+                "Could not toggle {}.",
+            );
+            record_history(&devices, &connected_before);
+            exit_code = print_result(
+                json_output,
+                quiet,
+                "Toggled",
+                &results,
+                &devices,
+                sub_matches,
+            );
+        }
+        Some(("info", sub_matches)) => {
+            let devices =
+                command_devices(&mut prefiltered, &mut devicelist, sub_matches, &mut index);
+            if json_output || !quiet {
+                devices.update_transports_all();
+            }
+            if json_output {
+                devices.print_json();
+            } else if quiet {
+                devices.print_field("address", '\n');
+            } else {
+                devices.print_info_all();
+            }
+            if devices.is_empty() {
+                exit_code = no_match(sub_matches);
+            }
+        }
+        Some(("battery", sub_matches)) => {
+            let devices =
+                command_devices(&mut prefiltered, &mut devicelist, sub_matches, &mut index);
+            let all_known = if json_output {
+                devices.print_json();
+                devices
+                    .iter()
+                    .all(|device| device.lock().is_ok_and(|device| device.battery.is_some()))
+            } else {
+                devices.print_battery(!stdout_is_terminal)
+            };
+            if devices.is_empty() {
+                exit_code = no_match(sub_matches);
+            } else if !all_known {
+                exit_code = ExitCode::FAILURE;
+            }
+        }
+        Some(("pair", sub_matches)) => {
+            let timeout = get_timeout(&sub_matches.get_one("timeout").copied(), 5);
+            let mut bt_man = bluetooth_manager
+                .lock()
+                .expect("Mutex should not be poisoned.");
+            scan_for_filter(&mut *bt_man, sub_matches, &index, timeout, interactive);
+            drop(bt_man);
+            let devices = filtered_devices(&mut devicelist, sub_matches, &mut index);
+            let picked = match check_exact_one(&devices, sub_matches) {
+                Some(exit) => Err(exit),
+                None => {
+                    pick_devices(devices, sub_matches, "Pair", interactive).ok_or(ExitCode::FAILURE)
+                }
+            };
+            match picked {
+                Ok(devices) => {
+                    let connect = sub_matches.get_flag("connect");
+                    let connected_before = connection_states(&devices);
+                    print_attempts(&devices, verbose, "pair with");
+                    let mut results = devices.pair_all_with(sub_matches.get_flag("trust"), false);
+                    report_results(
+                        &devices,
+                        &results,
+                        verbose,
+                        "{} paired.",
+                        "Could not pair {}.",
+                    );
+                    if connect {
+                        let paired = devices.filtered(|device| {
+                            results.iter().any(|(address, result)| {
+                                *address == device.address && succeeded(result)
+                            })
+                        });
+                        let connected = paired.connect_all();
+                        report_results(
+                            &paired,
+                            &connected,
+                            verbose,
+                            "{} connected.",
+                            "Could not connect {}.",
+                        );
+                        // Devices only count as paired once connected as well
+                        for (address, result) in connected {
+                            if let Some(entry) = results.iter_mut().find(|(a, _)| *a == address) {
+                                entry.1 = result;
+                            }
+                        }
+                    }
+                    record_history(&devices, &connected_before);
+                    let action = if connect {
+                        "Paired and connected"
+                    } else {
+                        "Paired"
+                    };
+                    exit_code =
+                        print_result(json_output, quiet, action, &results, &devices, sub_matches);
+                }
+                Err(exit) => exit_code = exit,
+            }
+        }
+        Some(("setup", sub_matches)) => {
+            let timeout = get_timeout(&sub_matches.get_one("timeout").copied(), 10);
+            let mut devices = filtered_devices(&mut devicelist, sub_matches, &mut index);
+            // Only devices that are not known yet need to be scanned for
+            if devices.is_empty() {
+                scan_for_filter(
+                    &mut *bluetooth_manager
+                        .lock()
+                        .expect("Mutex should not be poisoned."),
+                    sub_matches,
+                    &index,
+                    timeout,
+                    interactive,
+                );
+                let mut rescanned = devicelist.filtered(|_| false);
+                devices = filtered_devices(&mut rescanned, sub_matches, &mut index);
+            }
+            let picked = match check_exact_one(&devices, sub_matches) {
+                Some(exit) => Err(exit),
+                None => pick_devices(devices, sub_matches, "Set up", interactive)
+                    .ok_or(ExitCode::FAILURE),
+            };
+            match picked {
+                Ok(devices) => {
+                    let connected_before = connection_states(&devices);
+                    print_attempts(&devices, verbose, "set up");
+                    let mut results = devices.pair_all_with(true, true);
+                    report_results(
+                        &devices,
+                        &results,
+                        verbose,
+                        "{} paired, trusted and connected.",
+                        "Could not set up {}.",
+                    );
+                    record_history(&devices, &connected_before);
+                    if results.iter().any(|(_, result)| succeeded(result)) {
+                        if verbose {
+                            println!("Waiting for services to be resolved...");
+                        }
+                        results = devices.wait_for_services_all(&SETUP_SERVICES_TIMEOUT);
+                        report_results(
+                            &devices,
+                            &results,
+                            verbose,
+                            "",
+                            "Timed out waiting for services of {}.",
+                        );
+                    }
+                    exit_code = print_result(
+                        json_output,
+                        quiet,
+                        "Set up",
+                        &results,
+                        &devices,
+                        sub_matches,
+                    );
+                }
+                Err(exit) => exit_code = exit,
+            }
+        }
+        Some(("unpair", sub_matches)) => {
+            let devices =
+                command_devices(&mut prefiltered, &mut devicelist, sub_matches, &mut index);
+            if confirm_devices(&devices, "unpair", sub_matches) {
+                let results = devices.unpair_all();
+                report_results(
+                    &devices,
+                    &results,
+                    verbose,
+                    "{} unpaired.",
+                    "Could not unpair {}.",
+                );
+                // Unpaired devices are forgotten by bluez, so free their
+                // index
+                for device in devices.iter() {
+                    let device = device.lock().expect("Mutex should not be poisoned.");
+                    if !device.paired {
+                        index.forget(&device.address);
+                    }
+                }
+                exit_code = print_result(
+                    json_output,
+                    quiet,
+                    "Unpaired",
+                    &results,
+                    &devices,
+                    sub_matches,
+                );
+            } else {
+                exit_code = ExitCode::FAILURE;
+            }
+        }
+        Some(("prune", sub_matches)) => {
+            let age = sub_matches
+                .get_one::<Duration>("not-seen-for")
+                .expect("not-seen-for is required");
+            exit_code = prune(
+                &mut devicelist,
+                age,
+                json_output,
+                quiet,
+                sub_matches,
+                &mut index,
+            );
+        }
+        Some(("alias", sub_matches)) => {
+            let alias = sub_matches
+                .get_one::<String>("alias")
+                .expect("alias is required");
+            let devices =
+                command_devices(&mut prefiltered, &mut devicelist, sub_matches, &mut index);
+            // Giving several devices the same name is almost certainly
+            // unintended
+            if devices.len() > 1 {
+                eprintln!(
+                    "{} devices match the filter, refusing to rename them.",
+                    devices.len()
+                );
+                exit_code = ExitCode::FAILURE;
+            } else {
+                let old_names: Vec<String> = devices
+                    .iter()
+                    .map(|device| {
+                        let device = device.lock().expect("Mutex should not be poisoned.");
+                        device.get_name_colored()
+                    })
+                    .collect();
+                let results = devices.rename_all(alias);
+                // At most one device is renamed
+                let old_name = old_names.first().map_or("", String::as_str);
+                report_results(
+                    &devices,
+                    &results,
+                    verbose,
+                    &format!("Renamed {old_name} to {{}}."),
+                    "Could not rename {}.",
+                );
+                exit_code = print_result(
+                    json_output,
+                    quiet,
+                    "Renamed",
+                    &results,
+                    &devices,
+                    sub_matches,
+                );
+            }
+        }
+        Some(("block", sub_matches)) => {
+            let devices =
+                command_devices(&mut prefiltered, &mut devicelist, sub_matches, &mut index);
+            if confirm_devices(&devices, "block", sub_matches) {
+                let results = devices.block_all();
+                report_results(
+                    &devices,
+                    &results,
+                    verbose,
+                    "{} blocked.",
+                    "Could not block {}.",
+                );
+                exit_code = print_result(
+                    json_output,
+                    quiet,
+                    "Blocked",
+                    &results,
+                    &devices,
+                    sub_matches,
+                );
+            } else {
+                exit_code = ExitCode::FAILURE;
+            }
+        }
+        Some(("unblock", sub_matches)) => {
+            let devices =
+                command_devices(&mut prefiltered, &mut devicelist, sub_matches, &mut index);
+            let results = devices.unblock_all();
+            report_results(
+                &devices,
+                &results,
+                verbose,
+                "{} unblocked.",
+                "Could not unblock {}.",
+            );
+            exit_code = print_result(
+                json_output,
+                quiet,
+                "Unblocked",
+                &results,
+                &devices,
+                sub_matches,
+            );
+        }
+        Some(("wait", sub_matches)) => {
+            let connected = !sub_matches.get_flag("disconnected")
+                && sub_matches
+                    .get_one::<String>("for")
+                    .is_some_and(|state| state == "connected");
+            let timeout = get_timeout(&sub_matches.get_one("timeout").copied(), 30);
+            let devices =
+                command_devices(&mut prefiltered, &mut devicelist, sub_matches, &mut index);
+            let results = devices.wait_for_connected_all(connected, &Duration::from_secs(timeout));
+            let state = if connected {
+                "connected"
+            } else {
+                "disconnected"
+            };
+            report_results(
+                &devices,
+                &results,
+                verbose,
+                &format!("{{}} {state}."),
+                &format!("Timed out waiting for {{}} to be {state}."),
+            );
+            let reached = results.iter().all(|(_, result)| result.is_ok());
+            if json_output {
+                println!(
+                    "{}",
+                    json!({"reached": reached, "devices": devices.to_json()})
+                );
+            } else if quiet {
+                devices.print_field("address", '\n');
+            }
+            if devices.is_empty() {
+                exit_code = no_match(sub_matches);
+            } else if !reached {
+                exit_code = ExitCode::FAILURE;
+            }
+        }
+        Some(("adapter", sub_matches)) => {
+            let adapters = bluetooth_manager
+                .lock()
+                .expect("Mutex should not be poisoned.")
+                .get_all_adapters();
+            match sub_matches.subcommand() {
+                Some(("info", info_matches)) => {
+                    let adapters = filtered_adapters(adapters, info_matches);
+                    if adapters.is_empty() {
+                        exit_code = ExitCode::from(EXIT_NO_MATCH);
+                    } else if json_output {
+                        print_adapters_json(&adapters);
+                    } else {
+                        for adapter in &adapters {
+                            adapter.print_info(print_in_color);
+                        }
+                    }
+                }
+                Some(("power", power_matches)) => {
+                    let powered = get_switch(power_matches);
+                    let bt_man = bluetooth_manager
+                        .lock()
+                        .expect("Mutex should not be poisoned.");
+                    let state = if powered { "on" } else { "off" };
+                    exit_code = set_adapters(
+                        filtered_adapters(adapters, power_matches),
+                        json_output,
+                        &format!("powered {state}"),
+                        |adapter| {
+                            let success = bt_man.set_adapter_powered(adapter, powered);
+                            if success {
+                                adapter.powered = powered;
+                            }
+                            success
+                        },
+                    );
+                }
+                Some(("discoverable", discoverable_matches)) => {
+                    let discoverable = get_switch(discoverable_matches);
+                    let timeout = discoverable_matches.get_one::<u32>("timeout").copied();
+                    let bt_man = bluetooth_manager
+                        .lock()
+                        .expect("Mutex should not be poisoned.");
+                    let action = match (discoverable, timeout) {
+                        (true, Some(timeout @ 1..)) => {
+                            format!("discoverable for {timeout} seconds")
+                        }
+                        (true, _) => "discoverable".to_string(),
+                        (false, _) => "hidden".to_string(),
+                    };
+                    exit_code = set_adapters(
+                        filtered_adapters(adapters, discoverable_matches),
+                        json_output,
+                        &action,
+                        |adapter| {
+                            let success =
+                                bt_man.set_adapter_discoverable(adapter, discoverable, timeout);
+                            if success {
+                                adapter.discoverable = discoverable;
+                            }
+                            success
+                        },
+                    );
+                }
+                Some(("pairable", pairable_matches)) => {
+                    let pairable = get_switch(pairable_matches);
+                    let bt_man = bluetooth_manager
+                        .lock()
+                        .expect("Mutex should not be poisoned.");
+                    let state = if pairable { "on" } else { "off" };
+                    exit_code = set_adapters(
+                        filtered_adapters(adapters, pairable_matches),
+                        json_output,
+                        &format!("pairable {state}"),
+                        |adapter| {
+                            let success = bt_man.set_adapter_pairable(adapter, pairable);
+                            if success {
+                                adapter.pairable = pairable;
+                            }
+                            success
+                        },
+                    );
+                }
+                Some(("name", name_matches)) => {
+                    let alias = name_matches
+                        .get_one::<String>("name")
+                        .expect("name is required");
+                    let adapters = filtered_adapters(adapters, name_matches);
+                    // Same as for devices, adapters should not share a name
+                    if adapters.len() > 1 {
+                        eprintln!("{} adapters match, select one to rename.", adapters.len());
+                        exit_code = ExitCode::FAILURE;
+                    } else {
+                        let bt_man = bluetooth_manager
+                            .lock()
+                            .expect("Mutex should not be poisoned.");
+                        exit_code = set_adapters(
+                            adapters,
+                            json_output,
+                            &format!("renamed to {alias}"),
+                            |adapter| {
+                                let success = bt_man.set_adapter_alias(adapter, alias);
+                                if success {
+                                    adapter.alias = alias.clone();
+                                }
+                                success
+                            },
+                        );
+                    }
+                }
+                // Listing is the default
+                _ if json_output => print_adapters_json(&adapters),
+                _ => {
+                    for adapter in &adapters {
+                        adapter.print_line();
+                    }
+                }
+            }
+        }
+        Some(("scan", sub_matches)) => {
+            let timeout = get_timeout(&sub_matches.get_one("timeout").copied(), 10);
+            let bt_man = bluetooth_manager
+                .lock()
+                .expect("Mutex should not be poisoned.");
+            // Devices are printed again if they announce a new name
+            let mut seen: HashMap<String, String> = HashMap::new();
+            bt_man.scan_live(&Duration::from_secs(timeout), &mut |device| {
+                if seen.get(&device.address) == Some(&device.name) {
+                    return true;
+                }
+                seen.insert(device.address.clone(), device.name.clone());
+                if json_output {
+                    println!("{}", device.to_json());
+                } else if quiet {
+                    println!("{}", device.address);
+                } else {
+                    let rssi = device.rssi.map_or("?".to_string(), |rssi| rssi.to_string());
+                    println!("{} {rssi:>4} dBm {}", device.address, device.name);
+                }
+                true
+            });
+        }
+        Some(("monitor", sub_matches)) => {
+            let low_battery = sub_matches.get_one::<u8>("low-battery").copied();
+            let notify = sub_matches.get_flag("notify");
+            // Last battery percentage of each device, to only warn once
+            // when it drops below the threshold
+            let mut batteries: HashMap<String, u8> = HashMap::new();
+            bluetooth_manager
+                .lock()
+                .expect("Mutex should not be poisoned.")
+                .monitor(&mut |event| {
+                    let Some(event) = event else {
+                        return true;
+                    };
+                    let mut events = vec![event.clone()];
+                    if let (DeviceEventKind::Battery(percentage), Some(threshold)) =
+                        (&event.kind, low_battery)
+                    {
+                        let previous = batteries.insert(event.address.clone(), *percentage);
+                        if *percentage < threshold
+                            && previous.is_none_or(|previous| previous >= threshold)
+                        {
+                            events.push(DeviceEvent {
+                                kind: DeviceEventKind::LowBattery(*percentage),
+                                ..event.clone()
+                            });
+                        }
+                    }
+                    for event in events {
+                        if json_output {
+                            println!("{}", event.to_json());
+                        } else {
+                            println!("{}", event.description());
+                        }
+                        if notify {
+                            notify_event(&event);
+                        }
+                        if let Some(hook) = config.hook(event.kind_name()) {
+                            run_hook(hook, &event);
+                        }
+                    }
+                    true
+                });
+        }
+        Some(("tui", _)) => exit_code = tui::run(&bluetooth_manager, &devicelist),
+        // The bluez backend handles the agent before getting here
+        Some(("agent", _)) => {
+            eprintln!("bt agent is only supported by the bluez backend.");
+            exit_code = ExitCode::from(EXIT_UNAVAILABLE);
+        }
+        Some(("status", sub_matches)) => {
+            devicelist.fill();
+            index.apply(&devicelist);
+            let connected = devicelist.filtered(|device| device.connected);
+            if sub_matches.get_flag("waybar") {
+                connected.print_waybar();
+            } else if sub_matches.get_flag("short") {
+                let glyph = |id| {
+                    sub_matches
+                        .get_one::<String>(id)
+                        .expect("glyph has a default value")
+                };
+                connected.print_short(
+                    glyph("connected-glyph"),
+                    glyph("disconnected-glyph"),
+                    glyph("battery-glyph"),
+                );
+            } else if json_output {
+                let adapters: Vec<_> = bluetooth_manager
+                    .lock()
+                    .expect("Mutex should not be poisoned.")
+                    .get_all_adapters()
+                    .iter()
+                    .map(Adapter::to_json)
+                    .collect();
+                println!(
+                    "{}",
+                    json!({"adapters": adapters, "devices": connected.to_json()})
+                );
+            } else {
+                for adapter in bluetooth_manager
                     .lock()
                     .expect("Mutex should not be poisoned.")
-                    .scan_mut(&Duration::from_secs(timeout))
-                    .update();
-                let count = devicelist
-                    .fill()
-                    .filtered_name(filter, get_behaviour(sub_matches))
-                    .pair_all();
-                println!("Paired {} devices.", count);
+                    .get_all_adapters()
+                {
+                    adapter.print_summary(print_in_color);
+                }
+                connected.print_summary();
             }
-            Some(("unpair", sub_matches)) => {
-                let filter = sub_matches
-                    .get_one::<String>("filter")
-                    .expect("filter is required");
-                let count = devicelist
-                    .fill()
-                    .filtered_name(filter, get_behaviour(sub_matches))
-                    .unpair_all();
-                println!("Unpaired {} devices.", count);
+        }
+        // Some(_) should be unreachable but just in case
+        None | Some(_) => {
+            let _ = command.print_help();
+        }
+    }
+    index.save();
+    exit_code
+}
+
+/// Installs or uninstalls the systemd user unit for bt monitor and prints
+/// how to (de)activate it.
+fn gen_service(matches: &ArgMatches) -> ExitCode {
+    if matches.get_flag("uninstall") {
+        return match service::uninstall() {
+            Ok(path) => {
+                println!("Removed {}.", path.display());
+                println!("Run systemctl --user daemon-reload to apply the change.");
+                ExitCode::SUCCESS
             }
-            // Some(_) should be unreachable but just in case
-            None | Some(_) => {
-                let _ = command.print_help();
+            Err(error) => {
+                eprintln!("Could not remove the service: {error}");
+                ExitCode::FAILURE
             }
+        };
+    }
+    let mut monitor_args = Vec::new();
+    if matches.get_flag("notify") {
+        monitor_args.push("--notify".to_string());
+    }
+    if let Some(percent) = matches.get_one::<u8>("low-battery") {
+        monitor_args.extend(["--low-battery".to_string(), percent.to_string()]);
+    }
+    // Global options apply to the monitor of the service as well
+    if let Some(adapter) = matches.get_one::<String>("select-adapter") {
+        monitor_args.extend(["--adapter".to_string(), adapter.clone()]);
+    }
+    if let Some(backend) = matches.get_one::<String>("backend") {
+        monitor_args.extend(["--backend".to_string(), backend.clone()]);
+    }
+    for flag in ["json", "debug-dbus"] {
+        if matches.get_flag(flag) {
+            monitor_args.push(format!("--{flag}"));
+        }
+    }
+    match service::install(&monitor_args) {
+        Ok(path) => {
+            println!("Wrote {}.", path.display());
+            println!("Run systemctl --user enable --now bt-monitor.service to start it.");
+            ExitCode::SUCCESS
         }
+        Err(error) => {
+            eprintln!("Could not write the service: {error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Prints the connection history, restricted to entries matching the filter
+/// and limit arguments if they are given.
+fn print_history(matches: &ArgMatches, json_output: bool) {
+    let mut entries = state::history();
+    if let Some(filters) = filter_args(matches) {
+        let behaviour = get_behaviour(matches);
+        entries.retain(|entry| {
+            filters.iter().any(|filter| {
+                entry.address.eq_ignore_ascii_case(filter) || behaviour.matches(&entry.name, filter)
+            })
+        });
+    }
+    if let Some(limit) = matches.get_one::<usize>("limit") {
+        entries.drain(..entries.len().saturating_sub(*limit));
+    }
+    if json_output {
+        let entries: Vec<_> = entries.iter().map(state::HistoryEntry::to_json).collect();
+        println!("{}", json!(entries));
+    } else {
+        for entry in &entries {
+            entry.print();
+        }
+    }
+}
+
+/// Returns when to print in color, "auto", "always" or "never". Command line
+/// arguments take precedence over NO_COLOR, which takes precedence over the
+/// config file.
+fn color_choice(matches: &ArgMatches) -> &str {
+    if let Some(color) = matches.get_one::<String>("color") {
+        return color;
     }
+    if matches.get_flag("no-color") || env::var_os("NO_COLOR").is_some_and(|var| !var.is_empty()) {
+        return "never";
+    }
+    config::get().color().unwrap_or("auto")
 }
 
+/// Returns the timeout given by param, BT_TIMEOUT or the config file, in
+/// that order, falling back to default.
 fn get_timeout(param: &Option<u64>, default: u64) -> u64 {
-    param.unwrap_or_else(|| match env::var("BT_TIMEOUT") {
-        Ok(var) => var.trim().parse().ok().unwrap_or(default),
-        Err(_) => default,
+    param.unwrap_or_else(|| {
+        env::var("BT_TIMEOUT")
+            .ok()
+            .and_then(|var| var.trim().parse().ok())
+            .or_else(|| config::get().timeout())
+            .unwrap_or(default)
     })
 }
 
+/// Returns whether an operation succeeded, also counting devices that
+/// already were in the requested state.
+fn succeeded(result: &Result<(), BtError>) -> bool {
+    match result {
+        Ok(()) => true,
+        Err(error) => error.is_already_done(),
+    }
+}
+
+/// Returns the name of the device with address in devices, colored like the
+/// device if colors are enabled, or the address if it is not in devices.
+fn device_name<M: BluetoothManager>(devices: &DeviceList<M>, address: &str) -> String {
+    devices
+        .iter()
+        .map(|device| device.lock().expect("Mutex should not be poisoned."))
+        .find(|device| device.address == address)
+        .map_or_else(|| address.to_string(), |device| device.get_name_colored())
+}
+
+/// Prints "Attempting to {action} NAME..." for each of devices if verbose is
+/// set, before starting an operation on them.
+fn print_attempts<M: BluetoothManager>(devices: &DeviceList<M>, verbose: bool, action: &str) {
+    if !verbose {
+        return;
+    }
+    for device in devices.iter() {
+        let device = device.lock().expect("Mutex should not be poisoned.");
+        println!("Attempting to {action} {}...", device.get_name_colored());
+    }
+}
+
+/// Reports the results of an operation on devices. If verbose is set, done
+/// is printed for each device it succeeded for and failed for the others,
+/// with {} replaced by the device name. Errors are printed to stderr.
+fn report_results<M: BluetoothManager>(
+    devices: &DeviceList<M>,
+    results: &DeviceResults,
+    verbose: bool,
+    done: &str,
+    failed: &str,
+) {
+    for (address, result) in results {
+        let name = device_name(devices, address);
+        let message = if succeeded(result) { done } else { failed };
+        if verbose && !message.is_empty() {
+            println!("{}", message.replacen("{}", &name, 1));
+        }
+        match result {
+            Err(error) if !error.is_already_done() => eprintln!("{name}: {error}"),
+            _ => (),
+        }
+    }
+}
+
+/// Prints the result of an operation on devices, as a JSON object including
+/// the resulting device states if json is set or as the addresses of the
+/// devices it succeeded for if quiet is set. Returns the exit code for the amount of devices
+/// the operation succeeded for. If no device matches the filter of matches,
+/// that is reported instead.
+fn print_result<M: BluetoothManager>(
+    json: bool,
+    quiet: bool,
+    action: &str,
+    results: &DeviceResults,
+    devices: &DeviceList<M>,
+    matches: &ArgMatches,
+) -> ExitCode {
+    let count = results
+        .iter()
+        .filter(|(_, result)| succeeded(result))
+        .count();
+    if json {
+        println!("{}", json!({"count": count, "devices": devices.to_json()}));
+    } else if quiet {
+        for (address, _) in results.iter().filter(|(_, result)| succeeded(result)) {
+            println!("{address}");
+        }
+    } else if !devices.is_empty() {
+        println!("{action} {count} devices.");
+    }
+    if devices.is_empty() {
+        no_match(matches)
+    } else if count == 0 {
+        ExitCode::FAILURE
+    } else if count < devices.len() {
+        ExitCode::from(EXIT_PARTIAL_FAILURE)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// Returns a list of only the best match of devices for the filter argument,
+/// preferring devices whose name equals the filter, then shorter names. Ties
+/// are broken by address, so the same device is chosen every time.
+fn first_device<M: BluetoothManager>(
+    devices: DeviceList<M>,
+    sub_matches: &ArgMatches,
+) -> DeviceList<M> {
+    let filters = filter_args(sub_matches).unwrap_or_default();
+    let first = devices
+        .iter()
+        .map(|device| {
+            let device = device.lock().expect("Mutex should not be poisoned.");
+            (
+                !filters.contains(&device.name.as_str()),
+                device.name.chars().count(),
+                device.address.clone(),
+            )
+        })
+        .min()
+        .map(|(_, _, address)| address);
+    devices.filtered(|device| Some(&device.address) == first.as_ref())
+}
+
+/// Lets the user choose which of several devices to use from a numbered menu
+/// if interactive is set, action naming what is done with the device. Returns
+/// all devices if there are less than two or --all is given and only the
+/// first device if --first is given. Returns None if nothing was chosen.
+fn pick_devices<M: BluetoothManager>(
+    devices: DeviceList<M>,
+    sub_matches: &ArgMatches,
+    action: &str,
+    interactive: bool,
+) -> Option<DeviceList<M>> {
+    if sub_matches.get_flag("first") {
+        return Some(first_device(devices, sub_matches));
+    }
+    if devices.len() <= 1 || sub_matches.get_flag("all") || !interactive {
+        return Some(devices);
+    }
+    eprintln!("{} devices match the filter:", devices.len());
+    let addresses: Vec<String> = devices
+        .iter()
+        .enumerate()
+        .map(|(i, device)| {
+            let device = device.lock().expect("Mutex should not be poisoned.");
+            eprintln!(
+                "  {}) {} ({})",
+                i + 1,
+                device.get_name_colored(),
+                device.address
+            );
+            device.address.clone()
+        })
+        .collect();
+    let question = format!("{action} which device? [1-{}, a for all]", devices.len());
+    loop {
+        let answer = utils::prompt(&question)?;
+        if answer.is_empty() {
+            eprintln!("No device selected.");
+            return None;
+        } else if answer == "a" || answer == "all" {
+            return Some(devices);
+        }
+        if let Some(address) = answer
+            .parse::<usize>()
+            .ok()
+            .and_then(|n| addresses.get(n.checked_sub(1)?))
+        {
+            return Some(devices.filtered(|device| &device.address == address));
+        }
+    }
+}
+
+/// Lists devices on stderr as the devices matching the filter.
+fn eprint_candidates<M: BluetoothManager>(devices: &DeviceList<M>) {
+    eprintln!("{} devices match the filter:", devices.len());
+    for device in devices.iter() {
+        let device = device.lock().expect("Mutex should not be poisoned.");
+        eprintln!("  {} {}", device.address, device.get_name_colored());
+    }
+}
+
+/// Checks that exactly one of the filtered devices matches if --exact-one is
+/// given. Returns the exit code to fail with otherwise, after listing the
+/// matches.
+fn check_exact_one<M: BluetoothManager>(
+    devices: &DeviceList<M>,
+    sub_matches: &ArgMatches,
+) -> Option<ExitCode> {
+    // A picked device is always unique, so there is nothing to check
+    if !has_flag(sub_matches, "exact-one") || has_flag(sub_matches, "pick") {
+        return None;
+    }
+    match devices.len() {
+        0 => Some(no_match(sub_matches)),
+        1 => None,
+        _ => {
+            eprint_candidates(&devices);
+            eprintln!("Expected exactly one device, refusing to continue.");
+            Some(ExitCode::from(EXIT_AMBIGUOUS))
+        }
+    }
+}
+
+/// Unpairs the untrusted paired devices of devicelist last connected or
+/// disconnected with bt longer than age ago, after listing them and asking for
+/// confirmation unless --yes is given. Returns the exit code.
+fn prune<M: BluetoothManager + Send>(
+    devicelist: &mut DeviceList<M>,
+    age: &Duration,
+    json_output: bool,
+    quiet: bool,
+    sub_matches: &ArgMatches,
+    index: &mut DeviceIndex,
+) -> ExitCode {
+    devicelist.fill();
+    index.apply(devicelist);
+    let last_used = state::last_used();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    // Without a history entry it is unknown when a device was used last, so
+    // it is kept rather than unpaired by surprise. Trusted devices may have
+    // reconnected on their own since, which the history does not show.
+    let devices = devicelist.filtered(|device| {
+        device.paired
+            && !device.connected
+            && !device.trusted
+            && last_used
+                .get(&device.address)
+                .is_some_and(|time| now.saturating_sub(*time) >= age.as_secs())
+    });
+    if devices.is_empty() {
+        if json_output {
+            println!("{}", json!({"count": 0, "devices": devices.to_json()}));
+        } else if !quiet {
+            println!("No devices to prune.");
+        }
+        return ExitCode::SUCCESS;
+    }
+    if !sub_matches.get_flag("yes") {
+        eprintln!(
+            "{} devices have not been used with bt for a while (according to bt history):",
+            devices.len()
+        );
+        for device in devices.iter() {
+            let device = device.lock().expect("Mutex should not be poisoned.");
+            eprintln!(
+                "  {} {} (last used with bt {})",
+                device.address,
+                device.get_name_colored(),
+                utils::format_utc(last_used[&device.address])
+            );
+        }
+        if !utils::confirm(&format!("Unpair all {} devices?", devices.len())) {
+            eprintln!("Refusing to prune devices, pass --yes to skip confirming.");
+            return ExitCode::FAILURE;
+        }
+    }
+    let results = devices.unpair_all();
+    report_results(
+        &devices,
+        &results,
+        !json_output && !quiet,
+        "{} unpaired.",
+        "Could not unpair {}.",
+    );
+    for device in devices.iter() {
+        let device = device.lock().expect("Mutex should not be poisoned.");
+        if !device.paired {
+            index.forget(&device.address);
+        }
+    }
+    print_result(
+        json_output,
+        quiet,
+        "Unpaired",
+        &results,
+        &devices,
+        sub_matches,
+    )
+}
+
+/// Asks whether action should be applied to all devices if there are several,
+/// listing them. Returns whether to go ahead, always returning true for a
+/// single device or if --yes is given.
+fn confirm_devices<M: BluetoothManager>(
+    devices: &DeviceList<M>,
+    action: &str,
+    sub_matches: &ArgMatches,
+) -> bool {
+    if devices.len() <= 1 || sub_matches.get_flag("yes") {
+        return true;
+    }
+    eprint_candidates(devices);
+    let confirmed = utils::confirm(&format!("{action} all {} devices?", devices.len()));
+    if !confirmed {
+        eprintln!("Refusing to {action} several devices, pass --yes to skip confirming.");
+    }
+    confirmed
+}
+
+/// Reports on stderr that no device matches the filters of matches and
+/// returns the exit code for it. Nothing is reported without a filter, as
+/// the reason (like canceling --pick) has been reported already.
+fn no_match(matches: &ArgMatches) -> ExitCode {
+    let types = matches.try_get_many::<String>("type").ok().flatten();
+    match (filter_args(matches), types) {
+        (Some(filters), _) if !filters.is_empty() => {
+            let filters: Vec<String> = filters.iter().map(|f| format!("'{f}'")).collect();
+            eprintln!("No devices match {}.", filters.join(", "));
+        }
+        (_, Some(types)) => {
+            let types: Vec<&str> = types.map(String::as_str).collect();
+            eprintln!("No devices of type {} found.", types.join(" or "));
+        }
+        _ => (),
+    }
+    ExitCode::from(EXIT_NO_MATCH)
+}
+
+/// Prints what the media player of each of devices is playing, as JSON if
+/// json is set. With follow, the status is checked every second and printed
+/// again whenever it changes, until interrupted. Returns the exit code,
+/// failing if any status could not be read.
+fn print_media_status<M: BluetoothManager>(
+    devices: &DeviceList<M>,
+    json: bool,
+    follow: bool,
+    sub_matches: &ArgMatches,
+) -> ExitCode {
+    const FOLLOW_INTERVAL: Duration = Duration::from_secs(1);
+    if devices.is_empty() {
+        return no_match(sub_matches);
+    }
+    // Last output of each device, so following only prints changes
+    let mut last: Vec<Option<Result<String, String>>> = vec![None; devices.len()];
+    loop {
+        let mut failed = false;
+        let mut statuses = Vec::new();
+        for (device, last) in devices.iter().zip(&mut last) {
+            let device = device.lock().expect("Mutex should not be poisoned.");
+            let output = match device.media_status() {
+                Ok(status) if json => {
+                    let status = json!({
+                        "address": device.address,
+                        "name": device.name,
+                        "media": status,
+                    });
+                    statuses.push(status.clone());
+                    Ok(status.to_string())
+                }
+                Ok(status) if devices.len() == 1 => Ok(status.summary()),
+                Ok(status) => Ok(format!(
+                    "{}: {}",
+                    device.get_name_colored(),
+                    status.summary()
+                )),
+                Err(error) => {
+                    failed = true;
+                    Err(format!("{}: {error}", device.get_name_colored()))
+                }
+            };
+            if last.as_ref() == Some(&output) {
+                continue;
+            }
+            match &output {
+                // Without follow, JSON is printed as a single array
+                Ok(_) if json && !follow => (),
+                Ok(line) => println!("{line}"),
+                Err(line) => eprintln!("{line}"),
+            }
+            *last = Some(output);
+        }
+        if !follow {
+            if json {
+                println!("{}", json!(statuses));
+            }
+            return if failed {
+                ExitCode::FAILURE
+            } else {
+                ExitCode::SUCCESS
+            };
+        }
+        let _ = stdout().flush();
+        thread::sleep(FOLLOW_INTERVAL);
+    }
+}
+
+/// Prints the volume of each of devices, as a JSON array of objects if json
+/// is set. Returns the exit code, failing if any volume could not be read.
+fn print_volumes<M: BluetoothManager>(
+    devices: &DeviceList<M>,
+    json: bool,
+    sub_matches: &ArgMatches,
+) -> ExitCode {
+    if devices.is_empty() {
+        return no_match(sub_matches);
+    }
+    let mut failed = false;
+    let mut volumes = Vec::new();
+    for device in devices.iter() {
+        let device = device.lock().expect("Mutex should not be poisoned.");
+        match device.volume() {
+            Ok(volume) if json => volumes.push(json!({
+                "address": device.address,
+                "name": device.name,
+                "volume": volume,
+            })),
+            Ok(volume) if devices.len() == 1 => println!("{volume}%"),
+            Ok(volume) => println!("{}: {volume}%", device.get_name_colored()),
+            Err(error) => {
+                eprintln!("{}: {error}", device.get_name_colored());
+                failed = true;
+            }
+        }
+    }
+    if json {
+        println!("{}", json!(volumes));
+    }
+    if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// Prints the amount of devices. Returns the no match exit code if there are
+/// none.
+fn print_count<M: BluetoothManager>(devices: &DeviceList<M>) -> ExitCode {
+    println!("{}", devices.len());
+    if devices.is_empty() {
+        ExitCode::from(EXIT_NO_MATCH)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// Returns the non-empty lines of stdin, read when first called.
+fn stdin_filters() -> &'static [String] {
+    STDIN_FILTERS.get_or_init(|| {
+        io::stdin()
+            .lines()
+            .map_while(Result::ok)
+            .map(|line| line.trim().to_string())
+            .filter(|line| !line.is_empty())
+            .collect()
+    })
+}
+
+/// Returns the filter arguments, with the filter "-" replaced by the lines of
+/// stdin, or None if no filter is given.
+fn filter_args(matches: &ArgMatches) -> Option<Vec<&str>> {
+    let filters = matches.get_many::<String>("filter")?;
+    let mut args = Vec::new();
+    for filter in filters {
+        if filter == "-" {
+            args.extend(stdin_filters().iter().map(String::as_str));
+        } else {
+            args.push(filter.as_str());
+        }
+    }
+    Some(args)
+}
+
+/// Returns the devices filtered for the --exact-one check if there are any,
+/// otherwise the devices selected by filtered_devices.
+fn command_devices<M: BluetoothManager>(
+    prefiltered: &mut Option<DeviceList<M>>,
+    devicelist: &mut DeviceList<M>,
+    matches: &ArgMatches,
+    index: &mut DeviceIndex,
+) -> DeviceList<M> {
+    prefiltered
+        .take()
+        .unwrap_or_else(|| filtered_devices(devicelist, matches, index))
+}
+
+/// Fills devicelist and returns the devices selected by the filter argument.
+/// A filter consisting of a persistent device index selects that device,
+/// otherwise the filter is matched against device names. Filters read from
+/// stdin also select the device with that address.
+fn filtered_devices<M: BluetoothManager>(
+    devicelist: &mut DeviceList<M>,
+    matches: &ArgMatches,
+    index: &mut DeviceIndex,
+) -> DeviceList<M> {
+    devicelist.fill();
+    index.apply(devicelist);
+    let pick = has_flag(matches, "pick");
+    // Only connect has an optional filter, defaulting to the last device
+    let Some(filters) = filter_args(matches) else {
+        // Without a filter, --type and --pick choose from all devices
+        if pick || matches!(matches.try_get_many::<String>("type"), Ok(Some(_))) {
+            let devices = narrowed(devicelist.filtered(|_| true), matches);
+            return if pick { pick_fuzzy(devices) } else { devices };
+        }
+        let last = state::last_connected();
+        if last.is_none() {
+            eprintln!("No device given and no device was connected before.");
+        }
+        return devicelist.filtered(|device| Some(&device.address) == last.as_ref());
+    };
+    if has_flag(matches, "filter-fields") {
+        let queries: Result<Vec<FieldQuery>, String> =
+            filters.into_iter().map(FieldQuery::parse).collect();
+        let devices = match queries {
+            Ok(queries) => devicelist.filtered_fields(&queries),
+            Err(error) => {
+                eprintln!("{error}.");
+                devicelist.filtered(|_| false)
+            }
+        };
+        let devices = narrowed(devices, matches);
+        return if pick { pick_fuzzy(devices) } else { devices };
+    }
+    // Numbers select devices by index, all other filters match names, or
+    // addresses with --address
+    let by_address = has_flag(matches, "address");
+    let (addresses, names): (Vec<_>, Vec<_>) = filters
+        .into_iter()
+        .map(|filter| {
+            let index_address = filter.parse().ok().and_then(|n| index.address(n));
+            (index_address.filter(|_| !by_address), filter)
+        })
+        .partition(|(address, _)| address.is_some());
+    let names: Vec<&str> = names.into_iter().map(|(_, name)| name).collect();
+    let mut devices = if by_address {
+        devicelist.filtered_address(&names, get_behaviour(matches))
+    } else {
+        devicelist.filtered_name(&names, get_behaviour(matches))
+    };
+    devices.extend_unique(devicelist.filtered(|device| {
+        addresses
+            .iter()
+            .any(|(address, _)| *address == Some(device.address.as_str()))
+    }));
+    // Lines piped in may be addresses, e.g. printed by list -q
+    if has_stdin_filter(matches) {
+        devices.extend_unique(devicelist.filtered(|device| {
+            stdin_filters()
+                .iter()
+                .any(|line| line.eq_ignore_ascii_case(&device.address))
+        }));
+    }
+    let devices = narrowed(devices, matches);
+    if pick {
+        pick_fuzzy(devices)
+    } else {
+        devices
+    }
+}
+
+/// Returns devices of one of the types given by the type arguments, without
+/// those whose name or address matches one of the exclude arguments.
+fn narrowed<M: BluetoothManager>(devices: DeviceList<M>, matches: &ArgMatches) -> DeviceList<M> {
+    let devices = match matches.try_get_many::<String>("type") {
+        Ok(Some(types)) => devices.filtered_type(&types.collect::<Vec<_>>()),
+        _ => devices,
+    };
+    let Ok(Some(patterns)) = matches.try_get_many::<String>("exclude") else {
+        return devices;
+    };
+    let patterns: Vec<&String> = patterns.collect();
+    let behaviour = get_behaviour(matches);
+    devices.filtered(|device| {
+        !patterns.iter().any(|pattern| {
+            device.address.eq_ignore_ascii_case(pattern)
+                || device.any_name(|name| behaviour.matches(name, pattern))
+        })
+    })
+}
+
+/// Returns whether filters are read from stdin.
+fn has_stdin_filter(matches: &ArgMatches) -> bool {
+    matches
+        .get_many::<String>("filter")
+        .is_some_and(|mut filters| filters.any(|filter| filter == "-"))
+}
+
+/// Lets the user choose one of devices with the fuzzy finder. Returns a list
+/// of only the chosen device, or an empty list if none was chosen.
+fn pick_fuzzy<M: BluetoothManager>(devices: DeviceList<M>) -> DeviceList<M> {
+    let (labels, addresses): (Vec<String>, Vec<String>) = devices
+        .iter()
+        .map(|device| {
+            let device = device.lock().expect("Mutex should not be poisoned.");
+            (
+                format!("{}  {}", device.name, device.address),
+                device.address.clone(),
+            )
+        })
+        .unzip();
+    let chosen = picker::pick(&labels).map(|i| &addresses[i]);
+    if chosen.is_none() {
+        eprintln!("No device selected.");
+    }
+    devices.filtered(|device| Some(&device.address) == chosen)
+}
+
+/// Returns whether each device in devices is connected.
+fn connection_states<M: BluetoothManager>(devices: &DeviceList<M>) -> Vec<bool> {
+    devices
+        .iter()
+        .map(|device| {
+            device
+                .lock()
+                .expect("Mutex should not be poisoned.")
+                .connected
+        })
+        .collect()
+}
+
+/// Records devices whose connection state differs from connected_before, as
+/// returned by connection_states, in the history.
+fn record_history<M: BluetoothManager>(devices: &DeviceList<M>, connected_before: &[bool]) {
+    for (device, was_connected) in devices.iter().zip(connected_before) {
+        let device = device.lock().expect("Mutex should not be poisoned.");
+        if device.connected != *was_connected {
+            let event = if device.connected {
+                "connected"
+            } else {
+                "disconnected"
+            };
+            state::record(event, &device.address, &device.name);
+        }
+    }
+}
+
+/// Fills devicelist and returns the devices selected by the filters of the
+/// list command, sorted as requested.
+fn listed_devices<M: BluetoothManager>(
+    devicelist: &mut DeviceList<M>,
+    matches: &ArgMatches,
+    index: &mut DeviceIndex,
+) -> DeviceList<M> {
+    devicelist.fill();
+    index.apply(devicelist);
+    let mut devices = if matches.get_flag("merge") {
+        devicelist.merged()
+    } else {
+        devicelist.filtered(|_| true)
+    };
+    // State filters are combined, devices must match all of them
+    let state_flag = |state| matches.get_flag(state);
+    devices = devices.filtered(|device| {
+        (!state_flag("connected") || device.connected)
+            && (!state_flag("paired") || device.paired)
+            && (!state_flag("unpaired") || !device.paired)
+            && (!state_flag("trusted") || device.trusted)
+            && (!state_flag("blocked") || device.blocked)
+    });
+    devices = narrowed(devices, matches);
+    if let Some(field) = matches.get_one::<String>("sort") {
+        devices.sort(field, matches.get_flag("reverse"));
+    }
+    devices.set_show_index(matches.get_flag("index"));
+    let icons = matches
+        .get_one::<String>("icons")
+        .map(String::as_str)
+        .or_else(|| config::get().icons());
+    devices.set_icons(icons.and_then(IconStyle::from_name));
+    devices.set_print_header(matches.get_flag("header"));
+    devices
+}
+
+/// Calls render every interval after clearing the screen. Returns once Ctrl+C
+/// is pressed, which has to be deferred by an interrupt guard of the caller.
+fn watch<F: FnMut()>(interval: &Duration, mut render: F) {
+    const POLL_INTERVAL: Duration = Duration::from_millis(250);
+    while !interrupt::interrupted() {
+        // The frame is wrapped in a synchronized update so terminals
+        // supporting it swap it in at once instead of flickering
+        print!("\x1b[?2026h\x1b[H\x1b[2J");
+        render();
+        print!("\x1b[?2026l");
+        let _ = stdout().flush();
+        let start = Instant::now();
+        while start.elapsed() < *interval && !interrupt::interrupted() {
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+/// Runs scan while showing a hint that bt is scanning if hint is set. The hint
+/// is written to stderr to keep it out of output like --json.
+fn with_scan_hint<T>(hint: bool, scan: impl FnOnce() -> T) -> T {
+    if hint {
+        eprint!("\x1b[2;37mScanning for devices...{ANSI_RESET}");
+        let _ = io::stderr().flush();
+    }
+    let result = scan();
+    if hint {
+        eprint!("\x1b[1K\r");
+    }
+    result
+}
+
+/// Scans for up to timeout seconds, stopping as soon as each of the filter
+/// arguments matches a discovered device, and updates the manager afterwards.
+/// Shows a hint while scanning if hint is set.
+fn scan_for_filter<M: BluetoothManager>(
+    bt_man: &mut M,
+    matches: &ArgMatches,
+    index: &DeviceIndex,
+    timeout: u64,
+    hint: bool,
+) {
+    let mut unmatched = filter_args(matches).expect("filter is required");
+    let behaviour = get_behaviour(matches);
+    let by_address = has_flag(matches, "address");
+    with_scan_hint(hint, || {
+        bt_man.scan_live(&Duration::from_secs(timeout), &mut |device| {
+            unmatched.retain(|filter| {
+                let index_address = filter.parse().ok().and_then(|n| index.address(n));
+                !match index_address.filter(|_| !by_address) {
+                    Some(address) => device.address == address,
+                    None if by_address => behaviour.matches_address(&device.address, filter),
+                    None => {
+                        behaviour.matches(&device.name, filter)
+                            || device.address.eq_ignore_ascii_case(filter)
+                    }
+                }
+            });
+            // Keep scanning while some filter has not matched a device
+            !unmatched.is_empty()
+        });
+    });
+    bt_man.update();
+}
+
+/// Returns the adapters whose name or address matches the adapter argument
+/// or all adapters if it is not given. Prints an error if none matches.
+fn filtered_adapters(adapters: Vec<Adapter>, matches: &ArgMatches) -> Vec<Adapter> {
+    let Some(filter) = matches.get_one::<String>("adapter") else {
+        return adapters;
+    };
+    let adapters: Vec<Adapter> = adapters
+        .into_iter()
+        .filter(|adapter| adapter.matches(filter))
+        .collect();
+    if adapters.is_empty() {
+        eprintln!("No adapter matches {filter}.");
+    }
+    adapters
+}
+
+/// Calls set on each adapter, printing which adapters were changed with
+/// action (or a JSON object with the amount of changed adapters and their
+/// states). Returns a failure exit code if any adapter could not be changed.
+fn set_adapters<F>(mut adapters: Vec<Adapter>, json: bool, action: &str, mut set: F) -> ExitCode
+where
+    F: FnMut(&mut Adapter) -> bool,
+{
+    let mut exit_code = if adapters.is_empty() {
+        ExitCode::from(EXIT_NO_MATCH)
+    } else {
+        ExitCode::SUCCESS
+    };
+    let mut count = 0;
+    for adapter in &mut adapters {
+        if set(adapter) {
+            count += 1;
+            if !json {
+                println!("{} ({}) {action}.", adapter.name, adapter.address);
+            }
+        } else {
+            eprintln!("Could not set {} {action}.", adapter.name);
+            exit_code = ExitCode::FAILURE;
+        }
+    }
+    if json {
+        let adapters: Vec<_> = adapters.iter().map(Adapter::to_json).collect();
+        println!("{}", json!({"count": count, "adapters": adapters}));
+    }
+    exit_code
+}
+
+/// Returns whether the state argument of a command is "on".
+fn get_switch(matches: &ArgMatches) -> bool {
+    matches
+        .get_one::<String>("state")
+        .is_some_and(|state| state == "on")
+}
+
+/// Sends a desktop notification for connections, disconnections, completed
+/// pairings and low batteries, ignoring other events.
+fn notify_event(event: &DeviceEvent) {
+    let (summary, critical) = match event.kind {
+        DeviceEventKind::Connected => (format!("{} connected", event.name), false),
+        DeviceEventKind::Disconnected => (format!("{} disconnected", event.name), false),
+        DeviceEventKind::Paired => (format!("Paired with {}", event.name), false),
+        DeviceEventKind::LowBattery(_) => (format!("{} battery low", event.name), true),
+        _ => return,
+    };
+    let icon = match event.kind {
+        DeviceEventKind::LowBattery(_) => "battery-caution",
+        _ => event.icon.as_deref().unwrap_or("bluetooth"),
+    };
+    notifications::send(&summary, &event.description(), icon, critical);
+}
+
+/// Runs the hook command with sh in the background. The event and device are
+/// passed in the environment variables BT_EVENT, BT_ADDRESS, BT_NAME and
+/// BT_BATTERY (only for battery events).
+fn run_hook(hook: &str, event: &DeviceEvent) {
+    let mut command = process::Command::new("sh");
+    command
+        .args(["-c", hook])
+        .env("BT_EVENT", event.kind_name())
+        .env("BT_ADDRESS", &event.address)
+        .env("BT_NAME", &event.name);
+    if let DeviceEventKind::Battery(percentage) | DeviceEventKind::LowBattery(percentage) =
+        event.kind
+    {
+        command.env("BT_BATTERY", percentage.to_string());
+    }
+    match command.spawn() {
+        // Reap the hook once it exits without blocking further events
+        Ok(mut child) => {
+            thread::spawn(move || child.wait());
+        }
+        Err(error) => eprintln!("Could not run hook {hook}: {error}"),
+    }
+}
+
+/// Prints adapters as a JSON array
+fn print_adapters_json(adapters: &[Adapter]) {
+    let adapters: Vec<_> = adapters.iter().map(Adapter::to_json).collect();
+    println!("{}", json!(adapters));
+}
+
+/// Returns the discovery filter given by the scan options of a command.
+/// Options the command does not have are left unset.
+fn discovery_filter(matches: &ArgMatches) -> DiscoveryFilter {
+    DiscoveryFilter {
+        transport: matches
+            .try_get_one::<String>("transport")
+            .ok()
+            .flatten()
+            .and_then(|name| Transport::from_name(name)),
+        rssi: matches.try_get_one("min-rssi").ok().flatten().copied(),
+        pathloss: matches.try_get_one("pathloss").ok().flatten().copied(),
+        uuids: matches
+            .try_get_many::<String>("uuid")
+            .ok()
+            .flatten()
+            .map(|uuids| uuids.cloned().collect())
+            .unwrap_or_default(),
+    }
+}
+
+/// Returns whether the flag with id is set, also returning false if matches
+/// has no argument with id.
+fn has_flag(matches: &ArgMatches, id: &str) -> bool {
+    matches
+        .try_get_one::<bool>(id)
+        .is_ok_and(|flag| flag == Some(&true))
+}
+
 fn get_behaviour(matches: &ArgMatches) -> FilterBehaviour {
-    let partial = *matches.get_one::<bool>("partial").unwrap_or(&true)
-        || !matches.get_one::<bool>("no-partial").unwrap_or(&false);
-    let regex = *matches.get_one::<bool>("regex").unwrap_or(&false)
-        && !matches.get_one::<bool>("no-regex").unwrap_or(&true);
+    // Flags override the defaults from the config file
+    if has_flag(matches, "fuzzy") {
+        return FilterBehaviour::Fuzzy;
+    }
+    let config = config::get();
+    // Flags may also be set by default, like no-partial for unpair
+    let partial = has_flag(matches, "partial")
+        || (!has_flag(matches, "no-partial") && config.partial().unwrap_or(true));
+    let regex = !has_flag(matches, "no-regex")
+        && (has_flag(matches, "regex") || config.regex().unwrap_or(false));
     if partial {
         if regex {
             FilterBehaviour::ContainsRegex
@@ -0,0 +1,40 @@
+// vim: cc=81
+use dbus::{
+    arg::{PropMap, Variant},
+    blocking::Connection,
+};
+use std::time::Duration;
+
+const NOTIFICATIONS_DBUS: &str = "org.freedesktop.Notifications";
+const NOTIFICATIONS_PATH: &str = "/org/freedesktop/Notifications";
+const NOTIFY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Sends a desktop notification to the notification daemon on the session
+/// bus, with critical urgency if critical is true. Returns whether the
+/// notification was sent.
+pub fn send(summary: &str, body: &str, icon: &str, critical: bool) -> bool {
+    let Ok(connection) = Connection::new_session() else {
+        return false;
+    };
+    let mut hints = PropMap::new();
+    // Urgency levels of the specification are 0 (low), 1 (normal), 2 (critical)
+    let urgency: u8 = if critical { 2 } else { 1 };
+    hints.insert("urgency".into(), Variant(Box::new(urgency)));
+    let result: Result<(u32,), dbus::Error> = connection
+        .with_proxy(NOTIFICATIONS_DBUS, NOTIFICATIONS_PATH, NOTIFY_TIMEOUT)
+        .method_call(
+            NOTIFICATIONS_DBUS,
+            "Notify",
+            (
+                "bt",
+                0u32,
+                icon,
+                summary,
+                body,
+                Vec::<&str>::new(),
+                hints,
+                -1i32,
+            ),
+        );
+    result.is_ok()
+}
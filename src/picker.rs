@@ -0,0 +1,105 @@
+// vim: cc=81
+use crate::{
+    term::ansi::ANSI_RESET,
+    text,
+    utils::{keys::*, RawMode},
+};
+use std::{
+    cmp::Reverse,
+    io::{self, Read, Write},
+};
+
+/// Most matches shown below the query at once
+const MAX_SHOWN: usize = 10;
+
+/// Returns the indices of the items matching query, best matches first.
+/// Items that match equally well keep their order.
+fn matches(items: &[String], query: &str) -> Vec<usize> {
+    let mut scored: Vec<(usize, u32)> = items
+        .iter()
+        .enumerate()
+        .filter_map(|(i, item)| Some((i, text::fuzzy_score(query, item)?)))
+        .collect();
+    scored.sort_by_key(|&(_, score)| Reverse(score));
+    scored.into_iter().map(|(i, _)| i).collect()
+}
+
+/// Moves back to the first of the drawn lines above the query line and clears
+/// everything below.
+fn clear(stderr: &mut impl Write, drawn: usize) {
+    let _ = write!(stderr, "\r");
+    if drawn > 0 {
+        let _ = write!(stderr, "\x1b[{drawn}A");
+    }
+    let _ = write!(stderr, "\x1b[J");
+}
+
+/// Draws the shown matches above the query line, the best match closest to
+/// it, on stderr. Returns the number of lines drawn above the query line.
+fn draw(
+    stderr: &mut impl Write,
+    items: &[String],
+    shown: &[usize],
+    selected: usize,
+    query: &str,
+    drawn: usize,
+) -> usize {
+    clear(stderr, drawn);
+    for (line, &i) in shown.iter().enumerate().rev() {
+        if line == selected {
+            let _ = writeln!(stderr, "\x1b[7m> {}{}", items[i], ANSI_RESET);
+        } else {
+            let _ = writeln!(stderr, "  {}", items[i]);
+        }
+    }
+    let _ = write!(stderr, "{}/{} > {query}", shown.len(), items.len());
+    let _ = stderr.flush();
+    shown.len()
+}
+
+/// Lets the user narrow down items by typing a fuzzy query and choose one of
+/// the matches with the arrow keys and enter. Returns the index of the chosen
+/// item, or None if picking was canceled with Escape or Ctrl+C or stdin is not
+/// a terminal.
+pub fn pick(items: &[String]) -> Option<usize> {
+    let _raw_mode = RawMode::enable()?;
+    let mut stderr = io::stderr().lock();
+    let mut stdin = io::stdin().lock();
+    let mut query = String::new();
+    let mut selected = 0;
+    let mut drawn = 0;
+    let mut buf = [0u8; 8];
+    let chosen = loop {
+        let found = matches(items, &query);
+        let shown = &found[..found.len().min(MAX_SHOWN)];
+        selected = selected.min(shown.len().saturating_sub(1));
+        drawn = draw(&mut stderr, items, shown, selected, &query, drawn);
+        let Ok(read) = stdin.read(&mut buf) else {
+            break None;
+        };
+        match &buf[..read] {
+            [] | [KEY_CTRL_C] | [KEY_ESCAPE] => break None,
+            [b'\r' | b'\n'] => break shown.get(selected).copied(),
+            // Up moves towards the worse matches drawn above
+            KEY_UP | [KEY_CTRL_P] => {
+                selected = (selected + 1).min(shown.len().saturating_sub(1));
+            }
+            KEY_DOWN | [KEY_CTRL_N] => selected = selected.saturating_sub(1),
+            [KEY_BACKSPACE | KEY_CTRL_H] => {
+                query.pop();
+            }
+            [KEY_CTRL_U] => query.clear(),
+            // Other escape sequences, e.g. of the left and right arrow keys
+            [KEY_ESCAPE, ..] => {}
+            bytes => {
+                if let Ok(text) = std::str::from_utf8(bytes) {
+                    query.extend(text.chars().filter(|c| !c.is_control()));
+                    selected = 0;
+                }
+            }
+        }
+    };
+    clear(&mut stderr, drawn);
+    let _ = stderr.flush();
+    chosen
+}
@@ -0,0 +1,65 @@
+// vim: cc=81
+use crate::utils;
+use std::{
+    env, fs,
+    io::{self, ErrorKind},
+    path::PathBuf,
+};
+
+const UNIT_NAME: &str = "bt-monitor.service";
+
+/// Returns the path of the systemd user unit for bt monitor.
+pub fn unit_path() -> io::Result<PathBuf> {
+    utils::config_home()
+        .map(|dir| dir.join("systemd").join("user").join(UNIT_NAME))
+        .ok_or_else(|| io::Error::new(ErrorKind::NotFound, "HOME is not set"))
+}
+
+/// Quotes arg as a word of an ExecStart command line, escaping the
+/// specifiers and variables systemd would expand otherwise.
+fn quote(arg: &str) -> String {
+    let escaped = arg.replace('%', "%%").replace('$', "$$");
+    let plain = !escaped.is_empty()
+        && escaped != ";"
+        && !escaped.contains(|c: char| c.is_whitespace() || matches!(c, '"' | '\'' | '\\'));
+    if plain {
+        escaped
+    } else {
+        format!("\"{}\"", escaped.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+/// Writes a systemd user unit running bt monitor with monitor_args and
+/// returns its path. An existing unit is overwritten.
+pub fn install(monitor_args: &[String]) -> io::Result<PathBuf> {
+    let path = unit_path()?;
+    let exe = env::current_exe()?;
+    let mut exec_start = format!("{} monitor", quote(&exe.to_string_lossy()));
+    for arg in monitor_args {
+        exec_start += &format!(" {}", quote(arg));
+    }
+    let unit = format!(
+        "[Unit]\n\
+        Description=Bluetooth device event monitor (bt)\n\
+        After=bluetooth.target\n\
+        \n\
+        [Service]\n\
+        ExecStart={exec_start}\n\
+        Restart=on-failure\n\
+        \n\
+        [Install]\n\
+        WantedBy=default.target\n"
+    );
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(&path, unit)?;
+    Ok(path)
+}
+
+/// Removes the systemd user unit written by install and returns its path.
+pub fn uninstall() -> io::Result<PathBuf> {
+    let path = unit_path()?;
+    fs::remove_file(&path)?;
+    Ok(path)
+}
@@ -0,0 +1,166 @@
+// vim: cc=81
+use crate::utils;
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    fs::{self, OpenOptions},
+    io::Write,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const HISTORY_FILE: &str = "history";
+const RECENT_FILE: &str = "recent";
+/// The history is shortened to this many entries when it grows beyond twice
+/// the amount, so it is not rewritten on every change
+const MAX_HISTORY_ENTRIES: usize = 1000;
+
+/// A connection or disconnection of a device done with bt
+pub struct HistoryEntry {
+    /// Seconds since the unix epoch
+    pub time: u64,
+    /// "connected" or "disconnected"
+    pub event: String,
+    pub address: String,
+    pub name: String,
+}
+
+impl HistoryEntry {
+    /// Parses a line of the form "[TIME] [EVENT] [ADDRESS] [NAME]".
+    fn parse(line: &str) -> Option<HistoryEntry> {
+        let mut iter = line.splitn(4, ' ');
+        Some(HistoryEntry {
+            time: iter.next()?.parse().ok()?,
+            event: iter.next()?.to_string(),
+            address: iter.next()?.to_string(),
+            name: iter.next().unwrap_or_default().to_string(),
+        })
+    }
+
+    fn to_line(&self) -> String {
+        // Line breaks in names would split the entry
+        let name = self.name.replace(['\r', '\n'], " ");
+        format!("{} {} {} {name}\n", self.time, self.event, self.address)
+    }
+
+    /// Prints the entry on one line with the time in UTC.
+    pub fn print(&self) {
+        println!(
+            "{} {} ({}) {}",
+            utils::format_utc(self.time),
+            self.name,
+            self.address,
+            self.event
+        );
+    }
+
+    /// Returns the entry as a JSON object
+    pub fn to_json(&self) -> Value {
+        json!({
+            "time": self.time,
+            "event": self.event,
+            "address": self.address,
+            "name": self.name,
+        })
+    }
+}
+
+/// Returns the recorded history, oldest entries first. A missing or
+/// unreadable history is empty.
+pub fn history() -> Vec<HistoryEntry> {
+    utils::state_dir()
+        .and_then(|dir| fs::read_to_string(dir.join(HISTORY_FILE)).ok())
+        .map(|content| content.lines().filter_map(HistoryEntry::parse).collect())
+        .unwrap_or_default()
+}
+
+/// Appends an event of the device with address and name to the history.
+pub fn record(event: &str, address: &str, name: &str) {
+    let Some(dir) = utils::state_dir() else {
+        return;
+    };
+    let path = dir.join(HISTORY_FILE);
+    let entry = HistoryEntry {
+        time: now(),
+        event: event.to_string(),
+        address: address.to_string(),
+        name: name.to_string(),
+    };
+    let _ = fs::create_dir_all(&dir);
+    let mut entries = history();
+    if entries.len() >= 2 * MAX_HISTORY_ENTRIES {
+        entries.drain(..=entries.len() - MAX_HISTORY_ENTRIES);
+        entries.push(entry);
+        let content: String = entries.iter().map(HistoryEntry::to_line).collect();
+        let _ = fs::write(path, content);
+    } else if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
+        let _ = file.write_all(entry.to_line().as_bytes());
+    }
+}
+
+/// Returns the current time in seconds since the unix epoch.
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+/// Returns when each device was last connected with bt connect, in seconds
+/// since the unix epoch by address. Unlike the history, this includes
+/// devices that already were connected.
+fn recent() -> HashMap<String, u64> {
+    let Some(content) =
+        utils::state_dir().and_then(|dir| fs::read_to_string(dir.join(RECENT_FILE)).ok())
+    else {
+        return HashMap::new();
+    };
+    // Each line has the form "[TIME] [ADDRESS]"
+    content
+        .lines()
+        .filter_map(|line| {
+            let (time, address) = line.split_once(' ')?;
+            Some((address.to_string(), time.parse().ok()?))
+        })
+        .collect()
+}
+
+/// Records that the device with address was connected with bt connect, even
+/// if it already was connected, so it becomes the most recently used device.
+/// Only the latest time of each device is kept.
+pub fn record_used(address: &str) {
+    let Some(dir) = utils::state_dir() else {
+        return;
+    };
+    let mut recent = recent();
+    recent.insert(address.to_string(), now());
+    let content: String = recent
+        .iter()
+        .map(|(address, time)| format!("{time} {address}\n"))
+        .collect();
+    let _ = fs::create_dir_all(&dir);
+    let _ = fs::write(dir.join(RECENT_FILE), content);
+}
+
+/// Returns the address of the device most recently connected with bt.
+pub fn last_connected() -> Option<String> {
+    history()
+        .into_iter()
+        .filter(|entry| entry.event == "connected")
+        .map(|entry| (entry.address, entry.time))
+        .chain(recent())
+        .max_by_key(|(_, time)| *time)
+        .map(|(address, _)| address)
+}
+
+/// Returns when each device was last connected or disconnected with bt, in
+/// seconds since the unix epoch by address.
+pub fn last_used() -> HashMap<String, u64> {
+    let mut last_used: HashMap<String, u64> = history()
+        .into_iter()
+        .map(|entry| (entry.address, entry.time))
+        .collect();
+    for (address, time) in recent() {
+        let last = last_used.entry(address).or_default();
+        *last = time.max(*last);
+    }
+    last_used
+}
@@ -0,0 +1,91 @@
+// vim: cc=81
+//! Approximate matching of device names against filters.
+
+/// Returns how well query matches text as a subsequence ignoring case, or
+/// None if it does not match. Matches of consecutive characters and at the
+/// start of words score higher.
+pub fn fuzzy_score(query: &str, text: &str) -> Option<u32> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut start = 0;
+    let mut last = None;
+    for query_char in query.to_lowercase().chars() {
+        let found = start + text[start..].iter().position(|&c| c == query_char)?;
+        score += 1;
+        if found > 0 && last == Some(found - 1) {
+            score += 4;
+        }
+        if found == 0 || !text[found - 1].is_alphanumeric() {
+            score += 2;
+        }
+        last = Some(found);
+        start = found + 1;
+    }
+    Some(score)
+}
+
+/// Returns the number of characters that have to be inserted, removed or
+/// replaced to turn a into b, ignoring case.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.to_lowercase().chars().collect();
+    // Distances of the prefix of a handled so far to all prefixes of b
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.to_lowercase().chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &b_char) in b.iter().enumerate() {
+            let replaced = diagonal + usize::from(a_char != b_char);
+            diagonal = row[j + 1];
+            row[j + 1] = replaced.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fuzzy_score_matches_subsequences() {
+        for (query, text, expected) in [
+            ("", "Mouse", Some(0)),
+            ("mouse", "Mouse", Some(23)),
+            ("MOUSE", "mouse", Some(23)),
+            ("hed", "Headphones", Some(9)),
+            ("ac", "abc", Some(4)),
+            ("ba", "abc", None),
+            ("mousse", "Mouse", None),
+            ("x", "", None),
+        ] {
+            assert_eq!(fuzzy_score(query, text), expected, "{query} in {text}");
+        }
+    }
+
+    #[test]
+    fn fuzzy_score_prefers_consecutive_and_word_starts() {
+        let score = |query| fuzzy_score(query, "Headphones").unwrap();
+        // p continues no match and is inside a word
+        assert!(score("hp") < fuzzy_score("hp", "HP Laptop").unwrap());
+        assert!(score("p") < fuzzy_score("p", "My Phone").unwrap());
+        assert!(score("head") > score("hdph"));
+    }
+
+    #[test]
+    fn edit_distance_counts_changes() {
+        for (a, b, expected) in [
+            ("", "", 0),
+            ("", "abc", 3),
+            ("abc", "", 3),
+            ("Mouse", "mouse", 0),
+            ("mouse", "moose", 1),
+            ("mouse", "mous", 1),
+            ("mouse", "mouses", 1),
+            ("keybaord", "keyboard", 2),
+            ("kitten", "sitting", 3),
+        ] {
+            assert_eq!(edit_distance(a, b), expected, "{a} to {b}");
+            assert_eq!(edit_distance(b, a), expected, "{b} to {a}");
+        }
+    }
+}
@@ -0,0 +1,248 @@
+// vim: cc=81
+use crate::bluetooth::{BluetoothManager, BtError, Device, DeviceList};
+use crate::{
+    term::{self, ansi::ANSI_RESET},
+    utils::{keys::*, RawMode},
+};
+use std::{
+    collections::HashSet,
+    io::{self, stdout, Read, Write},
+    process::ExitCode,
+    sync::{
+        mpsc::{self, Receiver, Sender, TryRecvError},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+/// How long pressing s scans for devices
+const SCAN_DURATION: Duration = Duration::from_secs(10);
+/// Lines taken by the header, column titles, status and key help
+const CHROME_LINES: usize = 4;
+const KEY_HELP: &str = "c connect  d disconnect  p pair  t trust  s scan  j/k move  q quit";
+
+/// Reads key presses from stdin on a separate thread, so they can be checked
+/// without blocking while monitoring device events. After each key, reading
+/// waits until the returned sender signals that the key was handled, so it
+/// does not take input meant for prompts while pairing.
+fn spawn_key_reader() -> (Receiver<Vec<u8>>, Sender<()>) {
+    let (sender, keys) = mpsc::channel();
+    let (handled, wait) = mpsc::channel();
+    thread::spawn(move || {
+        let mut buf = [0u8; 8];
+        while let Ok(read @ 1..) = io::stdin().read(&mut buf) {
+            if sender.send(buf[..read].to_vec()).is_err() || wait.recv().is_err() {
+                break;
+            }
+        }
+    });
+    (keys, handled)
+}
+
+/// Draws the device table with the device at selected highlighted, scrolled
+/// so it is visible, followed by the status line and key help.
+fn draw<M: BluetoothManager>(devices: &DeviceList<M>, selected: usize, status: &str) {
+    let lines = term::get_termsize().map_or(24, |size| usize::from(size.lines));
+    let rows = lines.saturating_sub(CHROME_LINES).max(1);
+    let offset = (selected + 1).saturating_sub(rows);
+    let mut out = stdout().lock();
+    // Synchronized update like watch, so the frame does not flicker
+    let _ = write!(out, "\x1b[?2026h\x1b[H\x1b[2J");
+    let _ = writeln!(out, "\x1b[1mbt tui - {} devices{ANSI_RESET}", devices.len());
+    let _ = writeln!(out, "  FLAGS  BAT  RSSI  ADDRESS            NAME");
+    for (row, device) in devices.iter().enumerate().skip(offset).take(rows) {
+        let device = device.lock().expect("Mutex should not be poisoned.");
+        let battery = device.battery.map_or("-".to_string(), |b| format!("{b}%"));
+        let rssi = device.rssi.map_or("-".to_string(), |rssi| rssi.to_string());
+        // The name comes last, as its color codes reset the highlighting
+        let (marker, highlight) = if row == selected {
+            ('>', "\x1b[7m")
+        } else {
+            (' ', "")
+        };
+        let _ = writeln!(
+            out,
+            "{highlight}{marker} {}  {battery:>4} {rssi:>5}  {}  {}{ANSI_RESET}",
+            device.flags(),
+            device.address,
+            device.get_name_colored()
+        );
+    }
+    let _ = write!(
+        out,
+        "\x1b[{};1H{status}\n\x1b[2m{KEY_HELP}{ANSI_RESET}",
+        lines.saturating_sub(1)
+    );
+    let _ = write!(out, "\x1b[?2026l");
+    let _ = out.flush();
+}
+
+/// Runs action on the selected device, showing doing (like "Connecting")
+/// meanwhile. Returns a status line describing the result, using done (like
+/// "connected") on success.
+fn act<M: BluetoothManager>(
+    devices: &DeviceList<M>,
+    selected: usize,
+    doing: &str,
+    done: &str,
+    action: impl FnOnce(&mut Device<M>) -> Result<(), BtError>,
+) -> String {
+    let Some(device) = devices.iter().nth(selected) else {
+        return "No device selected.".to_string();
+    };
+    // Drawing locks the device, so it cannot stay locked meanwhile
+    let name = device
+        .lock()
+        .expect("Mutex should not be poisoned.")
+        .name
+        .clone();
+    draw(devices, selected, &format!("{doing} {name}..."));
+    let mut device = device.lock().expect("Mutex should not be poisoned.");
+    match action(&mut device) {
+        Ok(()) => format!("{} {done}.", device.name),
+        Err(error) => format!("{}: {error}", device.name),
+    }
+}
+
+/// Pairs with the selected device outside of raw mode and the alternate
+/// screen, so pin codes and passkeys can be entered and compared on the
+/// terminal. Returns a status line describing the result.
+fn pair<M: BluetoothManager>(
+    devices: &DeviceList<M>,
+    selected: usize,
+    raw_mode: &mut Option<RawMode>,
+) -> String {
+    let Some(device) = devices.iter().nth(selected) else {
+        return "No device selected.".to_string();
+    };
+    let mut device = device.lock().expect("Mutex should not be poisoned.");
+    *raw_mode = None;
+    print!("\x1b[?25h\x1b[?1049l");
+    println!("Pairing with {}...", device.get_name_colored());
+    let result = device.pair();
+    *raw_mode = RawMode::enable();
+    print!("\x1b[?1049h\x1b[?25l");
+    match result {
+        Ok(()) => format!("{} paired.", device.name),
+        Err(error) => format!("{}: {error}", device.name),
+    }
+}
+
+/// Shows a live table of the devices of template (whose settings are used)
+/// until q is pressed. Device events are followed with the monitor of the
+/// bluetooth manager, which updates the table and shows the latest event.
+pub fn run<M: BluetoothManager>(
+    bluetooth_manager: &Arc<Mutex<M>>,
+    template: &DeviceList<M>,
+) -> ExitCode {
+    let mut raw_mode = RawMode::enable();
+    if raw_mode.is_none() {
+        eprintln!("bt tui needs a terminal.");
+        return ExitCode::FAILURE;
+    }
+    let (keys, key_handled) = spawn_key_reader();
+    // Switch to the alternate screen and hide the cursor
+    print!("\x1b[?1049h\x1b[?25l");
+    let mut selected = 0;
+    let mut status = String::from("Watching for device events.");
+    loop {
+        bluetooth_manager
+            .lock()
+            .expect("Mutex should not be poisoned.")
+            .update();
+        // Filtering out all devices gives an empty list with the same
+        // settings
+        let mut devices = template.filtered(|_| false);
+        devices.fill();
+        selected = selected.min(devices.len().saturating_sub(1));
+        draw(&devices, selected, &status);
+
+        // Monitor until an event arrives or a key is pressed
+        let mut key = None;
+        bluetooth_manager
+            .lock()
+            .expect("Mutex should not be poisoned.")
+            .monitor(&mut |event| match event {
+                Some(event) => {
+                    status = event.description();
+                    false
+                }
+                None => match keys.try_recv() {
+                    Ok(pressed) => {
+                        key = Some(pressed);
+                        false
+                    }
+                    Err(TryRecvError::Empty) => true,
+                    Err(TryRecvError::Disconnected) => false,
+                },
+            });
+        // The monitor may also end without checking for a key
+        let key = match key.ok_or_else(|| keys.try_recv()) {
+            Ok(key) | Err(Ok(key)) => key,
+            Err(Err(TryRecvError::Empty)) => continue,
+            Err(Err(TryRecvError::Disconnected)) => break,
+        };
+        match key.as_slice() {
+            b"q" | [KEY_ESCAPE] | [KEY_CTRL_C] => break,
+            b"j" | KEY_DOWN => selected = (selected + 1).min(devices.len().saturating_sub(1)),
+            b"k" | KEY_UP => selected = selected.saturating_sub(1),
+            b"c" => {
+                status = act(
+                    &devices,
+                    selected,
+                    "Connecting",
+                    "connected",
+                    Device::connect,
+                );
+            }
+            b"d" => {
+                status = act(
+                    &devices,
+                    selected,
+                    "Disconnecting",
+                    "disconnected",
+                    Device::disconnect,
+                );
+            }
+            b"p" => status = pair(&devices, selected, &mut raw_mode),
+            b"t" => {
+                let trusted = devices.iter().nth(selected).is_some_and(|device| {
+                    device
+                        .lock()
+                        .expect("Mutex should not be poisoned.")
+                        .trusted
+                });
+                status = if trusted {
+                    act(
+                        &devices,
+                        selected,
+                        "Untrusting",
+                        "untrusted",
+                        Device::untrust,
+                    )
+                } else {
+                    act(&devices, selected, "Trusting", "trusted", Device::trust)
+                };
+            }
+            b"s" => {
+                draw(&devices, selected, "Scanning for 10 seconds...");
+                let mut seen = HashSet::new();
+                bluetooth_manager
+                    .lock()
+                    .expect("Mutex should not be poisoned.")
+                    .scan_live(&SCAN_DURATION, &mut |device| {
+                        seen.insert(device.address.clone());
+                        true
+                    });
+                status = format!("Scan finished, {} devices seen.", seen.len());
+            }
+            _ => (),
+        }
+        let _ = key_handled.send(());
+    }
+    // Restore the cursor and the previous screen contents
+    print!("\x1b[?25h\x1b[?1049l");
+    let _ = stdout().flush();
+    ExitCode::SUCCESS
+}
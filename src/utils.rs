@@ -1,6 +1,11 @@
 // vim: cc=81
-use std::process::{Command, Stdio};
-pub type DimType = u16;
+use std::{
+    env,
+    io::{self, IsTerminal, Write},
+    path::PathBuf,
+    process::{Command, Stdio},
+    time::Duration,
+};
 
 /*
 #[macro_export]
@@ -21,48 +26,241 @@ macro_rules! dict {
     };
 }
 
-#[derive(Debug)]
-pub struct TermSize {
-    pub lines: DimType,
-    pub cols: DimType,
+/// Asks question on stderr and reads the answer from stdin. Returns the
+/// trimmed answer, or None if stdin is closed or not a terminal.
+pub fn prompt(question: &str) -> Option<String> {
+    if !io::stdin().is_terminal() {
+        return None;
+    }
+    eprint!("{question} ");
+    let _ = io::stderr().flush();
+    let mut answer = String::new();
+    match io::stdin().read_line(&mut answer) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(answer.trim().to_string()),
+    }
 }
 
-/// Returns the size of the terminal, or None if the size cannot be determined.
-pub fn get_termsize() -> Option<TermSize> {
-    // Call "stty size" to get output in form of "[LINES] [COLUMNS]\n"
-    let output = match Command::new("stty")
-        .arg("size")
-        .stdin(Stdio::inherit())
-        .output()
-    {
-        Ok(out) => String::from_utf8(out.stdout).ok(),
-        Err(_) => None,
-    }?;
-    let mut iter = output.split(char::is_whitespace);
-    // Parse lines and cols from output
-    let lines = iter.next()?.parse().ok()?;
-    let cols = iter.next()?.parse().ok()?;
-    Some(TermSize { lines, cols })
+/// Asks a yes or no question like prompt. Returns whether the answer is yes,
+/// or false without asking if stdin is not a terminal.
+pub fn confirm(question: &str) -> bool {
+    prompt(&format!("{question} [y/N]"))
+        .is_some_and(|answer| matches!(answer.to_lowercase().as_str(), "y" | "yes"))
 }
 
-pub mod ansi {
-    pub const ANSI_RESET: &str = "\x1b[0m";
-    // pub const ANSI_BLACK: &str = "\x1b[30m";
-    pub const ANSI_RED: &str = "\x1b[31m";
-    pub const ANSI_GREEN: &str = "\x1b[32m";
-    pub const ANSI_YELLOW: &str = "\x1b[33m";
-    // pub const ANSI_BLUE: &str = "\x1b[34m";
-    // pub const ANSI_MAGENTA: &str = "\x1b[35m";
-    // pub const ANSI_CYAN: &str = "\x1b[36m";
-    // pub const ANSI_WHITE: &str = "\x1b[37m";
-    // pub const ANSI_DEFAULT: &str = "\x1b[39m";
-    // pub const ANSI_BLACK_BG: &str = "\x1b[40m";
-    // pub const ANSI_RED_BG: &str = "\x1b[41m";
-    // pub const ANSI_GREEN_BG: &str = "\x1b[42m";
-    // pub const ANSI_YELLOW_BG: &str = "\x1b[43m";
-    // pub const ANSI_BLUE_BG: &str = "\x1b[44m";
-    // pub const ANSI_MAGENTA_BG: &str = "\x1b[45m";
-    // pub const ANSI_CYAN_BG: &str = "\x1b[46m";
-    // pub const ANSI_WHITE_BG: &str = "\x1b[47m";
-    // pub const ANSI_DEFAULT_BG: &str = "\x1b[49m";
+/// Terminal mode reading single key presses without echoing them, set up
+/// with stty like term::get_termsize. The previous mode is restored on drop.
+pub struct RawMode {
+    saved: String,
+}
+
+impl RawMode {
+    /// Switches the terminal on stdin to raw mode. Returns None if stdin is
+    /// not a terminal or stty fails.
+    pub fn enable() -> Option<RawMode> {
+        if !io::stdin().is_terminal() {
+            return None;
+        }
+        let output = Command::new("stty")
+            .arg("-g")
+            .stdin(Stdio::inherit())
+            .output()
+            .ok()?;
+        let saved = String::from_utf8(output.stdout).ok()?.trim().to_string();
+        // Ctrl+C is read as a key instead of exiting without restoring
+        let status = Command::new("stty")
+            .args(["-icanon", "-echo", "-isig", "min", "1"])
+            .stdin(Stdio::inherit())
+            .status()
+            .ok()?;
+        status.success().then_some(RawMode { saved })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = Command::new("stty")
+            .arg(&self.saved)
+            .stdin(Stdio::inherit())
+            .status();
+    }
+}
+
+/// Bytes read for keys while the terminal is in raw mode
+pub mod keys {
+    pub const KEY_CTRL_C: u8 = 0x03;
+    pub const KEY_CTRL_H: u8 = 0x08;
+    pub const KEY_CTRL_N: u8 = 0x0e;
+    pub const KEY_CTRL_P: u8 = 0x10;
+    pub const KEY_CTRL_U: u8 = 0x15;
+    pub const KEY_ESCAPE: u8 = 0x1b;
+    pub const KEY_BACKSPACE: u8 = 0x7f;
+    pub const KEY_UP: &[u8] = b"\x1b[A";
+    pub const KEY_DOWN: &[u8] = b"\x1b[B";
+}
+
+/// Returns the directory bt reads its config file from, following the XDG
+/// base directory specification ($XDG_CONFIG_HOME/bt, falling back to
+/// ~/.config/bt).
+pub fn config_dir() -> Option<PathBuf> {
+    Some(config_home()?.join("bt"))
+}
+
+/// Returns the base directory for user config files ($XDG_CONFIG_HOME,
+/// falling back to ~/.config).
+pub fn config_home() -> Option<PathBuf> {
+    match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => Some(PathBuf::from(dir)),
+        _ => Some(PathBuf::from(env::var_os("HOME")?).join(".config")),
+    }
+}
+
+/// Returns the directory bt keeps state like recently used devices in,
+/// following the XDG base directory specification ($XDG_STATE_HOME/bt,
+/// falling back to ~/.local/state/bt).
+pub fn state_dir() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_STATE_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?)
+            .join(".local")
+            .join("state"),
+    };
+    Some(base.join("bt"))
+}
+
+/// Formats seconds since the unix epoch as UTC date and time like
+/// "2024-05-17 13:37:00 UTC".
+pub fn format_utc(time: u64) -> String {
+    let (days, seconds) = (time / 86400, time % 86400);
+    // Civil from days algorithm by Howard Hinnant, shifted to start the year
+    // in March so leap days are at the end of the year
+    let z = days + 719468;
+    let era = z / 146097;
+    let day_of_era = z % 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    format!(
+        "{year}-{month:02}-{day:02} {:02}:{:02}:{:02} UTC",
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
+/// Parses a duration like "30d", a number followed by one of the units s, m,
+/// h, d or w. The unit is required, as a bare number could mean seconds as
+/// well as days.
+pub fn parse_duration(arg: &str) -> Result<Duration, String> {
+    let split = arg.find(|c: char| !c.is_ascii_digit()).unwrap_or(arg.len());
+    let (number, unit) = arg.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("'{arg}' does not start with a number"))?;
+    let unit_seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        "" => return Err("a unit (s, m, h, d or w) is required".to_string()),
+        _ => return Err(format!("unknown unit '{unit}', use s, m, h, d or w")),
+    };
+    number
+        .checked_mul(unit_seconds)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("'{arg}' is too long"))
+}
+
+/// Returns the directory bt uses for cached data, following the XDG base
+/// directory specification ($XDG_CACHE_HOME/bt, falling back to ~/.cache/bt).
+pub fn cache_dir() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_CACHE_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".cache"),
+    };
+    Some(base.join("bt"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_duration_accepts_each_unit() {
+        for (arg, seconds) in [
+            ("0s", 0),
+            ("45s", 45),
+            ("90m", 90 * 60),
+            ("12h", 12 * 60 * 60),
+            ("30d", 30 * 24 * 60 * 60),
+            ("2w", 14 * 24 * 60 * 60),
+            ("18446744073709551615s", u64::MAX),
+        ] {
+            assert_eq!(
+                parse_duration(arg),
+                Ok(Duration::from_secs(seconds)),
+                "{arg}"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_duration_requires_known_unit() {
+        for arg in ["30", "0", "007"] {
+            let error = parse_duration(arg).unwrap_err();
+            assert!(
+                error.contains("unit (s, m, h, d or w) is required"),
+                "{arg}"
+            );
+        }
+        for (arg, unit) in [("30y", "y"), ("5 d", " d"), ("1D", "D"), ("2ms", "ms")] {
+            let error = parse_duration(arg).unwrap_err();
+            assert!(
+                error.starts_with(&format!("unknown unit '{unit}'")),
+                "{arg}"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_duration_requires_number() {
+        for arg in ["", "d", "-1d", "+1d", " 1d", "18446744073709551616s"] {
+            let error = parse_duration(arg).unwrap_err();
+            assert!(error.contains("does not start with a number"), "{arg}");
+        }
+    }
+
+    #[test]
+    fn parse_duration_rejects_overflow() {
+        assert!(parse_duration("30500568904943w").is_ok());
+        for arg in ["30500568904944w", "18446744073709551615m"] {
+            assert_eq!(parse_duration(arg), Err(format!("'{arg}' is too long")));
+        }
+    }
+
+    #[test]
+    fn format_utc_formats_known_dates() {
+        for (time, expected) in [
+            (0, "1970-01-01 00:00:00 UTC"),
+            (946684799, "1999-12-31 23:59:59 UTC"),
+            (951825600, "2000-02-29 12:00:00 UTC"),
+            (1704067199, "2023-12-31 23:59:59 UTC"),
+            (1709164800, "2024-02-29 00:00:00 UTC"),
+            (1709251199, "2024-02-29 23:59:59 UTC"),
+            (1715953020, "2024-05-17 13:37:00 UTC"),
+            // 2100 is no leap year
+            (4107542400, "2100-03-01 00:00:00 UTC"),
+        ] {
+            assert_eq!(format_utc(time), expected, "{time}");
+        }
+    }
 }
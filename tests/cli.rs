@@ -0,0 +1,97 @@
+// vim: cc=81
+use serde_json::Value;
+use std::{
+    path::PathBuf,
+    process::{Command, Output},
+};
+
+/// Runs bt with args using the mock backend and tests/fixture.toml. The
+/// index, history and config are kept in a directory of the test, so tests
+/// do not share them with each other or the user.
+fn bt(test: &str, args: &[&str]) -> Output {
+    let home = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(test);
+    let fixture = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixture.toml");
+    Command::new(env!("CARGO_BIN_EXE_bt"))
+        .args(args)
+        .env("BT_BACKEND", "mock")
+        .env("BT_MOCK_FIXTURE", fixture)
+        .env("XDG_CONFIG_HOME", &home)
+        .env("XDG_CACHE_HOME", &home)
+        .env("XDG_STATE_HOME", &home)
+        .env_remove("NO_COLOR")
+        .output()
+        .expect("bt should run")
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn json(output: &Output) -> Value {
+    serde_json::from_slice(&output.stdout).expect("stdout should be JSON")
+}
+
+#[test]
+fn list_prints_paired_devices() {
+    let output = bt("list", &["list"]);
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = stdout(&output);
+    for name in ["Headset 70%", "Speaker", "Mouse"] {
+        assert!(stdout.contains(name), "{name} missing in {stdout:?}");
+    }
+}
+
+#[test]
+fn list_json() {
+    let output = bt("list_json", &["--json", "list"]);
+    assert_eq!(output.status.code(), Some(0));
+    let devices = json(&output);
+    let devices = devices.as_array().expect("list --json should be an array");
+    assert_eq!(devices.len(), 3);
+    assert_eq!(devices[0]["name"], "Headset");
+    assert_eq!(devices[0]["address"], "00:11:22:33:44:55");
+    assert_eq!(devices[0]["connected"], true);
+    assert_eq!(devices[0]["battery"], 70);
+    assert_eq!(devices[1]["connected"], false);
+}
+
+#[test]
+fn connect() {
+    let output = bt("connect", &["connect", "Speaker"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stdout(&output).contains("Speaker connected."));
+}
+
+#[test]
+fn connect_json() {
+    let output = bt("connect_json", &["--json", "connect", "Speaker"]);
+    assert_eq!(output.status.code(), Some(0));
+    let result = json(&output);
+    assert_eq!(result["count"], 1);
+    assert_eq!(result["devices"][0]["name"], "Speaker");
+    assert_eq!(result["devices"][0]["connected"], true);
+}
+
+#[test]
+fn connect_failure() {
+    let output = bt("connect_failure", &["connect", "Mouse"]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Mouse: br-connection-page-timeout"));
+}
+
+#[test]
+fn connect_partial_failure() {
+    let output = bt("connect_partial_failure", &["connect", "Speaker", "Mouse"]);
+    assert_eq!(output.status.code(), Some(4));
+}
+
+#[test]
+fn no_match() {
+    for args in [&["info", "Keyboard"][..], &["connect", "Keyboard"]] {
+        let output = bt("no_match", args);
+        assert_eq!(output.status.code(), Some(2), "bt {args:?}");
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("No devices match 'Keyboard'."));
+    }
+}